@@ -0,0 +1,52 @@
+//! Compares decoding a frame signal by signal with [parse_signal] against a
+//! precompiled [MessageDecoder].
+//!
+//! Run with `cargo bench -p backend`
+
+use std::fmt::Write;
+use std::hint::black_box;
+
+use backend::{load_dbc_from_bytes, multiplexor_values, parse_signal, tree_dbc::Message, MessageDecoder};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+/// 64 byte CAN FD message with 64 signals of 8 bits, alternating Intel and
+/// Motorola byte order, scaled numbers and enums with 32 value descriptions
+fn wide_message() -> Message {
+    let mut dbc = String::from("VERSION \"\"\n\nNS_ :\n\nBS_:\n\nBU_: ECU\n\nBO_ 256 Wide: 64 ECU\n");
+    for idx in 0..64 {
+        let (start, order) = match idx % 2 {
+            0 => (idx * 8, 1),
+            _ => (idx * 8 + 7, 0),
+        };
+        writeln!(dbc, " SG_ Signal{} : {}|8@{}+ (0.5,-20) [-20|107.5] \"unit\" ECU", idx, start, order).unwrap();
+    }
+    dbc.push('\n');
+    for idx in (0..64).step_by(4) {
+        write!(dbc, "VAL_ 256 Signal{}", idx).unwrap();
+        for value in 0..32 {
+            write!(dbc, " {} \"State{}\"", value, value).unwrap();
+        }
+        dbc.push_str(" ;\n");
+    }
+    let dbc = load_dbc_from_bytes(dbc.as_bytes()).expect("Benchmark DBC is valid");
+    dbc.message_by_name("Wide").expect("Benchmark DBC has the message").clone()
+}
+
+fn decode(c: &mut Criterion) {
+    let msg = wide_message();
+    let payload: Vec<u8> = (0..64u8).map(|b| b.wrapping_mul(37)).collect();
+    let decoder = MessageDecoder::new(&msg);
+
+    let mut group = c.benchmark_group("decode_64_signals");
+    group.bench_function("parse_signal", |b| b.iter(|| {
+        let payload = black_box(&payload);
+        let mux_values = multiplexor_values(&msg, payload);
+        msg.active_signals(&mux_values).into_iter().map(|s| parse_signal(s, payload)).collect::<Vec<_>>()
+    }));
+    group.bench_function("message_decoder", |b| b.iter(|| decoder.decode(black_box(&payload))));
+    group.bench_function("compile_decoder", |b| b.iter(|| MessageDecoder::new(black_box(&msg))));
+    group.finish();
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
@@ -0,0 +1,892 @@
+//! Loader for AUTOSAR (4.x) System Description ARXML files.
+//!
+//! The communication matrix of a single CAN cluster is converted into the
+//! same [TreeDbc] model produced by [crate::load_dbc_from_bytes]:
+//!
+//! * `CAN-FRAME-TRIGGERING` -> [Message], with the CAN ID and addressing mode
+//! * `I-SIGNAL-I-PDU` / `MULTIPLEXED-I-PDU` mapped into the frame -> [Signal]s
+//! * `COMPU-METHOD` -> scaling ([SignalType::Linear]) or text tables ([SignalType::Enum])
+//! * `ECU-INSTANCE` frame ports -> sending [Ecu] and signal receivers
+//!
+//! Cycle times and signal init values are stored as the `GenMsgCycleTime` and
+//! `GenSigStartValue` attributes, so the result can be written out as a DBC.
+
+use std::collections::HashMap;
+
+use roxmltree::{Document, Node};
+
+use crate::{
+    tree_dbc::{
+        AttributeValue, Attributes, ByteOrder, CanId, Message, MuxCondition, Multiplexing, Signal, SignalType, TreeDbc,
+        ValueType,
+    },
+    add_loader_attribute_definitions, ecu_entry, physical_range, CanResult, CanViewError, NULL_SENDER,
+};
+
+/// Text tables with ranges larger than this are only given a label for their lower limit
+const MAX_TEXT_TABLE_RANGE: u64 = 64;
+
+fn err<T>(msg: String) -> CanResult<T> {
+    Err(CanViewError::ArxmlError(msg))
+}
+
+fn child<'a, 'i>(node: Node<'a, 'i>, tag: &str) -> Option<Node<'a, 'i>> {
+    node.children().find(|c| c.is_element() && c.tag_name().name() == tag)
+}
+
+fn children<'a, 'i: 'a>(node: Node<'a, 'i>, tag: &'a str) -> impl Iterator<Item = Node<'a, 'i>> + 'a {
+    node.children().filter(move |c| c.is_element() && c.tag_name().name() == tag)
+}
+
+/// Follows a path of child elements, E.g. `["COMPU-SCALES", "COMPU-SCALE"]`
+fn path<'a, 'i>(node: Node<'a, 'i>, tags: &[&str]) -> Option<Node<'a, 'i>> {
+    tags.iter().try_fold(node, |n, tag| child(n, tag))
+}
+
+/// All descendant elements with a tag name
+fn find_all<'a, 'i>(node: Node<'a, 'i>, tag: &str) -> Vec<Node<'a, 'i>> {
+    node.descendants().filter(|d| d.is_element() && d.tag_name().name() == tag).collect()
+}
+
+fn text<'a>(node: Node<'a, '_>, tag: &str) -> Option<&'a str> {
+    child(node, tag).and_then(|c| c.text()).map(|t| t.trim())
+}
+
+fn short_name<'a>(node: Node<'a, '_>) -> Option<&'a str> {
+    text(node, "SHORT-NAME")
+}
+
+/// Parses an AUTOSAR integer, which may be written in decimal, hex (0x), octal (0) or binary (0b)
+fn parse_int(s: &str) -> Option<i64> {
+    let s = s.trim();
+    let (neg, s) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let v = if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(bin) = s.strip_prefix("0b").or_else(|| s.strip_prefix("0B")) {
+        i64::from_str_radix(bin, 2).ok()?
+    } else if s.len() > 1 && s.starts_with('0') && s.chars().all(|c| c.is_ascii_digit()) {
+        i64::from_str_radix(&s[1..], 8).ok()?
+    } else {
+        s.parse::<i64>().ok().or_else(|| s.parse::<f64>().ok().filter(|f| f.fract() == 0.0).map(|f| f as i64))?
+    };
+    Some(if neg { -v } else { v })
+}
+
+fn parse_float(s: &str) -> Option<f64> {
+    s.trim().parse::<f64>().ok().or_else(|| parse_int(s).map(|i| i as f64))
+}
+
+fn int_text(node: Node, tag: &str) -> Option<i64> {
+    text(node, tag).and_then(parse_int)
+}
+
+/// Looks up elements by their AUTOSAR reference path (`/Package/Element`)
+struct ArxmlIndex<'a, 'i> {
+    by_path: HashMap<String, Vec<Node<'a, 'i>>>,
+}
+
+impl<'a, 'i> ArxmlIndex<'a, 'i> {
+    fn new(doc: &'a Document<'i>) -> Self {
+        let mut by_path = HashMap::new();
+        for node in doc.descendants().filter(|n| n.is_element()) {
+            if let Some(name) = short_name(node) {
+                let mut parts = vec![name];
+                for anc in node.ancestors().skip(1).filter(|a| a.is_element()) {
+                    if let Some(n) = short_name(anc) {
+                        parts.push(n);
+                    }
+                }
+                parts.reverse();
+                by_path.entry(format!("/{}", parts.join("/"))).or_insert_with(Vec::new).push(node);
+            }
+        }
+        Self { by_path }
+    }
+
+    /// Resolves a reference element, using its `DEST` attribute to pick
+    /// between elements sharing a path
+    fn resolve(&self, reference: Node<'a, 'i>) -> Option<Node<'a, 'i>> {
+        let candidates = self.by_path.get(reference.text()?.trim())?;
+        match reference.attribute("DEST") {
+            Some(dest) => candidates.iter().find(|c| c.tag_name().name() == dest).or(candidates.first()).copied(),
+            None => candidates.first().copied(),
+        }
+    }
+
+    /// Resolves the reference stored in the child element `ref_tag` of `node`
+    fn follow(&self, node: Node<'a, 'i>, ref_tag: &str) -> Option<Node<'a, 'i>> {
+        child(node, ref_tag).and_then(|r| self.resolve(r))
+    }
+}
+
+/// Lists the names of all CAN clusters in an ARXML file
+pub fn arxml_can_clusters(bytes: &[u8]) -> CanResult<Vec<String>> {
+    let s = std::str::from_utf8(bytes).map_err(|e| CanViewError::ArxmlError(format!("ARXML is not valid UTF-8: {}", e)))?;
+    let doc = Document::parse(s).map_err(|e| CanViewError::ArxmlError(format!("Invalid XML: {}", e)))?;
+    Ok(find_all(doc.root(), "CAN-CLUSTER").into_iter().filter_map(short_name).map(String::from).collect())
+}
+
+/// Loads the CAN cluster named `cluster` from an ARXML file. If `cluster` is `None`
+/// the file must contain exactly one CAN cluster.
+pub fn load_arxml_from_bytes(bytes: &[u8], cluster: Option<&str>) -> CanResult<TreeDbc> {
+    let s = std::str::from_utf8(bytes).map_err(|e| CanViewError::ArxmlError(format!("ARXML is not valid UTF-8: {}", e)))?;
+    let doc = Document::parse(s).map_err(|e| CanViewError::ArxmlError(format!("Invalid XML: {}", e)))?;
+    let clusters = find_all(doc.root(), "CAN-CLUSTER");
+    let cluster_node = match cluster {
+        Some(name) => match clusters.iter().find(|c| short_name(**c) == Some(name)) {
+            Some(c) => *c,
+            None => return err(format!("No CAN cluster named {}", name)),
+        },
+        None => match clusters.as_slice() {
+            [c] => *c,
+            [] => return err("ARXML contains no CAN clusters".into()),
+            _ => {
+                let names: Vec<&str> = clusters.iter().filter_map(|c| short_name(*c)).collect();
+                return err(format!("ARXML contains multiple CAN clusters, select one of: {}", names.join(", ")))
+            }
+        },
+    };
+    ArxmlLoader { index: ArxmlIndex::new(&doc) }.load_cluster(cluster_node)
+}
+
+struct ArxmlLoader<'a, 'i> {
+    index: ArxmlIndex<'a, 'i>,
+}
+
+impl<'a, 'i> ArxmlLoader<'a, 'i> {
+    fn load_cluster(&self, cluster: Node<'a, 'i>) -> CanResult<TreeDbc> {
+        let mut dbc = TreeDbc::default();
+
+        // ECUs connected to the cluster
+        for connector in find_all(cluster, "COMMUNICATION-CONNECTOR-REF") {
+            if let Some(ecu) = self.index.resolve(connector).and_then(|c| self.ecu_of(c)) {
+                ecu_entry(&mut dbc, ecu);
+            }
+        }
+
+        for triggering in find_all(cluster, "CAN-FRAME-TRIGGERING") {
+            let (mut msg, sender, receivers) = self.load_frame_triggering(triggering)?;
+            for signal in msg.signals.iter_mut() {
+                signal.receivers = receivers.clone();
+            }
+            for r in &receivers {
+                ecu_entry(&mut dbc, r);
+            }
+            let ecu_idx = ecu_entry(&mut dbc, sender.as_deref().unwrap_or(NULL_SENDER));
+            dbc.ecus[ecu_idx].messages.push(msg);
+        }
+
+        add_loader_attribute_definitions(&mut dbc);
+        dbc.reindex();
+        Ok(dbc)
+    }
+
+    /// Name of the ECU-INSTANCE containing a node (A connector or frame port)
+    fn ecu_of(&self, node: Node<'a, 'i>) -> Option<&'a str> {
+        node.ancestors().find(|a| a.tag_name().name() == "ECU-INSTANCE").and_then(short_name)
+    }
+
+    fn load_frame_triggering(&self, triggering: Node<'a, 'i>) -> CanResult<(Message, Option<String>, Vec<String>)> {
+        let trig_name = short_name(triggering).unwrap_or_default();
+        let raw_id = match int_text(triggering, "IDENTIFIER") {
+            Some(id) if id >= 0 => id as u32,
+            _ => return err(format!("Frame triggering {} has no valid IDENTIFIER", trig_name)),
+        };
+        let extended = text(triggering, "CAN-ADDRESSING-MODE") == Some("EXTENDED");
+        let frame = match self.index.follow(triggering, "FRAME-REF") {
+            Some(f) => f,
+            None => return err(format!("Frame triggering {} references an unknown frame", trig_name)),
+        };
+        let frame_name = short_name(frame).unwrap_or(trig_name);
+
+        let mut msg = Message {
+            id: CanId::new(raw_id, extended),
+            name: frame_name.to_string(),
+            comment: description(frame),
+            length_bytes: int_text(frame, "FRAME-LENGTH").unwrap_or(8).max(0) as u64,
+            ..Default::default()
+        };
+
+        let mut cycle_time: Option<i64> = None;
+        for mapping in find_all(frame, "PDU-TO-FRAME-MAPPING") {
+            let pdu = match self.index.follow(mapping, "PDU-REF") {
+                Some(p) => p,
+                None => continue,
+            };
+            // Only PDUs on byte boundaries keep both Intel and Motorola bit numbering valid
+            let offset = match int_text(mapping, "START-POSITION").unwrap_or(0) {
+                o if o >= 0 && o % 8 == 0 => o as u64,
+                o => return err(format!("PDU {} of frame {} starts at bit {}, which is not on a byte boundary",
+                    short_name(pdu).unwrap_or_default(), frame_name, o)),
+            };
+            self.load_pdu(pdu, offset, &mut msg)?;
+            if let Some(cycle) = pdu_cycle_time_ms(pdu) {
+                cycle_time = Some(cycle_time.map_or(cycle, |c| c.min(cycle)));
+            }
+        }
+        // Frames have no timing of their own, they are sent as often as their fastest PDU
+        if let Some(cycle) = cycle_time {
+            msg.attributes.insert("GenMsgCycleTime".into(), AttributeValue::Int(cycle));
+        }
+
+        let mut sender = None;
+        let mut receivers = Vec::new();
+        for port_ref in find_all(triggering, "FRAME-PORT-REF") {
+            let port = match self.index.resolve(port_ref) {
+                Some(p) => p,
+                None => continue,
+            };
+            if let Some(ecu) = self.ecu_of(port) {
+                match text(port, "COMMUNICATION-DIRECTION") {
+                    Some("OUT") => sender = Some(ecu.to_string()),
+                    Some("IN") if !receivers.iter().any(|r| r == ecu) => receivers.push(ecu.to_string()),
+                    _ => {}
+                }
+            }
+        }
+        Ok((msg, sender, receivers))
+    }
+
+    fn load_pdu(&self, pdu: Node<'a, 'i>, offset_bits: u64, msg: &mut Message) -> CanResult<()> {
+        match pdu.tag_name().name() {
+            "MULTIPLEXED-I-PDU" => self.load_multiplexed_pdu(pdu, offset_bits, msg),
+            _ => {
+                for mapping in find_all(pdu, "I-SIGNAL-TO-I-PDU-MAPPING") {
+                    if let Some(signal) = self.load_signal(mapping, offset_bits)? {
+                        msg.signals.push(signal);
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// The selector field becomes the multiplexor, and each dynamic part alternative's signals
+    /// are multiplexed on its selector code
+    fn load_multiplexed_pdu(&self, pdu: Node<'a, 'i>, offset_bits: u64, msg: &mut Message) -> CanResult<()> {
+        let name = short_name(pdu).unwrap_or_default();
+        let mux_name = format!("{}_Selector", name);
+        let length = int_text(pdu, "SELECTOR-FIELD-LENGTH").unwrap_or(0).max(0) as u64;
+        let start = int_text(pdu, "SELECTOR-FIELD-START-POSITION").unwrap_or(0).max(0) as u64;
+        let order = byte_order(text(pdu, "SELECTOR-FIELD-BYTE-ORDER"));
+        if length == 0 {
+            return err(format!("Multiplexed PDU {} has no selector field", name))
+        }
+        if length > 64 {
+            return err(format!("Selector field of multiplexed PDU {} is longer than 64 bits", name))
+        }
+        let start_bit = match dbc_start_bit(start, length, order) {
+            Some(b) => b,
+            None => return err(format!("Selector field of multiplexed PDU {} runs past the start of the PDU", name)),
+        };
+        msg.signals.push(Signal {
+            name: mux_name.clone(),
+            signal_type: SignalType::Linear { multi: 1.0, offset: 0.0 },
+            order,
+            start_bit: start_bit + offset_bits,
+            length_bits: length,
+            min: 0.0,
+            max: ((1u128 << length) - 1) as f64,
+            multiplexing: Multiplexing::Multiplexor,
+            ..Default::default()
+        });
+
+        for static_ref in find_all(pdu, "STATIC-PART") {
+            if let Some(part) = self.index.follow(static_ref, "I-PDU-REF") {
+                self.load_pdu(part, offset_bits, msg)?;
+            }
+        }
+        for alternative in find_all(pdu, "DYNAMIC-PART-ALTERNATIVE") {
+            let code = int_text(alternative, "SELECTOR-FIELD-CODE").unwrap_or(0).max(0) as u64;
+            let part = match self.index.follow(alternative, "I-PDU-REF") {
+                Some(p) => p,
+                None => continue,
+            };
+            let first = msg.signals.len();
+            self.load_pdu(part, offset_bits, msg)?;
+            for signal in &mut msg.signals[first..] {
+                if signal.multiplexing == Multiplexing::Plain {
+                    signal.multiplexing = Multiplexing::Multiplexed(MuxCondition {
+                        multiplexor: mux_name.clone(),
+                        ranges: vec![(code, code)],
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn load_signal(&self, mapping: Node<'a, 'i>, offset_bits: u64) -> CanResult<Option<Signal>> {
+        // Signal group mappings have no layout of their own, their members are mapped separately
+        let isignal = match self.index.follow(mapping, "I-SIGNAL-REF") {
+            Some(s) => s,
+            None => return Ok(None),
+        };
+        let name = short_name(isignal).or_else(|| short_name(mapping)).unwrap_or_default().to_string();
+        let length = match int_text(isignal, "LENGTH") {
+            Some(l) if l > 0 => l as u64,
+            _ => return err(format!("I-Signal {} has no valid LENGTH", name)),
+        };
+        let start = match int_text(mapping, "START-POSITION") {
+            Some(s) if s >= 0 => s as u64,
+            _ => return err(format!("I-Signal mapping of {} has no valid START-POSITION", name)),
+        };
+        let order = byte_order(text(mapping, "PACKING-BYTE-ORDER"));
+        let start_bit = match dbc_start_bit(start, length, order) {
+            Some(b) => b,
+            None => return err(format!("Motorola signal {} runs past the start of its PDU", name)),
+        };
+        let system_signal = self.index.follow(isignal, "SYSTEM-SIGNAL-REF");
+
+        // Coding of the signal on the bus, falling back to the physical properties of the system signal
+        let net_props = path(isignal, &["NETWORK-REPRESENTATION-PROPS", "SW-DATA-DEF-PROPS-VARIANTS", "SW-DATA-DEF-PROPS-CONDITIONAL"]);
+        let phys_props = system_signal
+            .and_then(|s| path(s, &["PHYSICAL-PROPS", "SW-DATA-DEF-PROPS-VARIANTS", "SW-DATA-DEF-PROPS-CONDITIONAL"]));
+        let compu_method = net_props
+            .and_then(|p| self.index.follow(p, "COMPU-METHOD-REF"))
+            .or_else(|| phys_props.and_then(|p| self.index.follow(p, "COMPU-METHOD-REF")));
+        let base_type = net_props.and_then(|p| self.index.follow(p, "BASE-TYPE-REF"));
+        let unit = compu_method
+            .and_then(|c| self.index.follow(c, "UNIT-REF"))
+            .or_else(|| phys_props.and_then(|p| self.index.follow(p, "UNIT-REF")))
+            .and_then(|u| text(u, "DISPLAY-NAME").or_else(|| short_name(u)))
+            .unwrap_or_default();
+
+        let encoding = base_type.and_then(|b| text(b, "BASE-TYPE-ENCODING")).unwrap_or_default();
+        let value_type = match (encoding, length) {
+            ("IEEE754", 32) => ValueType::Float32,
+            ("IEEE754", 64) => ValueType::Float64,
+            _ => ValueType::Integer,
+        };
+        let signed = encoding == "2C" || value_type != ValueType::Integer;
+        let signal_type = match value_type {
+            ValueType::Integer => compu_signal_type(compu_method, length),
+            _ => linear_scaling(compu_method).map(|(multi, offset)| SignalType::Linear { multi, offset })
+                .unwrap_or(SignalType::Linear { multi: 1.0, offset: 0.0 }),
+        };
+        let (min, max) = match value_type {
+            ValueType::Integer => physical_range(&signal_type, length, signed),
+            // Floats have no useful range
+            _ => (0.0, 0.0),
+        };
+
+        let mut attributes = Attributes::new();
+        if let Some(init) = isignal.descendants().find(|d| d.tag_name().name() == "INIT-VALUE")
+            .and_then(|v| v.descendants().find(|d| d.tag_name().name() == "VALUE"))
+            .and_then(|v| v.text())
+            .and_then(parse_int)
+        {
+            attributes.insert("GenSigStartValue".into(), AttributeValue::Int(init));
+        }
+
+        Ok(Some(Signal {
+            name,
+            comment: system_signal.and_then(description),
+            signal_type,
+            order,
+            start_bit: start_bit + offset_bits,
+            length_bits: length,
+            unit: unit.to_string(),
+            min,
+            max,
+            signed,
+            value_type,
+            attributes,
+            ..Default::default()
+        }))
+    }
+}
+
+fn description(node: Node) -> Option<String> {
+    child(node, "DESC")
+        .and_then(|d| child(d, "L-2"))
+        .and_then(|l| l.text())
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+}
+
+fn byte_order(packing: Option<&str>) -> ByteOrder {
+    match packing {
+        Some("MOST-SIGNIFICANT-BYTE-FIRST") => ByteOrder::BigEndian,
+        _ => ByteOrder::LittleEndian,
+    }
+}
+
+/// AUTOSAR gives the position of the least significant bit for both byte orders,
+/// whereas DBC gives the most significant bit for Motorola signals.
+/// Returns `None` for a Motorola signal whose MSB would be before the first byte.
+fn dbc_start_bit(start_position: u64, length: u64, order: ByteOrder) -> Option<u64> {
+    match order {
+        ByteOrder::LittleEndian => Some(start_position),
+        ByteOrder::BigEndian => {
+            // Walk from the LSB towards the MSB in sawtooth order
+            let mut pos = start_position;
+            for _ in 1..length {
+                if pos & 0x07 == 7 {
+                    pos = pos.checked_sub(15)?;
+                } else {
+                    pos += 1;
+                }
+            }
+            Some(pos)
+        }
+    }
+}
+
+/// Cyclic transmission period of a PDU in milliseconds
+fn pdu_cycle_time_ms(pdu: Node) -> Option<i64> {
+    let period = pdu.descendants().find(|d| d.tag_name().name() == "CYCLIC-TIMING")
+        .and_then(|c| path(c, &["TIME-PERIOD", "VALUE"]))
+        .and_then(|v| v.text())
+        .and_then(parse_float)?;
+    // Time periods are given in seconds
+    Some((period * 1000.0).round() as i64)
+}
+
+/// Factor and offset of the first linear scale of a computation method
+fn linear_scaling(compu_method: Option<Node>) -> Option<(f64, f64)> {
+    let scales = path(compu_method?, &["COMPU-INTERNAL-TO-PHYS", "COMPU-SCALES"])?;
+    children(scales, "COMPU-SCALE").find_map(|scale| {
+        let coeffs = child(scale, "COMPU-RATIONAL-COEFFS")?;
+        let num: Vec<f64> = children(child(coeffs, "COMPU-NUMERATOR")?, "V").filter_map(|v| v.text().and_then(parse_float)).collect();
+        let denom = child(coeffs, "COMPU-DENOMINATOR")
+            .and_then(|d| child(d, "V"))
+            .and_then(|v| v.text())
+            .and_then(parse_float)
+            .unwrap_or(1.0);
+        let offset = *num.first()?;
+        let factor = num.get(1).copied().unwrap_or(0.0);
+        Some((factor / denom, offset / denom))
+    })
+}
+
+/// Converts a computation method into a signal type, using the same rules as for DBC
+/// signals: text tables make an enum with the method's linear scaling, and unscaled
+/// 1 bit signals are booleans
+fn compu_signal_type(compu_method: Option<Node>, length: u64) -> SignalType {
+    let (multi, offset) = linear_scaling(compu_method).unwrap_or((1.0, 0.0));
+    if length == 1 && multi == 1.0 && offset == 0.0 {
+        return SignalType::Bool
+    }
+    let mut table = Vec::new();
+    if let Some(scales) = compu_method.and_then(|c| path(c, &["COMPU-INTERNAL-TO-PHYS", "COMPU-SCALES"])) {
+        for scale in children(scales, "COMPU-SCALE") {
+            let label = match child(scale, "COMPU-CONST").and_then(|c| text(c, "VT")) {
+                Some(l) => l,
+                None => continue,
+            };
+            let lower = match int_text(scale, "LOWER-LIMIT") {
+                Some(l) => l,
+                None => continue,
+            };
+            let upper = int_text(scale, "UPPER-LIMIT").unwrap_or(lower);
+            if upper.checked_sub(lower).is_some_and(|r| (0..=MAX_TEXT_TABLE_RANGE as i64).contains(&r)) {
+                table.extend((lower..=upper).map(|v| (v, label.to_string())));
+            } else {
+                table.push((lower, label.to_string()));
+            }
+        }
+    }
+    if !table.is_empty() {
+        table.sort_by_key(|e| e.0);
+        table.dedup_by_key(|e| e.0);
+        return SignalType::Enum { entries: table, multi, offset }
+    }
+    SignalType::Linear { multi, offset }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARXML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<AUTOSAR xmlns="http://autosar.org/schema/r4.0">
+  <AR-PACKAGES>
+    <AR-PACKAGE>
+      <SHORT-NAME>Sys</SHORT-NAME>
+      <ELEMENTS>
+        <CAN-CLUSTER>
+          <SHORT-NAME>Bus</SHORT-NAME>
+          <CAN-CLUSTER-VARIANTS>
+            <CAN-CLUSTER-CONDITIONAL>
+              <PHYSICAL-CHANNELS>
+                <CAN-PHYSICAL-CHANNEL>
+                  <SHORT-NAME>Channel</SHORT-NAME>
+                  <COMM-CONNECTORS>
+                    <COMMUNICATION-CONNECTOR-REF-CONDITIONAL>
+                      <COMMUNICATION-CONNECTOR-REF DEST="CAN-COMMUNICATION-CONNECTOR">/Sys/Engine/Conn</COMMUNICATION-CONNECTOR-REF>
+                    </COMMUNICATION-CONNECTOR-REF-CONDITIONAL>
+                    <COMMUNICATION-CONNECTOR-REF-CONDITIONAL>
+                      <COMMUNICATION-CONNECTOR-REF DEST="CAN-COMMUNICATION-CONNECTOR">/Sys/Dash/Conn</COMMUNICATION-CONNECTOR-REF>
+                    </COMMUNICATION-CONNECTOR-REF-CONDITIONAL>
+                  </COMM-CONNECTORS>
+                  <FRAME-TRIGGERINGS>
+                    <CAN-FRAME-TRIGGERING>
+                      <SHORT-NAME>EngineStatusTrig</SHORT-NAME>
+                      <FRAME-PORT-REFS>
+                        <FRAME-PORT-REF DEST="FRAME-PORT">/Sys/Engine/Conn/Out</FRAME-PORT-REF>
+                        <FRAME-PORT-REF DEST="FRAME-PORT">/Sys/Dash/Conn/In</FRAME-PORT-REF>
+                      </FRAME-PORT-REFS>
+                      <FRAME-REF DEST="CAN-FRAME">/Sys/EngineStatus</FRAME-REF>
+                      <CAN-ADDRESSING-MODE>STANDARD</CAN-ADDRESSING-MODE>
+                      <IDENTIFIER>0x100</IDENTIFIER>
+                    </CAN-FRAME-TRIGGERING>
+                    <CAN-FRAME-TRIGGERING>
+                      <SHORT-NAME>DiagTrig</SHORT-NAME>
+                      <FRAME-PORT-REFS>
+                        <FRAME-PORT-REF DEST="FRAME-PORT">/Sys/Engine/Conn/Out</FRAME-PORT-REF>
+                      </FRAME-PORT-REFS>
+                      <FRAME-REF DEST="CAN-FRAME">/Sys/Diag</FRAME-REF>
+                      <CAN-ADDRESSING-MODE>EXTENDED</CAN-ADDRESSING-MODE>
+                      <IDENTIFIER>0x18FF0001</IDENTIFIER>
+                    </CAN-FRAME-TRIGGERING>
+                  </FRAME-TRIGGERINGS>
+                </CAN-PHYSICAL-CHANNEL>
+              </PHYSICAL-CHANNELS>
+            </CAN-CLUSTER-CONDITIONAL>
+          </CAN-CLUSTER-VARIANTS>
+        </CAN-CLUSTER>
+        <ECU-INSTANCE>
+          <SHORT-NAME>Engine</SHORT-NAME>
+          <CONNECTORS>
+            <CAN-COMMUNICATION-CONNECTOR>
+              <SHORT-NAME>Conn</SHORT-NAME>
+              <ECU-COMM-PORT-INSTANCES>
+                <FRAME-PORT>
+                  <SHORT-NAME>Out</SHORT-NAME>
+                  <COMMUNICATION-DIRECTION>OUT</COMMUNICATION-DIRECTION>
+                </FRAME-PORT>
+              </ECU-COMM-PORT-INSTANCES>
+            </CAN-COMMUNICATION-CONNECTOR>
+          </CONNECTORS>
+        </ECU-INSTANCE>
+        <ECU-INSTANCE>
+          <SHORT-NAME>Dash</SHORT-NAME>
+          <CONNECTORS>
+            <CAN-COMMUNICATION-CONNECTOR>
+              <SHORT-NAME>Conn</SHORT-NAME>
+              <ECU-COMM-PORT-INSTANCES>
+                <FRAME-PORT>
+                  <SHORT-NAME>In</SHORT-NAME>
+                  <COMMUNICATION-DIRECTION>IN</COMMUNICATION-DIRECTION>
+                </FRAME-PORT>
+              </ECU-COMM-PORT-INSTANCES>
+            </CAN-COMMUNICATION-CONNECTOR>
+          </CONNECTORS>
+        </ECU-INSTANCE>
+        <CAN-FRAME>
+          <SHORT-NAME>EngineStatus</SHORT-NAME>
+          <FRAME-LENGTH>8</FRAME-LENGTH>
+          <PDU-TO-FRAME-MAPPINGS>
+            <PDU-TO-FRAME-MAPPING>
+              <SHORT-NAME>EngineStatusMapping</SHORT-NAME>
+              <PDU-REF DEST="I-SIGNAL-I-PDU">/Sys/EngineStatusPdu</PDU-REF>
+              <START-POSITION>0</START-POSITION>
+            </PDU-TO-FRAME-MAPPING>
+          </PDU-TO-FRAME-MAPPINGS>
+        </CAN-FRAME>
+        <CAN-FRAME>
+          <SHORT-NAME>Diag</SHORT-NAME>
+          <FRAME-LENGTH>4</FRAME-LENGTH>
+          <PDU-TO-FRAME-MAPPINGS>
+            <PDU-TO-FRAME-MAPPING>
+              <SHORT-NAME>DiagMapping</SHORT-NAME>
+              <PDU-REF DEST="MULTIPLEXED-I-PDU">/Sys/DiagPdu</PDU-REF>
+              <START-POSITION>0</START-POSITION>
+            </PDU-TO-FRAME-MAPPING>
+          </PDU-TO-FRAME-MAPPINGS>
+        </CAN-FRAME>
+        <I-SIGNAL-I-PDU>
+          <SHORT-NAME>EngineStatusPdu</SHORT-NAME>
+          <I-PDU-TIMING-SPECIFICATIONS>
+            <I-PDU-TIMING>
+              <TRANSMISSION-MODE-DECLARATION>
+                <TRANSMISSION-MODE-TRUE-TIMING>
+                  <CYCLIC-TIMING>
+                    <TIME-PERIOD>
+                      <VALUE>0.1</VALUE>
+                    </TIME-PERIOD>
+                  </CYCLIC-TIMING>
+                </TRANSMISSION-MODE-TRUE-TIMING>
+              </TRANSMISSION-MODE-DECLARATION>
+            </I-PDU-TIMING>
+          </I-PDU-TIMING-SPECIFICATIONS>
+          <I-SIGNAL-TO-PDU-MAPPINGS>
+            <I-SIGNAL-TO-I-PDU-MAPPING>
+              <SHORT-NAME>SpeedMapping</SHORT-NAME>
+              <I-SIGNAL-REF DEST="I-SIGNAL">/Sys/Speed</I-SIGNAL-REF>
+              <PACKING-BYTE-ORDER>MOST-SIGNIFICANT-BYTE-LAST</PACKING-BYTE-ORDER>
+              <START-POSITION>0</START-POSITION>
+            </I-SIGNAL-TO-I-PDU-MAPPING>
+            <I-SIGNAL-TO-I-PDU-MAPPING>
+              <SHORT-NAME>GearMapping</SHORT-NAME>
+              <I-SIGNAL-REF DEST="I-SIGNAL">/Sys/Gear</I-SIGNAL-REF>
+              <PACKING-BYTE-ORDER>MOST-SIGNIFICANT-BYTE-FIRST</PACKING-BYTE-ORDER>
+              <START-POSITION>16</START-POSITION>
+            </I-SIGNAL-TO-I-PDU-MAPPING>
+          </I-SIGNAL-TO-PDU-MAPPINGS>
+        </I-SIGNAL-I-PDU>
+        <MULTIPLEXED-I-PDU>
+          <SHORT-NAME>DiagPdu</SHORT-NAME>
+          <DYNAMIC-PARTS>
+            <DYNAMIC-PART>
+              <DYNAMIC-PART-ALTERNATIVES>
+                <DYNAMIC-PART-ALTERNATIVE>
+                  <I-PDU-REF DEST="I-SIGNAL-I-PDU">/Sys/DiagTempPdu</I-PDU-REF>
+                  <SELECTOR-FIELD-CODE>1</SELECTOR-FIELD-CODE>
+                </DYNAMIC-PART-ALTERNATIVE>
+                <DYNAMIC-PART-ALTERNATIVE>
+                  <I-PDU-REF DEST="I-SIGNAL-I-PDU">/Sys/DiagVoltPdu</I-PDU-REF>
+                  <SELECTOR-FIELD-CODE>2</SELECTOR-FIELD-CODE>
+                </DYNAMIC-PART-ALTERNATIVE>
+              </DYNAMIC-PART-ALTERNATIVES>
+            </DYNAMIC-PART>
+          </DYNAMIC-PARTS>
+          <SELECTOR-FIELD-BYTE-ORDER>MOST-SIGNIFICANT-BYTE-LAST</SELECTOR-FIELD-BYTE-ORDER>
+          <SELECTOR-FIELD-LENGTH>8</SELECTOR-FIELD-LENGTH>
+          <SELECTOR-FIELD-START-POSITION>0</SELECTOR-FIELD-START-POSITION>
+        </MULTIPLEXED-I-PDU>
+        <I-SIGNAL-I-PDU>
+          <SHORT-NAME>DiagTempPdu</SHORT-NAME>
+          <I-SIGNAL-TO-PDU-MAPPINGS>
+            <I-SIGNAL-TO-I-PDU-MAPPING>
+              <SHORT-NAME>DiagTempMapping</SHORT-NAME>
+              <I-SIGNAL-REF DEST="I-SIGNAL">/Sys/DiagTemp</I-SIGNAL-REF>
+              <PACKING-BYTE-ORDER>MOST-SIGNIFICANT-BYTE-LAST</PACKING-BYTE-ORDER>
+              <START-POSITION>8</START-POSITION>
+            </I-SIGNAL-TO-I-PDU-MAPPING>
+          </I-SIGNAL-TO-PDU-MAPPINGS>
+        </I-SIGNAL-I-PDU>
+        <I-SIGNAL-I-PDU>
+          <SHORT-NAME>DiagVoltPdu</SHORT-NAME>
+          <I-SIGNAL-TO-PDU-MAPPINGS>
+            <I-SIGNAL-TO-I-PDU-MAPPING>
+              <SHORT-NAME>DiagVoltMapping</SHORT-NAME>
+              <I-SIGNAL-REF DEST="I-SIGNAL">/Sys/DiagVolt</I-SIGNAL-REF>
+              <PACKING-BYTE-ORDER>MOST-SIGNIFICANT-BYTE-LAST</PACKING-BYTE-ORDER>
+              <START-POSITION>8</START-POSITION>
+            </I-SIGNAL-TO-I-PDU-MAPPING>
+          </I-SIGNAL-TO-PDU-MAPPINGS>
+        </I-SIGNAL-I-PDU>
+        <I-SIGNAL>
+          <SHORT-NAME>Speed</SHORT-NAME>
+          <INIT-VALUE>
+            <NUMERICAL-VALUE-SPECIFICATION>
+              <VALUE>20</VALUE>
+            </NUMERICAL-VALUE-SPECIFICATION>
+          </INIT-VALUE>
+          <LENGTH>16</LENGTH>
+          <NETWORK-REPRESENTATION-PROPS>
+            <SW-DATA-DEF-PROPS-VARIANTS>
+              <SW-DATA-DEF-PROPS-CONDITIONAL>
+                <COMPU-METHOD-REF DEST="COMPU-METHOD">/Sys/SpeedScale</COMPU-METHOD-REF>
+              </SW-DATA-DEF-PROPS-CONDITIONAL>
+            </SW-DATA-DEF-PROPS-VARIANTS>
+          </NETWORK-REPRESENTATION-PROPS>
+          <SYSTEM-SIGNAL-REF DEST="SYSTEM-SIGNAL">/Sys/SpeedSystem</SYSTEM-SIGNAL-REF>
+        </I-SIGNAL>
+        <I-SIGNAL>
+          <SHORT-NAME>Gear</SHORT-NAME>
+          <LENGTH>4</LENGTH>
+          <NETWORK-REPRESENTATION-PROPS>
+            <SW-DATA-DEF-PROPS-VARIANTS>
+              <SW-DATA-DEF-PROPS-CONDITIONAL>
+                <COMPU-METHOD-REF DEST="COMPU-METHOD">/Sys/GearTable</COMPU-METHOD-REF>
+              </SW-DATA-DEF-PROPS-CONDITIONAL>
+            </SW-DATA-DEF-PROPS-VARIANTS>
+          </NETWORK-REPRESENTATION-PROPS>
+        </I-SIGNAL>
+        <I-SIGNAL>
+          <SHORT-NAME>DiagTemp</SHORT-NAME>
+          <LENGTH>8</LENGTH>
+        </I-SIGNAL>
+        <I-SIGNAL>
+          <SHORT-NAME>DiagVolt</SHORT-NAME>
+          <LENGTH>16</LENGTH>
+        </I-SIGNAL>
+        <SYSTEM-SIGNAL>
+          <SHORT-NAME>SpeedSystem</SHORT-NAME>
+          <DESC>
+            <L-2 L="EN">Vehicle speed</L-2>
+          </DESC>
+        </SYSTEM-SIGNAL>
+        <COMPU-METHOD>
+          <SHORT-NAME>SpeedScale</SHORT-NAME>
+          <UNIT-REF DEST="UNIT">/Sys/Kmh</UNIT-REF>
+          <COMPU-INTERNAL-TO-PHYS>
+            <COMPU-SCALES>
+              <COMPU-SCALE>
+                <COMPU-RATIONAL-COEFFS>
+                  <COMPU-NUMERATOR>
+                    <V>-10</V>
+                    <V>0.5</V>
+                  </COMPU-NUMERATOR>
+                  <COMPU-DENOMINATOR>
+                    <V>1</V>
+                  </COMPU-DENOMINATOR>
+                </COMPU-RATIONAL-COEFFS>
+              </COMPU-SCALE>
+            </COMPU-SCALES>
+          </COMPU-INTERNAL-TO-PHYS>
+        </COMPU-METHOD>
+        <COMPU-METHOD>
+          <SHORT-NAME>GearTable</SHORT-NAME>
+          <COMPU-INTERNAL-TO-PHYS>
+            <COMPU-SCALES>
+              <COMPU-SCALE>
+                <LOWER-LIMIT>0</LOWER-LIMIT>
+                <UPPER-LIMIT>0</UPPER-LIMIT>
+                <COMPU-CONST><VT>Park</VT></COMPU-CONST>
+              </COMPU-SCALE>
+              <COMPU-SCALE>
+                <LOWER-LIMIT>1</LOWER-LIMIT>
+                <UPPER-LIMIT>3</UPPER-LIMIT>
+                <COMPU-CONST><VT>Drive</VT></COMPU-CONST>
+              </COMPU-SCALE>
+              <COMPU-SCALE>
+                <LOWER-LIMIT>-0x7FFFFFFFFFFFFFFF</LOWER-LIMIT>
+                <UPPER-LIMIT>0x7FFFFFFFFFFFFFFF</UPPER-LIMIT>
+                <COMPU-CONST><VT>Invalid</VT></COMPU-CONST>
+              </COMPU-SCALE>
+            </COMPU-SCALES>
+          </COMPU-INTERNAL-TO-PHYS>
+        </COMPU-METHOD>
+        <UNIT>
+          <SHORT-NAME>Kmh</SHORT-NAME>
+          <DISPLAY-NAME>km/h</DISPLAY-NAME>
+        </UNIT>
+      </ELEMENTS>
+    </AR-PACKAGE>
+  </AR-PACKAGES>
+</AUTOSAR>
+"#;
+
+    fn signal<'a>(msg: &'a Message, name: &str) -> &'a Signal {
+        msg.signals.iter().find(|s| s.name == name).unwrap()
+    }
+
+    #[test]
+    fn load_cluster() {
+        assert_eq!(arxml_can_clusters(ARXML.as_bytes()).unwrap(), ["Bus"]);
+        let dbc = load_arxml_from_bytes(ARXML.as_bytes(), None).unwrap();
+        let ecus: Vec<(&str, usize)> = dbc.ecus.iter().map(|e| (e.name.as_str(), e.messages.len())).collect();
+        assert_eq!(ecus, [("Engine", 2), ("Dash", 0)]);
+
+        let msg = dbc.message_by_name("EngineStatus").unwrap();
+        assert_eq!((msg.id, msg.length_bytes), (CanId::new(0x100, false), 8));
+        assert_eq!(msg.attributes.get("GenMsgCycleTime"), Some(&AttributeValue::Int(100)));
+
+        let speed = signal(msg, "Speed");
+        assert_eq!((speed.start_bit, speed.length_bits, speed.order), (0, 16, ByteOrder::LittleEndian));
+        assert_eq!(speed.signal_type, SignalType::Linear { multi: 0.5, offset: -10.0 });
+        assert_eq!((speed.unit.as_str(), speed.comment.as_deref()), ("km/h", Some("Vehicle speed")));
+        assert_eq!(speed.receivers, ["Dash"]);
+        assert_eq!(speed.attributes.get("GenSigStartValue"), Some(&AttributeValue::Int(20)));
+
+        // LSB at bit 16, so the MSB is bit 19
+        let gear = signal(msg, "Gear");
+        assert_eq!((gear.start_bit, gear.length_bits, gear.order), (19, 4, ByteOrder::BigEndian));
+        let invalid = (-i64::MAX, "Invalid".to_string());
+        assert_eq!(gear.signal_type.entries(), Some(&vec![
+            invalid,
+            (0, "Park".into()),
+            (1, "Drive".into()),
+            (2, "Drive".into()),
+            (3, "Drive".into()),
+        ]));
+    }
+
+    #[test]
+    fn load_multiplexed_pdu() {
+        let dbc = load_arxml_from_bytes(ARXML.as_bytes(), Some("Bus")).unwrap();
+        let msg = dbc.message_by_name("Diag").unwrap();
+        assert_eq!(msg.id, CanId::new(0x18FF0001, true));
+        let selector = signal(msg, "DiagPdu_Selector");
+        assert_eq!((selector.multiplexing.clone(), selector.length_bits, selector.max), (Multiplexing::Multiplexor, 8, 255.0));
+        for (name, code) in [("DiagTemp", 1), ("DiagVolt", 2)] {
+            assert_eq!(signal(msg, name).multiplexing, Multiplexing::Multiplexed(MuxCondition {
+                multiplexor: "DiagPdu_Selector".into(),
+                ranges: vec![(code, code)],
+            }));
+        }
+    }
+
+    #[test]
+    fn invalid_selector_length() {
+        for length in ["0", "65", "128", "1000"] {
+            let arxml = ARXML.replace("<SELECTOR-FIELD-LENGTH>8<", &format!("<SELECTOR-FIELD-LENGTH>{}<", length));
+            assert!(matches!(load_arxml_from_bytes(arxml.as_bytes(), None), Err(CanViewError::ArxmlError(_))));
+        }
+    }
+
+    #[test]
+    fn unknown_cluster() {
+        assert!(load_arxml_from_bytes(ARXML.as_bytes(), Some("Other")).is_err());
+        assert!(load_arxml_from_bytes(b"<AUTOSAR/>", None).is_err());
+    }
+
+    #[test]
+    fn motorola_start_bit() {
+        assert_eq!(dbc_start_bit(16, 4, ByteOrder::BigEndian), Some(19));
+        // LSB at bit 6 of byte 1, MSB at bit 1 of byte 0
+        assert_eq!(dbc_start_bit(14, 4, ByteOrder::BigEndian), Some(1));
+        assert_eq!(dbc_start_bit(0, 8, ByteOrder::BigEndian), Some(7));
+        assert_eq!(dbc_start_bit(6, 4, ByteOrder::BigEndian), None);
+        assert_eq!(dbc_start_bit(6, 4, ByteOrder::LittleEndian), Some(6));
+
+        let arxml = ARXML.replace("<START-POSITION>16<", "<START-POSITION>6<");
+        match load_arxml_from_bytes(arxml.as_bytes(), None) {
+            Err(CanViewError::ArxmlError(e)) => assert_eq!(e, "Motorola signal Gear runs past the start of its PDU"),
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+        let arxml = ARXML
+            .replace("<SELECTOR-FIELD-BYTE-ORDER>MOST-SIGNIFICANT-BYTE-LAST<", "<SELECTOR-FIELD-BYTE-ORDER>MOST-SIGNIFICANT-BYTE-FIRST<")
+            .replace("<SELECTOR-FIELD-START-POSITION>0<", "<SELECTOR-FIELD-START-POSITION>4<");
+        assert!(matches!(load_arxml_from_bytes(arxml.as_bytes(), None), Err(CanViewError::ArxmlError(_))));
+    }
+
+    /// Maps DiagTempPdu into the EngineStatus frame as well, at the given bit
+    fn with_second_pdu(start_position: u32) -> String {
+        ARXML.replacen("</PDU-TO-FRAME-MAPPING>", &format!(r#"</PDU-TO-FRAME-MAPPING>
+            <PDU-TO-FRAME-MAPPING>
+              <SHORT-NAME>TempMapping</SHORT-NAME>
+              <PDU-REF DEST="I-SIGNAL-I-PDU">/Sys/DiagTempPdu</PDU-REF>
+              <START-POSITION>{}</START-POSITION>
+            </PDU-TO-FRAME-MAPPING>"#, start_position), 1)
+    }
+
+    #[test]
+    fn cycle_time_of_fastest_pdu() {
+        // The second PDU is sent less often than the first
+        let arxml = with_second_pdu(32).replace("<SHORT-NAME>DiagTempPdu</SHORT-NAME>", r#"<SHORT-NAME>DiagTempPdu</SHORT-NAME>
+          <I-PDU-TIMING-SPECIFICATIONS>
+            <I-PDU-TIMING>
+              <TRANSMISSION-MODE-DECLARATION>
+                <TRANSMISSION-MODE-TRUE-TIMING>
+                  <CYCLIC-TIMING>
+                    <TIME-PERIOD>
+                      <VALUE>0.5</VALUE>
+                    </TIME-PERIOD>
+                  </CYCLIC-TIMING>
+                </TRANSMISSION-MODE-TRUE-TIMING>
+              </TRANSMISSION-MODE-DECLARATION>
+            </I-PDU-TIMING>
+          </I-PDU-TIMING-SPECIFICATIONS>"#);
+        let dbc = load_arxml_from_bytes(arxml.as_bytes(), None).unwrap();
+        let msg = dbc.message_by_name("EngineStatus").unwrap();
+        assert_eq!(msg.attributes.get("GenMsgCycleTime"), Some(&AttributeValue::Int(100)));
+        assert_eq!(signal(msg, "DiagTemp").start_bit, 40);
+    }
+
+    #[test]
+    fn pdu_not_on_byte_boundary() {
+        match load_arxml_from_bytes(with_second_pdu(36).as_bytes(), None) {
+            Err(CanViewError::ArxmlError(e)) => assert_eq!(e, "PDU DiagTempPdu of frame EngineStatus starts at bit 36, which is not on a byte boundary"),
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+        assert!(load_arxml_from_bytes(with_second_pdu(48).as_bytes(), None).is_ok());
+    }
+}
@@ -0,0 +1,599 @@
+use crate::{CanResult, CanViewError};
+use crate::signal_parser::signal_bit_positions;
+use crate::tree_dbc::{CanId, Ecu, Message, Multiplexing, Signal, SignalGroup, SignalType, TreeDbc, ValueTable, ValueType};
+
+/// Maximum payload length of a classic CAN frame
+pub const MAX_DLC_BYTES: u64 = 8;
+
+/// Maximum payload length of a CAN FD frame
+pub const MAX_FD_BYTES: u64 = 64;
+
+/// Payload lengths above 8 bytes that a CAN FD DLC can encode
+const FD_LENGTHS: [u64; 7] = [12, 16, 20, 24, 32, 48, 64];
+
+/// Returns true if `length_bytes` is the payload length of a classic CAN or CAN FD frame
+pub fn is_valid_frame_length(length_bytes: u64) -> bool {
+    length_bytes <= MAX_DLC_BYTES || FD_LENGTHS.contains(&length_bytes)
+}
+
+/// Maximum number of edits that can be undone
+const UNDO_DEPTH: usize = 100;
+
+fn invalid<T>(reason: String) -> CanResult<T> {
+    Err(CanViewError::ValidationError(reason))
+}
+
+/// Checks a signal is self consistent and fits within a message of `length_bytes`
+pub fn validate_signal(signal: &Signal, length_bytes: u64) -> CanResult<()> {
+    if signal.name.is_empty() {
+        return invalid("Signal name cannot be empty".into())
+    }
+    if signal.length_bits == 0 || signal.length_bits > 64 {
+        return invalid(format!("Signal {} has invalid length {} bits", signal.name, signal.length_bits))
+    }
+    match signal.value_type {
+        ValueType::Float32 if signal.length_bits != 32 => return invalid(format!("IEEE float signal {} must be 32 bits long", signal.name)),
+        ValueType::Float64 if signal.length_bits != 64 => return invalid(format!("IEEE double signal {} must be 64 bits long", signal.name)),
+        _ => {}
+    }
+    if signal_bit_positions(signal.start_bit, signal.length_bits, signal.order).iter().any(|p| p / 8 >= length_bytes) {
+        return invalid(format!("Signal {} does not fit in a {} byte message", signal.name, length_bytes))
+    }
+    if signal.min > signal.max {
+        return invalid(format!("Signal {} has min {} greater than max {}", signal.name, signal.min, signal.max))
+    }
+    if let Some(entries) = signal.signal_type.entries() {
+        let (raw_min, raw_max) = match (signal.signed, signal.length_bits >= 64) {
+            (true, true) => (i64::MIN as i128, i64::MAX as i128),
+            (false, true) => (0, u64::MAX as i128),
+            (true, false) => (-(1i128 << (signal.length_bits - 1)), (1i128 << (signal.length_bits - 1)) - 1),
+            (false, false) => (0, (1i128 << signal.length_bits) - 1),
+        };
+        for (idx, (raw, name)) in entries.iter().enumerate() {
+            if (*raw as i128) < raw_min || (*raw as i128) > raw_max {
+                return invalid(format!("Value {} ({}) of signal {} cannot be represented in {} bits", raw, name, signal.name, signal.length_bits))
+            }
+            if entries[..idx].iter().any(|(r, _)| r == raw) {
+                return invalid(format!("Value {} of signal {} is defined more than once", raw, signal.name))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks a message and its signals are self consistent
+pub fn validate_message(msg: &Message) -> CanResult<()> {
+    if msg.name.is_empty() {
+        return invalid("Message name cannot be empty".into())
+    }
+    if !is_valid_frame_length(msg.length_bytes) {
+        return invalid(format!("Message {} is {} bytes long, which is not a CAN or CAN FD payload length (0-8, 12, 16, 20, 24, 32, 48 or {})",
+            msg.name, msg.length_bytes, MAX_FD_BYTES))
+    }
+    for (idx, signal) in msg.signals.iter().enumerate() {
+        validate_signal(signal, msg.length_bytes)?;
+        let bits = signal_bit_positions(signal.start_bit, signal.length_bits, signal.order);
+        for other in &msg.signals[..idx] {
+            if other.name == signal.name {
+                return invalid(format!("Message {} has more than one signal named {}", msg.name, signal.name))
+            }
+            if msg.are_mutually_exclusive(signal, other) {
+                continue;
+            }
+            let other_bits = signal_bit_positions(other.start_bit, other.length_bits, other.order);
+            if bits.iter().any(|b| other_bits.contains(b)) {
+                return invalid(format!("Signals {} and {} of message {} overlap", other.name, signal.name, msg.name))
+            }
+        }
+    }
+    for signal in &msg.signals {
+        if let Some(c) = signal.mux_condition() {
+            if !msg.signals.iter().any(|s| s.name == c.multiplexor && s.is_multiplexor()) {
+                return invalid(format!("Signal {} is multiplexed by {}, which is not a multiplexor of message {}", signal.name, c.multiplexor, msg.name))
+            }
+        }
+    }
+    for group in &msg.signal_groups {
+        if let Some(missing) = group.signals.iter().find(|n| !msg.signals.iter().any(|s| &s.name == *n)) {
+            return invalid(format!("Signal group {} of message {} contains unknown signal {}", group.name, msg.name, missing))
+        }
+    }
+    Ok(())
+}
+
+/// Editing layer over a [TreeDbc]. Every edit is validated before it is applied,
+/// and can be undone / redone.
+#[derive(Debug, Clone, Default)]
+pub struct DbcEditor {
+    dbc: TreeDbc,
+    undo_stack: Vec<TreeDbc>,
+    redo_stack: Vec<TreeDbc>,
+}
+
+impl DbcEditor {
+    pub fn new(dbc: TreeDbc) -> Self {
+        Self {
+            dbc,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+        }
+    }
+
+    pub fn dbc(&self) -> &TreeDbc {
+        &self.dbc
+    }
+
+    pub fn into_dbc(self) -> TreeDbc {
+        self.dbc
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Reverts the last edit. Returns false if there was nothing to undo
+    pub fn undo(&mut self) -> bool {
+        match self.undo_stack.pop() {
+            Some(prev) => {
+                self.redo_stack.push(std::mem::replace(&mut self.dbc, prev));
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Re-applies the last undone edit. Returns false if there was nothing to redo
+    pub fn redo(&mut self) -> bool {
+        match self.redo_stack.pop() {
+            Some(next) => {
+                self.undo_stack.push(std::mem::replace(&mut self.dbc, next));
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Applies an edit to a copy of the DBC, only committing it if it succeeds
+    fn apply<F: FnOnce(&mut TreeDbc) -> CanResult<()>>(&mut self, f: F) -> CanResult<()> {
+        let mut edited = self.dbc.clone();
+        f(&mut edited)?;
+        edited.reindex();
+        self.undo_stack.push(std::mem::replace(&mut self.dbc, edited));
+        if self.undo_stack.len() > UNDO_DEPTH {
+            self.undo_stack.remove(0);
+        }
+        self.redo_stack.clear();
+        Ok(())
+    }
+
+    fn ecu_idx(dbc: &TreeDbc, name: &str) -> CanResult<usize> {
+        match dbc.ecus.iter().position(|e| e.name == name) {
+            Some(idx) => Ok(idx),
+            None => invalid(format!("No ECU named {}", name))
+        }
+    }
+
+    fn message_idx(dbc: &TreeDbc, id: CanId) -> CanResult<(usize, usize)> {
+        for (ecu_idx, ecu) in dbc.ecus.iter().enumerate() {
+            if let Some(msg_idx) = ecu.messages.iter().position(|m| m.id == id) {
+                return Ok((ecu_idx, msg_idx))
+            }
+        }
+        invalid(format!("No message with ID {}", id))
+    }
+
+    /// Checks all receivers of the message's signals are known ECUs
+    fn check_receivers(dbc: &TreeDbc, msg: &Message) -> CanResult<()> {
+        for signal in &msg.signals {
+            for r in &signal.receivers {
+                if !dbc.ecus.iter().any(|e| e.name == *r) {
+                    return invalid(format!("Receiver {} of signal {} is not a known ECU", r, signal.name))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks a message is valid and its ID and name are unique, ignoring the
+    /// message currently stored under `replacing` (if any)
+    fn check_message(dbc: &TreeDbc, msg: &Message, replacing: Option<CanId>) -> CanResult<()> {
+        validate_message(msg)?;
+        Self::check_receivers(dbc, msg)?;
+        for other in dbc.ecus.iter().flat_map(|e| e.messages.iter()) {
+            if Some(other.id) == replacing {
+                continue;
+            }
+            if other.id == msg.id {
+                return invalid(format!("Message ID {} is already used by {}", msg.id, other.name))
+            }
+            if other.name == msg.name {
+                return invalid(format!("Message name {} is already used", msg.name))
+            }
+        }
+        Ok(())
+    }
+
+    pub fn add_ecu(&mut self, name: &str) -> CanResult<()> {
+        self.apply(|dbc| {
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return invalid(format!("'{}' is not a valid ECU name", name))
+            }
+            if dbc.ecus.iter().any(|e| e.name == name) {
+                return invalid(format!("ECU {} already exists", name))
+            }
+            dbc.ecus.push(Ecu { name: name.to_string(), ..Default::default() });
+            Ok(())
+        })
+    }
+
+    /// Removes an ECU along with all the messages it transmits, and removes
+    /// it as a receiver of any signal
+    pub fn remove_ecu(&mut self, name: &str) -> CanResult<()> {
+        self.apply(|dbc| {
+            let idx = Self::ecu_idx(dbc, name)?;
+            dbc.ecus.remove(idx);
+            for signal in dbc.ecus.iter_mut().flat_map(|e| e.messages.iter_mut()).flat_map(|m| m.signals.iter_mut()) {
+                signal.receivers.retain(|r| r != name);
+            }
+            Ok(())
+        })
+    }
+
+    pub fn rename_ecu(&mut self, name: &str, new_name: &str) -> CanResult<()> {
+        self.apply(|dbc| {
+            let idx = Self::ecu_idx(dbc, name)?;
+            if new_name.is_empty() || new_name.contains(char::is_whitespace) {
+                return invalid(format!("'{}' is not a valid ECU name", new_name))
+            }
+            if dbc.ecus.iter().any(|e| e.name == new_name) {
+                return invalid(format!("ECU {} already exists", new_name))
+            }
+            dbc.ecus[idx].name = new_name.to_string();
+            for signal in dbc.ecus.iter_mut().flat_map(|e| e.messages.iter_mut()).flat_map(|m| m.signals.iter_mut()) {
+                for r in signal.receivers.iter_mut().filter(|r| *r == name) {
+                    *r = new_name.to_string();
+                }
+            }
+            Ok(())
+        })
+    }
+
+    pub fn add_message(&mut self, ecu: &str, msg: Message) -> CanResult<()> {
+        self.apply(|dbc| {
+            let idx = Self::ecu_idx(dbc, ecu)?;
+            Self::check_message(dbc, &msg, None)?;
+            dbc.ecus[idx].messages.push(msg);
+            Ok(())
+        })
+    }
+
+    pub fn remove_message(&mut self, id: CanId) -> CanResult<()> {
+        self.apply(|dbc| {
+            let (ecu_idx, msg_idx) = Self::message_idx(dbc, id)?;
+            dbc.ecus[ecu_idx].messages.remove(msg_idx);
+            Ok(())
+        })
+    }
+
+    /// Replaces the message with ID `id`. The new message may have a different ID
+    pub fn update_message(&mut self, id: CanId, msg: Message) -> CanResult<()> {
+        self.apply(|dbc| {
+            let (ecu_idx, msg_idx) = Self::message_idx(dbc, id)?;
+            Self::check_message(dbc, &msg, Some(id))?;
+            dbc.ecus[ecu_idx].messages[msg_idx] = msg;
+            Ok(())
+        })
+    }
+
+    /// Moves a message to be transmitted by another ECU
+    pub fn move_message(&mut self, id: CanId, to_ecu: &str) -> CanResult<()> {
+        self.apply(|dbc| {
+            let to_idx = Self::ecu_idx(dbc, to_ecu)?;
+            let (ecu_idx, msg_idx) = Self::message_idx(dbc, id)?;
+            let msg = dbc.ecus[ecu_idx].messages.remove(msg_idx);
+            dbc.ecus[to_idx].messages.push(msg);
+            Ok(())
+        })
+    }
+
+    /// Edits a message in place, validating the result
+    fn edit_message<F: FnOnce(&mut Message) -> CanResult<()>>(&mut self, id: CanId, f: F) -> CanResult<()> {
+        self.apply(|dbc| {
+            let (ecu_idx, msg_idx) = Self::message_idx(dbc, id)?;
+            let mut msg = dbc.ecus[ecu_idx].messages[msg_idx].clone();
+            f(&mut msg)?;
+            Self::check_message(dbc, &msg, Some(id))?;
+            dbc.ecus[ecu_idx].messages[msg_idx] = msg;
+            Ok(())
+        })
+    }
+
+    pub fn add_signal(&mut self, msg_id: CanId, signal: Signal) -> CanResult<()> {
+        self.edit_message(msg_id, |msg| {
+            msg.signals.push(signal);
+            Ok(())
+        })
+    }
+
+    pub fn remove_signal(&mut self, msg_id: CanId, name: &str) -> CanResult<()> {
+        self.edit_message(msg_id, |msg| {
+            match msg.signals.iter().position(|s| s.name == name) {
+                Some(idx) => {
+                    msg.signals.remove(idx);
+                    for group in msg.signal_groups.iter_mut() {
+                        group.signals.retain(|s| s != name);
+                    }
+                    Ok(())
+                },
+                None => invalid(format!("Message {} has no signal {}", msg.name, name))
+            }
+        })
+    }
+
+    /// Replaces a signal, e.g. to move it within the frame or change its scaling.
+    ///
+    /// Renaming a multiplexor also updates the signals it multiplexes, and renaming
+    /// any signal updates the signal groups containing it
+    pub fn update_signal(&mut self, msg_id: CanId, name: &str, signal: Signal) -> CanResult<()> {
+        self.edit_message(msg_id, |msg| {
+            let new_name = signal.name.clone();
+            match msg.signals.iter_mut().find(|s| s.name == name) {
+                Some(s) => *s = signal,
+                None => return invalid(format!("Message {} has no signal {}", msg.name, name))
+            }
+            if new_name != name {
+                for s in msg.signals.iter_mut() {
+                    match &mut s.multiplexing {
+                        Multiplexing::Multiplexed(c) | Multiplexing::MultiplexorAndMultiplexed(c) if c.multiplexor == name => {
+                            c.multiplexor = new_name.clone();
+                        },
+                        _ => {}
+                    }
+                }
+                for n in msg.signal_groups.iter_mut().flat_map(|g| g.signals.iter_mut()).filter(|n| *n == name) {
+                    *n = new_name.clone();
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Adds or replaces a signal group of a message
+    pub fn set_signal_group(&mut self, msg_id: CanId, group: SignalGroup) -> CanResult<()> {
+        self.edit_message(msg_id, |msg| {
+            if group.name.is_empty() || group.name.contains(char::is_whitespace) {
+                return invalid(format!("'{}' is not a valid signal group name", group.name))
+            }
+            match msg.signal_groups.iter_mut().find(|g| g.name == group.name) {
+                Some(g) => *g = group,
+                None => msg.signal_groups.push(group),
+            }
+            Ok(())
+        })
+    }
+
+    pub fn remove_signal_group(&mut self, msg_id: CanId, name: &str) -> CanResult<()> {
+        self.edit_message(msg_id, |msg| {
+            match msg.signal_groups.iter().position(|g| g.name == name) {
+                Some(idx) => {
+                    msg.signal_groups.remove(idx);
+                    Ok(())
+                },
+                None => invalid(format!("Message {} has no signal group {}", msg.name, name))
+            }
+        })
+    }
+
+    /// Adds or replaces a shared value table. Signals using the table are updated
+    /// to its new values, which must be valid for each of them.
+    pub fn set_value_table(&mut self, name: &str, mut values: ValueTable) -> CanResult<()> {
+        self.apply(|dbc| {
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return invalid(format!("'{}' is not a valid value table name", name))
+            }
+            values.sort_by_key(|v| v.0);
+            if let Some(w) = values.windows(2).find(|w| w[0].0 == w[1].0) {
+                return invalid(format!("Value {} of value table {} is defined more than once", w[0].0, name))
+            }
+            for msg in dbc.ecus.iter_mut().flat_map(|e| e.messages.iter_mut()) {
+                let mut changed = false;
+                for signal in msg.signals.iter_mut().filter(|s| s.value_table.as_deref() == Some(name)) {
+                    let (multi, offset) = signal.signal_type.scaling();
+                    signal.signal_type = SignalType::Enum { entries: values.clone(), multi, offset };
+                    changed = true;
+                }
+                if changed {
+                    validate_message(msg)?;
+                }
+            }
+            dbc.value_tables.insert(name.to_string(), values);
+            Ok(())
+        })
+    }
+
+    /// Removes a shared value table. Signals which used it keep their values.
+    pub fn remove_value_table(&mut self, name: &str) -> CanResult<()> {
+        self.apply(|dbc| {
+            if dbc.value_tables.remove(name).is_none() {
+                return invalid(format!("Value table {} does not exist", name))
+            }
+            for signal in dbc.ecus.iter_mut().flat_map(|e| e.messages.iter_mut()).flat_map(|m| m.signals.iter_mut()) {
+                if signal.value_table.as_deref() == Some(name) {
+                    signal.value_table = None;
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_dbc_from_bytes;
+    use crate::tree_dbc::MuxCondition;
+
+    const DBC: &str = r#"VERSION ""
+
+NS_ :
+
+BS_:
+
+BU_: Engine Dash
+VAL_TABLE_ GearTable 0 "Park" 1 "Drive" ;
+
+BO_ 256 Status: 8 Engine
+ SG_ Speed : 0|16@1+ (0.1,0) [0|0] "km/h" Dash
+ SG_ Gear : 16|2@1+ (1,0) [0|0] "" Dash
+
+BO_ 512 Muxed: 4 Engine
+ SG_ Mux M : 0|8@1+ (1,0) [0|0] "" Dash
+ SG_ A m1 : 8|8@1+ (1,0) [0|0] "" Dash
+
+VAL_ 256 Gear 0 "Park" 1 "Drive" ;
+"#;
+
+    fn editor() -> DbcEditor {
+        DbcEditor::new(load_dbc_from_bytes(DBC.as_bytes()).unwrap())
+    }
+
+    fn signal(name: &str, start_bit: u64, length_bits: u64) -> Signal {
+        Signal { name: name.into(), start_bit, length_bits, ..Default::default() }
+    }
+
+    fn message(name: &str, id: u32, length_bytes: u64) -> Message {
+        Message { id: CanId::Standard(id), name: name.into(), length_bytes, signals: vec![signal("Value", 0, 8)], ..Default::default() }
+    }
+
+    /// Checks the edit was rejected with a reason containing `reason`, leaving the DBC unchanged
+    fn assert_rejected(editor: &DbcEditor, before: &TreeDbc, res: CanResult<()>, reason: &str) {
+        match res {
+            Err(CanViewError::ValidationError(e)) => assert!(e.contains(reason), "{}", e),
+            other => panic!("expected a validation error containing '{}', got {:?}", reason, other),
+        }
+        assert_eq!(editor.dbc(), before);
+    }
+
+    #[test]
+    fn overlapping_signals_are_rejected() {
+        let mut editor = editor();
+        let before = editor.dbc().clone();
+        let status = CanId::Standard(256);
+        assert_rejected(&editor, &before, editor.clone().add_signal(status, signal("Overlap", 12, 8)), "Signals Speed and Overlap of message Status overlap");
+        assert!(!editor.can_undo());
+        editor.add_signal(status, signal("Next", 18, 8)).unwrap();
+
+        // Signals of different multiplexor values may share bits
+        let muxed = CanId::Standard(512);
+        let multiplexed = |name: &str, value: u64, length_bits: u64| Signal {
+            multiplexing: Multiplexing::Multiplexed(MuxCondition { multiplexor: "Mux".into(), ranges: vec![(value, value)] }),
+            ..signal(name, 8, length_bits)
+        };
+        editor.add_signal(muxed, multiplexed("B", 2, 16)).unwrap();
+        let before = editor.dbc().clone();
+        assert_rejected(&editor, &before, editor.clone().add_signal(muxed, multiplexed("C", 1, 4)), "Signals A and C of message Muxed overlap");
+        assert_rejected(&editor, &before, editor.clone().add_signal(muxed, signal("D", 20, 8)), "Signals B and D of message Muxed overlap");
+    }
+
+    #[test]
+    fn duplicate_ids_and_names_are_rejected() {
+        let mut editor = editor();
+        let before = editor.dbc().clone();
+        assert_rejected(&editor, &before, editor.clone().add_message("Dash", message("Other", 256, 8)), "Message ID 0x0100 is already used by Status");
+        assert_rejected(&editor, &before, editor.clone().add_message("Dash", message("Status", 300, 8)), "Message name Status is already used");
+        assert_rejected(&editor, &before, editor.clone().update_message(CanId::Standard(512), message("Muxed", 256, 8)), "already used by Status");
+        // Extended IDs are separate from standard ones
+        editor.add_message("Dash", Message { id: CanId::Extended(256), ..message("Other", 0, 8) }).unwrap();
+        // A message may keep its own ID and name
+        editor.update_message(CanId::Standard(512), message("Muxed", 512, 8)).unwrap();
+    }
+
+    #[test]
+    fn invalid_lengths_are_rejected() {
+        let mut editor = editor();
+        let before = editor.dbc().clone();
+        for length in [9, 13, 65] {
+            assert_rejected(&editor, &before, editor.clone().add_message("Dash", message("Long", 300, length)), "not a CAN or CAN FD payload length");
+        }
+        // Shrinking the message must keep all signals inside of it
+        let shorter = Message { length_bytes: 2, ..editor.dbc().message_by_id(CanId::Standard(256)).unwrap().clone() };
+        assert_rejected(&editor, &before, editor.clone().update_message(CanId::Standard(256), shorter), "Signal Gear does not fit in a 2 byte message");
+        for length in [0, 8, 12, 64] {
+            editor.add_message("Dash", Message { signals: Vec::new(), ..message(&format!("Len{}", length), 0x300 + length as u32, length) }).unwrap();
+        }
+    }
+
+    #[test]
+    fn undo_redo_round_trip() {
+        let mut editor = editor();
+        let original = editor.dbc().clone();
+        assert!(!editor.undo() && !editor.redo());
+
+        editor.add_ecu("Gateway").unwrap();
+        editor.add_message("Gateway", message("Routed", 0x300, 8)).unwrap();
+        editor.update_signal(CanId::Standard(512), "Mux", Signal { multiplexing: Multiplexing::Multiplexor, ..signal("Page", 0, 8) }).unwrap();
+        let edited = editor.dbc().clone();
+        // Renaming the multiplexor updated the signal it multiplexes
+        assert_eq!(edited.signal_by_path("Muxed.A").unwrap().mux_condition().unwrap().multiplexor, "Page");
+
+        for _ in 0..3 {
+            assert!(editor.undo());
+        }
+        assert!(!editor.undo() && editor.can_redo());
+        assert_eq!(editor.dbc(), &original);
+        for _ in 0..3 {
+            assert!(editor.redo());
+        }
+        assert!(!editor.redo());
+        assert_eq!(editor.dbc(), &edited);
+
+        // A new edit drops the undone edits
+        assert!(editor.undo());
+        editor.remove_ecu("Gateway").unwrap();
+        assert!(!editor.can_redo());
+        assert!(editor.dbc().ecus.iter().all(|e| e.name != "Gateway"));
+    }
+
+    #[test]
+    fn undo_depth_is_limited() {
+        let mut editor = editor();
+        for i in 0..UNDO_DEPTH + 5 {
+            editor.add_ecu(&format!("Node{}", i)).unwrap();
+        }
+        let mut undone = 0;
+        while editor.undo() {
+            undone += 1;
+        }
+        assert_eq!(undone, UNDO_DEPTH);
+        // The oldest edits can no longer be undone
+        let names: Vec<&str> = editor.dbc().ecus.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["Engine", "Dash", "Node0", "Node1", "Node2", "Node3", "Node4"]);
+    }
+
+    #[test]
+    fn value_tables_are_validated_for_their_signals() {
+        let mut editor = editor();
+        let gear = |editor: &DbcEditor| editor.dbc().signal_by_path("Status.Gear").unwrap().signal_type.clone();
+        assert_eq!(editor.dbc().signal_by_path("Status.Gear").unwrap().value_table.as_deref(), Some("GearTable"));
+        let before = editor.dbc().clone();
+
+        // Gear is only 2 bits long
+        let too_large = vec![(0, "Park".into()), (4, "Sport".into())];
+        assert_rejected(&editor, &before, editor.clone().set_value_table("GearTable", too_large), "Value 4 (Sport) of signal Gear cannot be represented in 2 bits");
+        let duplicate = vec![(1, "Drive".into()), (1, "Sport".into())];
+        assert_rejected(&editor, &before, editor.clone().set_value_table("GearTable", duplicate), "Value 1 of value table GearTable is defined more than once");
+        // Tables not used by any signal are not limited
+        editor.set_value_table("Unused", vec![(1000, "Big".into())]).unwrap();
+
+        editor.set_value_table("GearTable", vec![(3, "Reverse".into()), (0, "Park".into())]).unwrap();
+        assert_eq!(gear(&editor), SignalType::Enum { entries: vec![(0, "Park".into()), (3, "Reverse".into())], multi: 1.0, offset: 0.0 });
+        editor.remove_value_table("GearTable").unwrap();
+        assert_eq!(editor.dbc().signal_by_path("Status.Gear").unwrap().value_table, None);
+        // The signal keeps the values it had
+        assert_eq!(gear(&editor).entries().unwrap().len(), 2);
+    }
+}
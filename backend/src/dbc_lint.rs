@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+
+use crate::dbc_editor::{is_valid_frame_length, MAX_DLC_BYTES, MAX_FD_BYTES};
+use crate::signal_parser::signal_bit_positions;
+use crate::tree_dbc::{CanId, Message, Signal, SignalType, TreeDbc, ValueType};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A single problem found in a DBC by [lint_dbc]
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// ID of the message the problem was found in
+    pub message_id: Option<CanId>,
+    /// Name of the message the problem was found in
+    pub message: Option<String>,
+    /// Name of the signal the problem was found in
+    pub signal: Option<String>,
+    /// One line description of the problem
+    pub summary: String,
+    /// Longer explanation of why this is a problem
+    pub explanation: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] ", self.severity)?;
+        match (&self.message, &self.signal) {
+            (Some(m), Some(s)) => write!(f, "{}.{}: ", m, s)?,
+            (Some(m), None) => write!(f, "{}: ", m)?,
+            _ => {}
+        }
+        write!(f, "{}", self.summary)
+    }
+}
+
+fn diag(severity: Severity, msg: &Message, signal: Option<&Signal>, summary: String, explanation: &str) -> Diagnostic {
+    Diagnostic {
+        severity,
+        message_id: Some(msg.id),
+        message: Some(msg.name.clone()),
+        signal: signal.map(|s| s.name.clone()),
+        summary,
+        explanation: explanation.to_string(),
+    }
+}
+
+/// Returns the (min, max) raw integer values representable by a signal
+fn raw_range(signal: &Signal) -> (f64, f64) {
+    let bits = signal.length_bits.min(64) as i32;
+    if signal.signed {
+        (-(2f64.powi(bits - 1)), 2f64.powi(bits - 1) - 1.0)
+    } else {
+        (0.0, 2f64.powi(bits) - 1.0)
+    }
+}
+
+fn lint_signal(msg: &Message, signal: &Signal, out: &mut Vec<Diagnostic>) {
+    if signal.length_bits == 0 || signal.length_bits > 64 {
+        out.push(diag(Severity::Error, msg, Some(signal),
+            format!("Invalid signal length of {} bits", signal.length_bits),
+            "Signals must be between 1 and 64 bits long. The signal cannot be decoded."));
+        return;
+    }
+    if signal_bit_positions(signal.start_bit, signal.length_bits, signal.order).iter().any(|p| p / 8 >= msg.length_bytes) {
+        out.push(diag(Severity::Error, msg, Some(signal),
+            format!("Signal runs past the end of the {} byte message", msg.length_bytes),
+            "Part of the signal lies outside the message payload, so it can never be decoded from a frame of the declared length."));
+    }
+    match (signal.value_type, signal.length_bits) {
+        (ValueType::Float32, l) if l != 32 => out.push(diag(Severity::Error, msg, Some(signal),
+            format!("IEEE float signal is {} bits long", l),
+            "Signals declared as IEEE float via SIG_VALTYPE_ must be exactly 32 bits long.")),
+        (ValueType::Float64, l) if l != 64 => out.push(diag(Severity::Error, msg, Some(signal),
+            format!("IEEE double signal is {} bits long", l),
+            "Signals declared as IEEE double via SIG_VALTYPE_ must be exactly 64 bits long.")),
+        _ => {}
+    }
+    if signal.min > signal.max {
+        out.push(diag(Severity::Error, msg, Some(signal),
+            format!("Minimum {} is greater than maximum {}", signal.min, signal.max),
+            "No physical value can satisfy the declared range."));
+    }
+
+    let (raw_min, raw_max) = raw_range(signal);
+    match &signal.signal_type {
+        SignalType::Linear { multi, offset } if signal.value_type == ValueType::Integer && signal.min < signal.max => {
+            let a = raw_min * multi + offset;
+            let b = raw_max * multi + offset;
+            let (phys_min, phys_max) = (a.min(b), a.max(b));
+            // Allow for rounding of the decimal values written in the DBC
+            let tolerance = (multi.abs() * 0.5).max(phys_min.abs().max(phys_max.abs()) * f64::EPSILON);
+            if signal.min < phys_min - tolerance || signal.max > phys_max + tolerance {
+                out.push(diag(Severity::Warning, msg, Some(signal),
+                    format!("Range [{}|{}] is not reachable, signal can only represent [{}|{}]", signal.min, signal.max, phys_min, phys_max),
+                    "With the given factor, offset and bit length, part of the declared min/max range can never be encoded."));
+            }
+        },
+        SignalType::Enum { entries, .. } => {
+            for (raw, name) in entries {
+                if (*raw as f64) < raw_min || (*raw as f64) > raw_max {
+                    out.push(diag(Severity::Warning, msg, Some(signal),
+                        format!("Value description {} ({}) is outside the raw range [{}|{}]", raw, name, raw_min, raw_max),
+                        "The value description can never be shown, as the raw value cannot be represented by the signal."));
+                }
+            }
+        },
+        _ => {}
+    }
+}
+
+fn lint_message(msg: &Message, out: &mut Vec<Diagnostic>) {
+    if let CanId::Standard(id) = msg.id {
+        if id > CanId::MAX_STANDARD {
+            out.push(diag(Severity::Warning, msg, None,
+                format!("Standard ID 0x{:X} does not fit in 11 bits", id),
+                "Extended (29 bit) message IDs must have bit 31 set in the DBC, otherwise they never match a received frame."));
+        }
+    }
+    if msg.length_bytes > MAX_FD_BYTES {
+        out.push(diag(Severity::Error, msg, None,
+            format!("Message length of {} bytes is too long", msg.length_bytes),
+            "CAN frames can carry at most 8 bytes (64 bytes for CAN FD)."));
+    } else if !is_valid_frame_length(msg.length_bytes) {
+        out.push(diag(Severity::Warning, msg, None,
+            format!("Message length of {} bytes is not a valid CAN FD length", msg.length_bytes),
+            "CAN FD frames longer than 8 bytes are 12, 16, 20, 24, 32, 48 or 64 bytes long. The frame is padded to the next of these."));
+    } else if msg.length_bytes > MAX_DLC_BYTES {
+        out.push(diag(Severity::Info, msg, None,
+            format!("Message is {} bytes long, and requires CAN FD", msg.length_bytes),
+            "Classic CAN frames carry at most 8 bytes."));
+    }
+    for (idx, signal) in msg.signals.iter().enumerate() {
+        lint_signal(msg, signal, out);
+        if signal.length_bits == 0 || signal.length_bits > 64 {
+            continue;
+        }
+        let bits = signal_bit_positions(signal.start_bit, signal.length_bits, signal.order);
+        for other in &msg.signals[..idx] {
+            if other.name == signal.name {
+                out.push(diag(Severity::Error, msg, Some(signal),
+                    "Signal name is used more than once".into(),
+                    "Signal names must be unique within a message."));
+            }
+            if other.length_bits == 0 || other.length_bits > 64 || msg.are_mutually_exclusive(signal, other) {
+                continue;
+            }
+            let other_bits = signal_bit_positions(other.start_bit, other.length_bits, other.order);
+            if bits.iter().any(|b| other_bits.contains(b)) {
+                out.push(diag(Severity::Error, msg, Some(signal),
+                    format!("Signal overlaps with {}", other.name),
+                    "Both signals can be present in the same frame and share bits, so at least one of them decodes incorrectly."));
+            }
+        }
+        if let Some(c) = signal.mux_condition() {
+            if !msg.signals.iter().any(|s| s.name == c.multiplexor && s.is_multiplexor()) {
+                out.push(diag(Severity::Error, msg, Some(signal),
+                    format!("Multiplexor {} does not exist", c.multiplexor),
+                    "The signal is multiplexed by a signal which is not a multiplexor of this message, so it is never shown."));
+            }
+        }
+    }
+    for group in &msg.signal_groups {
+        for name in group.signals.iter().filter(|n| !msg.signals.iter().any(|s| &s.name == *n)) {
+            out.push(diag(Severity::Warning, msg, None,
+                format!("Signal group {} contains unknown signal {}", group.name, name),
+                "SIG_GROUP_ lists a signal which is not part of this message."));
+        }
+    }
+}
+
+/// Checks a DBC for consistency problems, returning one diagnostic per problem,
+/// most severe first
+pub fn lint_dbc(dbc: &TreeDbc) -> Vec<Diagnostic> {
+    let mut out = Vec::new();
+    let mut ids: HashMap<CanId, Vec<(&str, &Message)>> = HashMap::new();
+    for ecu in &dbc.ecus {
+        for msg in &ecu.messages {
+            lint_message(msg, &mut out);
+            ids.entry(msg.id).or_default().push((&ecu.name, msg));
+        }
+    }
+    for msg in dbc.ecus.iter().flat_map(|e| e.messages.iter()) {
+        for signal in &msg.signals {
+            for r in signal.receivers.iter().filter(|r| !dbc.ecus.iter().any(|e| e.name == **r)) {
+                out.push(diag(Severity::Warning, msg, Some(signal),
+                    format!("Receiver {} is not a declared node", r),
+                    "The receiving ECU is not listed in BU_, so it is missing from the node list."));
+            }
+        }
+    }
+    let mut duplicates: Vec<&Vec<(&str, &Message)>> = ids.values().filter(|v| v.len() > 1).collect();
+    duplicates.sort_by_key(|v| v[0].1.id);
+    for dup in duplicates {
+        let owners: Vec<String> = dup.iter().map(|(ecu, m)| format!("{} ({})", m.name, ecu)).collect();
+        for (_, msg) in dup {
+            out.push(diag(Severity::Error, msg, None,
+                format!("Message ID {} is defined more than once: {}", msg.id, owners.join(", ")),
+                "Received frames can only be matched against one definition, so the others are never decoded."));
+        }
+    }
+    out.sort_by_key(|d| std::cmp::Reverse(d.severity));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_dbc_from_bytes;
+    use crate::tree_dbc::{Ecu, Multiplexing, MuxCondition};
+
+    const DBC: &str = r#"VERSION ""
+
+NS_ :
+
+BS_:
+
+BU_: Engine Dash
+BO_ 256 Status: 8 Engine
+ SG_ Mux M : 0|4@1+ (1,0) [0|15] "" Dash
+ SG_ A m1 : 8|16@1+ (0.1,0) [0|6553.5] "" Dash
+ SG_ B m2 : 8|8@1+ (1,0) [0|255] "" Dash
+ SG_ Gear : 24|3@1+ (1,0) [0|7] "" Dash
+ SG_ Temp : 39|8@0- (0.5,-40) [-104|23.5] "degC" Dash
+
+VAL_ 256 Gear 0 "Park" 1 "Drive" ;
+SIG_GROUP_ 256 Group 1 : A Gear;
+"#;
+
+    /// Lints the DBC after editing its Status message
+    fn lint(edit: impl FnOnce(&mut Message)) -> Vec<String> {
+        let mut dbc = load_dbc_from_bytes(DBC.as_bytes()).unwrap();
+        edit(&mut dbc.ecus[0].messages[0]);
+        lint_dbc(&dbc).iter().map(|d| d.to_string()).collect()
+    }
+
+    fn signal<'a>(msg: &'a mut Message, name: &str) -> &'a mut Signal {
+        msg.signals.iter_mut().find(|s| s.name == name).unwrap()
+    }
+
+    #[test]
+    fn mutually_exclusive_signals_may_overlap() {
+        // A and B share bits 8 to 15, but are never in the same frame
+        assert_eq!(lint(|_| {}), Vec::<String>::new());
+    }
+
+    #[test]
+    fn overlapping_signals() {
+        assert_eq!(lint(|msg| signal(msg, "Gear").start_bit = 20), ["[error] Status.Gear: Signal overlaps with A"]);
+        // B is no longer exclusive with A once it is present for the same multiplexor value
+        assert_eq!(lint(|msg| signal(msg, "B").multiplexing = Multiplexing::Multiplexed(MuxCondition {
+            multiplexor: "Mux".into(),
+            ranges: vec![(1, 2)],
+        })), ["[error] Status.B: Signal overlaps with A"]);
+    }
+
+    #[test]
+    fn signal_past_end_of_payload() {
+        assert_eq!(lint(|msg| msg.length_bytes = 4), ["[error] Status.Temp: Signal runs past the end of the 4 byte message"]);
+    }
+
+    #[test]
+    fn standard_id_too_large() {
+        assert_eq!(lint(|msg| msg.id = CanId::Standard(0x800)), ["[warning] Status: Standard ID 0x800 does not fit in 11 bits"]);
+        assert_eq!(lint(|msg| msg.id = CanId::Extended(0x800)), Vec::<String>::new());
+    }
+
+    #[test]
+    fn float_signal_length() {
+        assert_eq!(lint(|msg| signal(msg, "Temp").value_type = ValueType::Float32), ["[error] Status.Temp: IEEE float signal is 8 bits long"]);
+        assert_eq!(lint(|msg| signal(msg, "Temp").value_type = ValueType::Float64), ["[error] Status.Temp: IEEE double signal is 8 bits long"]);
+    }
+
+    #[test]
+    fn enum_value_outside_raw_range() {
+        let diagnostics = lint(|msg| if let SignalType::Enum { entries, .. } = &mut signal(msg, "Gear").signal_type {
+            entries.push((8, "Invalid".into()));
+            entries.push((-1, "Error".into()));
+        });
+        assert_eq!(diagnostics, [
+            "[warning] Status.Gear: Value description 8 (Invalid) is outside the raw range [0|7]",
+            "[warning] Status.Gear: Value description -1 (Error) is outside the raw range [0|7]",
+        ]);
+    }
+
+    #[test]
+    fn duplicate_ids() {
+        let mut dbc = load_dbc_from_bytes(DBC.as_bytes()).unwrap();
+        let copy = Message { name: "Copy".into(), ..dbc.ecus[0].messages[0].clone() };
+        dbc.ecus[1].messages.push(copy);
+        dbc.ecus.push(Ecu { name: "Other".into(), messages: vec![Message { id: CanId::Extended(256), ..dbc.ecus[0].messages[0].clone() }], ..Default::default() });
+        let diagnostics: Vec<Diagnostic> = lint_dbc(&dbc);
+        assert_eq!(diagnostics.iter().map(|d| d.to_string()).collect::<Vec<_>>(), [
+            "[error] Status: Message ID 0x0100 is defined more than once: Status (Engine), Copy (Dash)",
+            "[error] Copy: Message ID 0x0100 is defined more than once: Status (Engine), Copy (Dash)",
+        ]);
+        assert!(diagnostics.iter().all(|d| d.message_id == Some(CanId::Standard(256))));
+    }
+
+    type Edit = fn(&mut Message);
+
+    #[test]
+    fn other_checks() {
+        let cases: [(Edit, &str); 11] = [
+            (|msg| signal(msg, "Gear").length_bits = 0, "[error] Status.Gear: Invalid signal length of 0 bits"),
+            (|msg| signal(msg, "Gear").min = 10.0, "[error] Status.Gear: Minimum 10 is greater than maximum 7"),
+            (|msg| signal(msg, "Temp").max = 100.0, "[warning] Status.Temp: Range [-104|100] is not reachable, signal can only represent [-104|23.5]"),
+            (|msg| msg.length_bytes = 65, "[error] Status: Message length of 65 bytes is too long"),
+            (|msg| msg.length_bytes = 10, "[warning] Status: Message length of 10 bytes is not a valid CAN FD length"),
+            (|msg| msg.length_bytes = 12, "[info] Status: Message is 12 bytes long, and requires CAN FD"),
+            (|msg| {
+                let mut copy = signal(msg, "Gear").clone();
+                copy.start_bit = 56;
+                msg.signals.push(copy);
+            }, "[error] Status.Gear: Signal name is used more than once"),
+            (|msg| {
+                let b = signal(msg, "B");
+                b.start_bit = 48;
+                b.multiplexing = Multiplexing::Multiplexed(MuxCondition { multiplexor: "Gear".into(), ranges: vec![(2, 2)] });
+            }, "[error] Status.B: Multiplexor Gear does not exist"),
+            (|msg| msg.signal_groups[0].signals.push("Missing".into()), "[warning] Status: Signal group Group contains unknown signal Missing"),
+            (|msg| signal(msg, "Temp").receivers.push("Logger".into()), "[warning] Status.Temp: Receiver Logger is not a declared node"),
+            (|msg| signal(msg, "Temp").min = -110.0, "[warning] Status.Temp: Range [-110|23.5] is not reachable, signal can only represent [-104|23.5]"),
+        ];
+        for (edit, expected) in cases {
+            assert_eq!(lint(edit), [expected]);
+        }
+    }
+}
@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+
+use crate::tree_dbc::{AttributeDefinition, AttributeObject, AttributeValue, CanId, Ecu, Message, MessageHandle, Signal, TreeDbc, ValueTable};
+
+/// What two files define differently
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ConflictSubject {
+    Message(CanId),
+    /// Attribute definition (`BA_DEF_`), by object type and name
+    AttributeDefinition(AttributeObject, String),
+    /// Network attribute value (`BA_`), by name
+    NetworkAttribute(String),
+    /// Shared value table (`VAL_TABLE_`), by name
+    ValueTable(String),
+}
+
+impl std::fmt::Display for ConflictSubject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConflictSubject::Message(id) => write!(f, "{}", id),
+            ConflictSubject::AttributeDefinition(object, name) => write!(f, "Attribute definition {} ({:?})", name, object),
+            ConflictSubject::NetworkAttribute(name) => write!(f, "Network attribute {}", name),
+            ConflictSubject::ValueTable(name) => write!(f, "Value table {}", name),
+        }
+    }
+}
+
+/// Two files defining the same CAN ID, attribute or value table differently.
+/// The definition from the file given first is the one kept in the merged DBC.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DbcConflict {
+    pub subject: ConflictSubject,
+    /// File and message name of the definition which was kept
+    pub kept: (String, String),
+    /// File and message name of the definition which was dropped
+    pub dropped: (String, String),
+    /// Human readable list of what differs between the two definitions
+    pub differences: Vec<String>,
+}
+
+impl std::fmt::Display for DbcConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} ({}) conflicts with {} ({}): {}",
+            self.subject,
+            self.kept.1,
+            self.kept.0,
+            self.dropped.1,
+            self.dropped.0,
+            self.differences.join(", ")
+        )
+    }
+}
+
+/// Several DBC files merged into one for decoding
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MergedDbc {
+    pub dbc: TreeDbc,
+    /// Names of the merged files, in the order given
+    pub files: Vec<String>,
+    /// File each message of `dbc` was taken from
+    pub sources: HashMap<MessageHandle, String>,
+    pub conflicts: Vec<DbcConflict>,
+}
+
+impl MergedDbc {
+    pub fn source_of(&self, handle: MessageHandle) -> Option<&str> {
+        self.sources.get(&handle).map(|s| s.as_str())
+    }
+}
+
+fn signal_differences(name: &str, a: &Signal, b: &Signal, out: &mut Vec<String>) {
+    let mut fields = Vec::new();
+    if a.start_bit != b.start_bit || a.length_bits != b.length_bits || a.order != b.order {
+        fields.push("layout");
+    }
+    if a.signed != b.signed || a.value_type != b.value_type {
+        fields.push("value type");
+    }
+    if a.signal_type != b.signal_type {
+        fields.push("scaling");
+    }
+    if a.min != b.min || a.max != b.max {
+        fields.push("range");
+    }
+    if a.unit != b.unit {
+        fields.push("unit");
+    }
+    if a.multiplexing != b.multiplexing {
+        fields.push("multiplexing");
+    }
+    if !fields.is_empty() {
+        out.push(format!("signal {} differs in {}", name, fields.join("/")));
+    }
+}
+
+/// Lists differences between two definitions of a message which matter for
+/// decoding. Comments, receivers and attributes are ignored.
+fn message_differences(a: &Message, b: &Message) -> Vec<String> {
+    let mut res = Vec::new();
+    if a.name != b.name {
+        res.push(format!("named {} vs {}", a.name, b.name));
+    }
+    if a.length_bytes != b.length_bytes {
+        res.push(format!("length {} vs {} bytes", a.length_bytes, b.length_bytes));
+    }
+    for sa in &a.signals {
+        match b.signals.iter().find(|sb| sb.name == sa.name) {
+            Some(sb) => signal_differences(&sa.name, sa, sb, &mut res),
+            None => res.push(format!("signal {} only in first", sa.name)),
+        }
+    }
+    for sb in &b.signals {
+        if !a.signals.iter().any(|sa| sa.name == sb.name) {
+            res.push(format!("signal {} only in second", sb.name));
+        }
+    }
+    res
+}
+
+fn attribute_definition_differences(a: &AttributeDefinition, b: &AttributeDefinition) -> Vec<String> {
+    let mut res = Vec::new();
+    if a.value_type != b.value_type {
+        res.push(format!("type {:?} vs {:?}", a.value_type, b.value_type));
+    }
+    if a.default != b.default {
+        let show = |v: &Option<AttributeValue>| v.as_ref().map_or("none".to_string(), |v| v.to_string());
+        res.push(format!("default {} vs {}", show(&a.default), show(&b.default)));
+    }
+    res
+}
+
+fn value_table_differences(a: &ValueTable, b: &ValueTable) -> Vec<String> {
+    let mut res = Vec::new();
+    for (value, label) in a {
+        match b.iter().find(|(v, _)| v == value) {
+            Some((_, other)) if other != label => res.push(format!("value {} is {} vs {}", value, label, other)),
+            Some(_) => {},
+            None => res.push(format!("value {} only in first", value)),
+        }
+    }
+    for (value, _) in b {
+        if !a.iter().any(|(v, _)| v == value) {
+            res.push(format!("value {} only in second", value));
+        }
+    }
+    res
+}
+
+/// Merges several DBCs into one. ECUs with the same name are combined.
+/// When a CAN ID, attribute definition, network attribute or value table is
+/// defined more than once the first definition is kept, and a [DbcConflict]
+/// is reported unless both definitions are the same.
+pub fn merge_dbcs(files: Vec<(String, TreeDbc)>) -> MergedDbc {
+    let mut merged = MergedDbc::default();
+    // ID -> (file, position) of the kept definition
+    let mut seen: HashMap<CanId, (String, usize, usize)> = HashMap::new();
+    // Files the kept attributes and value tables come from
+    let mut seen_definitions: HashMap<ConflictSubject, String> = HashMap::new();
+    for (file, dbc) in files {
+        merged.files.push(file.clone());
+        let mut conflict = |subject: ConflictSubject, name: &str, differences: Vec<String>| {
+            let kept_file = seen_definitions.entry(subject.clone()).or_insert_with(|| file.clone());
+            if !differences.is_empty() {
+                merged.conflicts.push(DbcConflict {
+                    subject,
+                    kept: (kept_file.clone(), name.to_string()),
+                    dropped: (file.clone(), name.to_string()),
+                    differences,
+                });
+            }
+        };
+        for def in dbc.attribute_definitions {
+            let subject = ConflictSubject::AttributeDefinition(def.object, def.name.clone());
+            match merged.dbc.attribute_definitions.iter().find(|d| d.name == def.name && d.object == def.object) {
+                Some(kept) => conflict(subject, &def.name, attribute_definition_differences(kept, &def)),
+                None => {
+                    conflict(subject, &def.name, Vec::new());
+                    merged.dbc.attribute_definitions.push(def);
+                }
+            }
+        }
+        for (name, value) in dbc.attributes {
+            let subject = ConflictSubject::NetworkAttribute(name.clone());
+            match merged.dbc.attributes.get(&name) {
+                Some(kept) if *kept != value => conflict(subject, &name, vec![format!("value {} vs {}", kept, value)]),
+                Some(_) => {},
+                None => {
+                    conflict(subject, &name, Vec::new());
+                    merged.dbc.attributes.insert(name, value);
+                }
+            }
+        }
+        // The version and network comment of the first file which has them are kept
+        if merged.dbc.version.is_empty() {
+            merged.dbc.version = dbc.version;
+        }
+        if merged.dbc.comment.is_none() {
+            merged.dbc.comment = dbc.comment;
+        }
+        for ev in dbc.environment_variables {
+            if !merged.dbc.environment_variables.iter().any(|e| e.name == ev.name) {
+                merged.dbc.environment_variables.push(ev);
+            }
+        }
+        for (name, table) in dbc.value_tables {
+            let subject = ConflictSubject::ValueTable(name.clone());
+            match merged.dbc.value_tables.get(&name) {
+                Some(kept) => conflict(subject, &name, value_table_differences(kept, &table)),
+                None => {
+                    conflict(subject, &name, Vec::new());
+                    merged.dbc.value_tables.insert(name, table);
+                }
+            }
+        }
+        for ecu in dbc.ecus {
+            let ecu_idx = match merged.dbc.ecus.iter().position(|e| e.name == ecu.name) {
+                Some(idx) => idx,
+                None => {
+                    merged.dbc.ecus.push(Ecu {
+                        name: ecu.name.clone(),
+                        comment: ecu.comment.clone(),
+                        messages: Vec::new(),
+                        attributes: ecu.attributes.clone(),
+                    });
+                    merged.dbc.ecus.len() - 1
+                }
+            };
+            for msg in ecu.messages {
+                if let Some((kept_file, e, m)) = seen.get(&msg.id) {
+                    let kept = &merged.dbc.ecus[*e].messages[*m];
+                    let differences = message_differences(kept, &msg);
+                    if !differences.is_empty() {
+                        merged.conflicts.push(DbcConflict {
+                            subject: ConflictSubject::Message(msg.id),
+                            kept: (kept_file.clone(), kept.name.clone()),
+                            dropped: (file.clone(), msg.name.clone()),
+                            differences,
+                        });
+                    }
+                    continue;
+                }
+                let target = &mut merged.dbc.ecus[ecu_idx].messages;
+                seen.insert(msg.id, (file.clone(), ecu_idx, target.len()));
+                // Later definitions of an ID are dropped, so every kept message is the first with its ID
+                merged.sources.insert(msg.id.into(), file.clone());
+                target.push(msg);
+            }
+        }
+    }
+    merged.dbc.reindex();
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_dbc_from_bytes;
+    use crate::tree_dbc::{AttributeValueType, SignalType};
+
+    const ENGINE: &str = r#"VERSION ""
+
+NS_ :
+
+BS_:
+
+BU_: Engine Dash
+VAL_TABLE_ OnOff 1 "On" 0 "Off" ;
+VAL_TABLE_ Shared 0 "A" ;
+
+BO_ 256 Status: 8 Engine
+ SG_ Speed : 0|16@1+ (0.1,0) [0|0] "km/h" Dash
+
+BO_ 512 Lights: 2 Dash
+ SG_ Beam : 0|2@1+ (1,0) [0|0] "" Engine
+
+BA_DEF_ BO_  "GenMsgCycleTime" INT 0 10000;
+BA_DEF_  "BusType" STRING ;
+BA_DEF_DEF_  "GenMsgCycleTime" 100;
+BA_DEF_DEF_  "BusType" "CAN";
+BA_ "BusType" "CAN";
+"#;
+
+    const BODY: &str = r#"VERSION ""
+
+NS_ :
+
+BS_:
+
+BU_: Body Dash
+VAL_TABLE_ OnOff 1 "Enabled" 0 "Off" 2 "Error" ;
+VAL_TABLE_ Shared 0 "A" ;
+VAL_TABLE_ Extra 0 "X" ;
+
+BO_ 256 Status: 8 Body
+ SG_ Speed : 0|16@1+ (0.2,0) [0|0] "km/h" Dash
+
+BO_ 512 Lights: 2 Dash
+ SG_ Beam : 0|2@1+ (1,0) [0|0] "" Body
+
+BO_ 768 Doors: 1 Dash
+ SG_ Open : 0|1@1+ (1,0) [0|0] "" Body
+
+CM_ BO_ 512 "Comments do not matter for decoding";
+BA_DEF_ BO_  "GenMsgCycleTime" INT 0 1000;
+BA_DEF_  "BusType" STRING ;
+BA_DEF_DEF_  "GenMsgCycleTime" 100;
+BA_DEF_DEF_  "BusType" "CAN";
+BA_ "BusType" "CAN FD";
+"#;
+
+    fn merge(files: &[(&str, &str)]) -> MergedDbc {
+        merge_dbcs(files.iter().map(|(name, text)| (name.to_string(), load_dbc_from_bytes(text.as_bytes()).unwrap())).collect())
+    }
+
+    fn conflicts(merged: &MergedDbc, subject: fn(&ConflictSubject) -> bool) -> Vec<String> {
+        merged.conflicts.iter().filter(|c| subject(&c.subject)).map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn message_conflicts() {
+        let merged = merge(&[("engine.dbc", ENGINE), ("body.dbc", BODY)]);
+        assert_eq!(merged.files, ["engine.dbc", "body.dbc"]);
+        // Lights only differs in its comment and receivers
+        assert_eq!(conflicts(&merged, |s| matches!(s, ConflictSubject::Message(_))), [
+            "0x0100: Status (engine.dbc) conflicts with Status (body.dbc): signal Speed differs in scaling",
+        ]);
+
+        let ecus: Vec<(&str, usize)> = merged.dbc.ecus.iter().map(|e| (e.name.as_str(), e.messages.len())).collect();
+        assert_eq!(ecus, [("Engine", 1), ("Dash", 2), ("Body", 0)]);
+        let source = |id: u32| merged.source_of(CanId::Standard(id).into());
+        assert_eq!((source(256), source(512), source(768)), (Some("engine.dbc"), Some("engine.dbc"), Some("body.dbc")));
+        assert_eq!(merged.dbc.message_by_id(CanId::Standard(768)).unwrap().name, "Doors");
+    }
+
+    #[test]
+    fn value_table_conflicts() {
+        let merged = merge(&[("engine.dbc", ENGINE), ("body.dbc", BODY)]);
+        assert_eq!(conflicts(&merged, |s| matches!(s, ConflictSubject::ValueTable(_))), [
+            "Value table OnOff: OnOff (engine.dbc) conflicts with OnOff (body.dbc): value 1 is On vs Enabled, value 2 only in second",
+        ]);
+        assert_eq!(merged.dbc.value_tables.keys().collect::<Vec<_>>(), ["Extra", "OnOff", "Shared"]);
+    }
+
+    #[test]
+    fn attribute_conflicts() {
+        let merged = merge(&[("engine.dbc", ENGINE), ("body.dbc", BODY)]);
+        assert_eq!(conflicts(&merged, |s| matches!(s, ConflictSubject::AttributeDefinition(..) | ConflictSubject::NetworkAttribute(_))), [
+            "Attribute definition GenMsgCycleTime (Message): GenMsgCycleTime (engine.dbc) conflicts with GenMsgCycleTime (body.dbc): \
+             type Int { min: 0, max: 10000 } vs Int { min: 0, max: 1000 }",
+            "Network attribute BusType: BusType (engine.dbc) conflicts with BusType (body.dbc): value CAN vs CAN FD",
+        ]);
+        assert_eq!(merged.dbc.attribute_definitions.len(), 2);
+    }
+
+    #[test]
+    fn first_definition_wins() {
+        for (first, second, scale, label, max, bus) in [
+            (("engine.dbc", ENGINE), ("body.dbc", BODY), 0.1, "On", 10000, "CAN"),
+            (("body.dbc", BODY), ("engine.dbc", ENGINE), 0.2, "Enabled", 1000, "CAN FD"),
+        ] {
+            let merged = merge(&[first, second]);
+            let status = merged.dbc.message_by_id(CanId::Standard(256)).unwrap();
+            assert_eq!(status.signals[0].signal_type, SignalType::Linear { multi: scale, offset: 0.0 });
+            assert_eq!(merged.source_of(status.id.into()), Some(first.0));
+            assert_eq!(merged.dbc.value_tables["OnOff"].iter().find(|(v, _)| *v == 1).unwrap().1, label);
+            assert_eq!(merged.dbc.attribute_definition("GenMsgCycleTime").unwrap().value_type, AttributeValueType::Int { min: 0, max });
+            assert_eq!(merged.dbc.network_attribute("BusType"), Some(AttributeValue::String(bus.into())));
+            assert!(merged.conflicts.iter().all(|c| c.kept.0 == first.0 && c.dropped.0 == second.0));
+        }
+    }
+
+    #[test]
+    fn identical_files_do_not_conflict() {
+        let merged = merge(&[("a.dbc", ENGINE), ("b.dbc", ENGINE)]);
+        assert!(merged.conflicts.is_empty(), "{:?}", merged.conflicts);
+        assert_eq!(merged.dbc.messages().count(), 2);
+    }
+}
@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+
+use crate::tree_dbc::{Message, MessageHandle, Signal, SignalHandle, TreeDbc};
+
+/// What a search result points at
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SearchTarget {
+    Message(MessageHandle),
+    Signal(SignalHandle),
+}
+
+impl SearchTarget {
+    pub fn message(&self) -> MessageHandle {
+        match self {
+            SearchTarget::Message(h) => *h,
+            SearchTarget::Signal(h) => h.message,
+        }
+    }
+}
+
+/// The part of the DBC which matched the query
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SearchField {
+    MessageName,
+    MessageId,
+    SignalName,
+    Comment,
+    Unit,
+    EnumLabel,
+}
+
+impl std::fmt::Display for SearchField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SearchField::MessageName => "message",
+            SearchField::MessageId => "ID",
+            SearchField::SignalName => "signal",
+            SearchField::Comment => "comment",
+            SearchField::Unit => "unit",
+            SearchField::EnumLabel => "value",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    pub target: SearchTarget,
+    pub field: SearchField,
+    /// Text which was matched against
+    pub matched: String,
+    /// Higher is better
+    pub score: u32,
+}
+
+/// Scores `candidate` against `query` as a case insensitive subsequence match.
+/// Consecutive characters, matches at the start of words and prefix matches
+/// score higher. Returns `None` if not every query character is found in order.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<u32> {
+    let query: Vec<char> = query.chars().flat_map(|c| c.to_lowercase()).filter(|c| !c.is_whitespace()).collect();
+    if query.is_empty() {
+        return None
+    }
+    let chars: Vec<char> = candidate.chars().collect();
+    let lower: Vec<char> = chars.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+
+    let mut score = 0;
+    let mut qi = 0;
+    let mut prev_match: Option<usize> = None;
+    for (i, c) in lower.iter().enumerate() {
+        if qi == query.len() {
+            break;
+        }
+        if *c != query[qi] {
+            continue;
+        }
+        score += 1;
+        if prev_match.is_some_and(|p| p + 1 == i) {
+            score += 5;
+        }
+        let word_start = i == 0
+            || !chars[i - 1].is_alphanumeric()
+            || (chars[i].is_uppercase() && chars[i - 1].is_lowercase());
+        if word_start {
+            score += 3;
+        }
+        prev_match = Some(i);
+        qi += 1;
+    }
+    if qi != query.len() {
+        return None
+    }
+    let query_str: String = query.iter().collect();
+    let lower_str: String = lower.iter().collect();
+    if lower_str == query_str {
+        score += 50;
+    } else if lower_str.starts_with(&query_str) {
+        score += 20;
+    } else if lower_str.contains(&query_str) {
+        score += 10;
+    }
+    // Prefer shorter candidates when everything else is equal
+    let length_bonus = 3u32.saturating_sub(((lower.len() - query.len()) / 8) as u32);
+    Some(score * 4 + length_bonus)
+}
+
+/// Comments are free text, where a subsequence match is almost always found,
+/// so they have to contain the query as is
+fn comment_score(query: &str, comment: &str) -> Option<u32> {
+    let query = query.trim().to_lowercase();
+    if !query.is_empty() && comment.to_lowercase().contains(&query) {
+        fuzzy_score(&query, comment)
+    } else {
+        None
+    }
+}
+
+/// IDs are only matched as a hex substring or as the whole decimal ID, as
+/// fuzzy matching digits matches nearly everything
+fn id_score(query: &str, msg: &Message) -> Option<u32> {
+    let q = query.trim();
+    if q.parse::<u32>().is_ok_and(|id| id == msg.id.raw()) {
+        return Some(300)
+    }
+    let q = q.strip_prefix("0x").or_else(|| q.strip_prefix("0X")).unwrap_or(q).to_uppercase();
+    if q.is_empty() || !q.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None
+    }
+    let hex = format!("{:X}", msg.id.raw());
+    if hex == q.trim_start_matches('0') || hex == q {
+        Some(300)
+    } else if hex.contains(&q) {
+        Some(40)
+    } else {
+        None
+    }
+}
+
+fn signal_candidates(signal: &Signal) -> Vec<(SearchField, &str)> {
+    let mut res = vec![(SearchField::SignalName, signal.name.as_str())];
+    if !signal.unit.is_empty() {
+        res.push((SearchField::Unit, signal.unit.as_str()));
+    }
+    if let Some(values) = signal.signal_type.entries() {
+        res.extend(values.iter().map(|(_, label)| (SearchField::EnumLabel, label.as_str())));
+    }
+    res
+}
+
+/// Weights names above the descriptive fields, so a signal called `Speed`
+/// ranks above one which mentions speed in its comment
+fn field_weight(field: SearchField) -> u32 {
+    match field {
+        SearchField::MessageName | SearchField::SignalName | SearchField::MessageId => 4,
+        SearchField::EnumLabel | SearchField::Unit => 2,
+        SearchField::Comment => 1,
+    }
+}
+
+/// Searches message names, IDs, signal names, comments, units and enum labels.
+/// Each message or signal is returned at most once (with its best matching field),
+/// sorted by best match first. Returns at most `limit` results.
+pub fn search_dbc(dbc: &TreeDbc, query: &str, limit: usize) -> Vec<SearchResult> {
+    let mut best: HashMap<SearchTarget, SearchResult> = HashMap::new();
+    let mut offer = |target: SearchTarget, field: SearchField, text: &str, score: Option<u32>| {
+        if let Some(score) = score {
+            let score = score * field_weight(field);
+            let entry = best.entry(target.clone()).or_insert_with(|| SearchResult {
+                target,
+                field,
+                matched: text.to_string(),
+                score: 0,
+            });
+            if score > entry.score {
+                entry.field = field;
+                entry.matched = text.to_string();
+                entry.score = score;
+            }
+        }
+    };
+    for (handle, msg) in dbc.messages_with_handles() {
+        let target = SearchTarget::Message(handle);
+        offer(target.clone(), SearchField::MessageName, &msg.name, fuzzy_score(query, &msg.name));
+        offer(target.clone(), SearchField::MessageId, &msg.id.to_string(), id_score(query, msg));
+        if let Some(c) = &msg.comment {
+            offer(target, SearchField::Comment, c, comment_score(query, c));
+        }
+        for signal in &msg.signals {
+            let target = SearchTarget::Signal(SignalHandle { message: handle, signal: signal.name.clone() });
+            for (field, text) in signal_candidates(signal) {
+                offer(target.clone(), field, text, fuzzy_score(query, text));
+            }
+            if let Some(c) = &signal.comment {
+                offer(target, SearchField::Comment, c, comment_score(query, c));
+            }
+        }
+    }
+    let mut results: Vec<SearchResult> = best.into_values().collect();
+    results.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.target.cmp(&b.target)));
+    results.truncate(limit);
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_dbc_from_bytes;
+    use crate::tree_dbc::CanId;
+
+    const DBC: &str = r#"VERSION ""
+
+NS_ :
+
+BS_:
+
+BU_: ECU
+BO_ 256 EngineData: 8 ECU
+ SG_ EngineSpeed : 0|16@1+ (1,0) [0|0] "rpm" Vector__XXX
+ SG_ Speed : 16|16@1+ (0.01,0) [0|0] "km/h" Vector__XXX
+ SG_ Gear : 32|4@1+ (1,0) [0|0] "" Vector__XXX
+
+BO_ 1194 VehicleSpeed: 8 ECU
+ SG_ Odometer : 0|32@1+ (1,0) [0|0] "km" Vector__XXX
+
+BO_ 598 Misc: 8 ECU
+ SG_ Counter : 0|8@1+ (1,0) [0|0] "" Vector__XXX
+
+CM_ BO_ 598 "Reports the cruise control set speed";
+CM_ SG_ 256 EngineSpeed "Crankshaft rotation";
+VAL_ 256 Gear 0 "Park" 1 "Speedy" ;
+"#;
+
+    fn search(query: &str) -> Vec<(String, SearchField)> {
+        let dbc = load_dbc_from_bytes(DBC.as_bytes()).unwrap();
+        search_dbc(&dbc, query, 10).iter().map(|r| {
+            let name = match &r.target {
+                SearchTarget::Message(h) => dbc.message(*h).unwrap().name.clone(),
+                SearchTarget::Signal(h) => h.signal.clone(),
+            };
+            (name, r.field)
+        }).collect()
+    }
+
+    fn found(results: &[(&str, SearchField)]) -> Vec<(String, SearchField)> {
+        results.iter().map(|(n, f)| (n.to_string(), *f)).collect()
+    }
+
+    #[test]
+    fn fuzzy_score_order() {
+        let scores: Vec<Option<u32>> = ["Speed", "SpeedLimit", "EngineSpeed", "SupervisedDrive", "Spd"].iter()
+            .map(|c| fuzzy_score("speed", c))
+            .collect();
+        assert!(scores[..4].iter().all(|s| s.is_some()) && scores[4].is_none(), "{:?}", scores);
+        assert!(scores.windows(2).take(3).all(|w| w[0] > w[1]), "{:?}", scores);
+        assert_eq!(fuzzy_score("SPEED", "speed"), fuzzy_score("speed", "Speed"));
+        assert_eq!(fuzzy_score(" ", "Speed"), None);
+        // Word starts score higher than matches within words
+        assert!(fuzzy_score("es", "EngineSpeed") > fuzzy_score("es", "Diesel"));
+    }
+
+    #[test]
+    fn exact_name_ranks_first() {
+        assert_eq!(search("speed"), found(&[
+            ("Speed", SearchField::SignalName),
+            // Equal scores, messages come before signals
+            ("VehicleSpeed", SearchField::MessageName),
+            ("EngineSpeed", SearchField::SignalName),
+            ("Gear", SearchField::EnumLabel),
+            ("Misc", SearchField::Comment),
+        ]));
+    }
+
+    #[test]
+    fn id_matches() {
+        let dbc = load_dbc_from_bytes(DBC.as_bytes()).unwrap();
+        let engine = MessageHandle::from(CanId::Standard(256));
+        let vehicle = MessageHandle::from(CanId::Standard(1194));
+        let misc = MessageHandle::from(CanId::Standard(598));
+        let ids = |query: &str| -> Vec<(MessageHandle, u32)> {
+            search_dbc(&dbc, query, 10).iter()
+                .filter(|r| r.field == SearchField::MessageId)
+                .map(|r| (r.target.message(), r.score))
+                .collect()
+        };
+        let exact = 300 * field_weight(SearchField::MessageId);
+        assert_eq!(ids("0x4AA"), [(vehicle, exact)]);
+        assert_eq!(ids("4aa"), [(vehicle, exact)]);
+        assert_eq!(ids("0x0100"), [(engine, exact)]);
+        // Decimal IDs only match as a whole, 256 is also the hex ID of Misc
+        assert_eq!(ids("1194"), [(vehicle, exact)]);
+        assert_eq!(ids("256"), [(engine, exact), (misc, exact)]);
+        assert_eq!(ids("0x4"), [(vehicle, 40 * field_weight(SearchField::MessageId))]);
+        assert_eq!(ids("119"), []);
+        assert_eq!(ids("0xZZ"), []);
+    }
+
+    #[test]
+    fn comments_match_as_substring() {
+        assert_eq!(search("crankshaft"), found(&[("EngineSpeed", SearchField::Comment)]));
+        assert_eq!(search("cruise control"), found(&[("Misc", SearchField::Comment)]));
+        // A subsequence is enough for names, but not for comments
+        assert_eq!(search("crnk"), []);
+    }
+
+    #[test]
+    fn results_are_limited() {
+        let dbc = load_dbc_from_bytes(DBC.as_bytes()).unwrap();
+        assert_eq!(search_dbc(&dbc, "e", 3).len(), 3);
+        assert!(search_dbc(&dbc, "", 10).is_empty());
+    }
+}
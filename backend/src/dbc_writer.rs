@@ -0,0 +1,371 @@
+use std::fmt::Write;
+
+use crate::tree_dbc::{AttributeObject, AttributeValue, AttributeValueType, Attributes, ByteOrder, EnvVarType, Message, Multiplexing, Signal, TreeDbc, ValueType};
+use crate::NULL_SENDER;
+
+const VECTOR_XXX: &str = "Vector__XXX";
+
+/// Escapes quotes and backslashes in DBC char strings
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn node_name(name: &str) -> &str {
+    if name == NULL_SENDER {
+        VECTOR_XXX
+    } else {
+        name
+    }
+}
+
+/// Returns true if the message needs `SG_MUL_VAL_` entries to describe its
+/// multiplexing, rather than just the `M` / `m<n>` indicators
+fn uses_extended_multiplexing(msg: &Message) -> bool {
+    let root = msg.multiplexor().map(|s| s.name.as_str());
+    msg.signals.iter().filter(|s| s.is_multiplexor()).count() > 1 ||
+        msg.signals.iter().filter_map(|s| s.mux_condition()).any(|c| {
+            Some(c.multiplexor.as_str()) != root || c.ranges.len() != 1 || c.ranges[0].0 != c.ranges[0].1
+        })
+}
+
+fn attribute_value(value: &AttributeValue) -> String {
+    match value {
+        AttributeValue::Int(i) => format!("{}", i),
+        AttributeValue::Float(f) => format!("{}", f),
+        AttributeValue::String(s) => format!("\"{}\"", escape(s)),
+    }
+}
+
+fn write_attributes(out: &mut String, dbc: &TreeDbc, messages: &[(&str, &Message)]) {
+    for def in &dbc.attribute_definitions {
+        let object = match def.object {
+            AttributeObject::Network => "",
+            AttributeObject::Node => "BU_ ",
+            AttributeObject::Message => "BO_ ",
+            AttributeObject::Signal => "SG_ ",
+            AttributeObject::EnvironmentVariable => "EV_ ",
+        };
+        let value_type = match &def.value_type {
+            AttributeValueType::Int { min, max } => format!("INT {} {}", min, max),
+            AttributeValueType::Hex { min, max } => format!("HEX {} {}", min, max),
+            AttributeValueType::Float { min, max } => format!("FLOAT {} {}", min, max),
+            AttributeValueType::String => "STRING ".into(),
+            AttributeValueType::Enum(labels) => {
+                let labels: Vec<String> = labels.iter().map(|l| format!("\"{}\"", escape(l))).collect();
+                format!("ENUM  {}", labels.join(","))
+            },
+        };
+        let _ = writeln!(out, "BA_DEF_ {} \"{}\" {};", object, def.name, value_type);
+    }
+    for def in &dbc.attribute_definitions {
+        if let Some(v) = &def.default {
+            let _ = writeln!(out, "BA_DEF_DEF_  \"{}\" {};", def.name, attribute_value(v));
+        }
+    }
+    let values = |attrs: &Attributes| attrs.iter().map(|(n, v)| (n.clone(), attribute_value(v))).collect::<Vec<_>>();
+    for (name, v) in values(&dbc.attributes) {
+        let _ = writeln!(out, "BA_ \"{}\" {};", name, v);
+    }
+    for ecu in dbc.ecus.iter().filter(|e| e.name != NULL_SENDER) {
+        for (name, v) in values(&ecu.attributes) {
+            let _ = writeln!(out, "BA_ \"{}\" BU_ {} {};", name, ecu.name, v);
+        }
+    }
+    for ev in &dbc.environment_variables {
+        for (name, v) in values(&ev.attributes) {
+            let _ = writeln!(out, "BA_ \"{}\" EV_ {} {};", name, ev.name, v);
+        }
+    }
+    for (_, msg) in messages {
+        for (name, v) in values(&msg.attributes) {
+            let _ = writeln!(out, "BA_ \"{}\" BO_ {} {};", name, msg.id.to_dbc(), v);
+        }
+        for signal in &msg.signals {
+            for (name, v) in values(&signal.attributes) {
+                let _ = writeln!(out, "BA_ \"{}\" SG_ {} {} {};", name, msg.id.to_dbc(), signal.name, v);
+            }
+        }
+    }
+}
+
+fn write_signal(out: &mut String, signal: &Signal) {
+    let mux = match &signal.multiplexing {
+        Multiplexing::Plain => String::new(),
+        Multiplexing::Multiplexor => " M".into(),
+        Multiplexing::Multiplexed(c) => format!(" m{}", c.ranges.first().map_or(0, |r| r.0)),
+        Multiplexing::MultiplexorAndMultiplexed(c) => format!(" m{}M", c.ranges.first().map_or(0, |r| r.0)),
+    };
+    let (factor, offset) = signal.signal_type.scaling();
+    let _ = writeln!(out, " SG_ {}{} : {}|{}@{}{} ({},{}) [{}|{}] \"{}\" {}",
+        signal.name,
+        mux,
+        signal.start_bit,
+        signal.length_bits,
+        match signal.order {
+            ByteOrder::LittleEndian => '1',
+            ByteOrder::BigEndian => '0',
+        },
+        if signal.signed { '-' } else { '+' },
+        factor,
+        offset,
+        signal.min,
+        signal.max,
+        escape(&signal.unit),
+        if signal.receivers.is_empty() { VECTOR_XXX.to_string() } else { signal.receivers.join(",") }
+    );
+}
+
+/// Writes the `<value> "<label>"` list of a `VAL_` or `VAL_TABLE_` statement
+fn write_value_descriptions(out: &mut String, entries: &[(i64, String)]) {
+    for (raw, name) in entries {
+        let _ = write!(out, " {} \"{}\"", raw, escape(name));
+    }
+    out.push_str(" ;\n");
+}
+
+/// Serializes a [TreeDbc] back into the DBC file format
+pub fn write_dbc(dbc: &TreeDbc) -> String {
+    let mut out = String::new();
+    let _ = write!(out, "VERSION \"{}\"\n\n\nNS_ :\n\n", escape(&dbc.version));
+    out.push_str("BS_:\n\n");
+
+    let nodes: Vec<&str> = dbc.ecus.iter()
+        .map(|e| e.name.as_str())
+        .filter(|n| *n != NULL_SENDER)
+        .collect();
+    let _ = writeln!(out, "BU_: {}\n", nodes.join(" "));
+
+    for (name, entries) in &dbc.value_tables {
+        let _ = write!(out, "VAL_TABLE_ {}", name);
+        write_value_descriptions(&mut out, entries);
+    }
+    if !dbc.value_tables.is_empty() {
+        out.push('\n');
+    }
+
+    let messages: Vec<(&str, &Message)> = dbc.ecus.iter()
+        .flat_map(|e| e.messages.iter().map(move |m| (e.name.as_str(), m)))
+        .collect();
+
+    for (sender, msg) in &messages {
+        let _ = writeln!(out, "BO_ {} {}: {} {}", msg.id.to_dbc(), msg.name, msg.length_bytes, node_name(sender));
+        for signal in &msg.signals {
+            write_signal(&mut out, signal);
+        }
+        out.push('\n');
+    }
+    out.push('\n');
+
+    for (_, msg) in messages.iter().filter(|(_, m)| !m.transmitters.is_empty()) {
+        let _ = writeln!(out, "BO_TX_BU_ {} : {};", msg.id.to_dbc(), msg.transmitters.join(","));
+    }
+
+    for ev in &dbc.environment_variables {
+        let _ = writeln!(out, "EV_ {}: {} [{}|{}] \"{}\" {} {} DUMMY_NODE_VECTOR{} {};",
+            ev.name,
+            match ev.var_type {
+                EnvVarType::Float => 0,
+                EnvVarType::Integer => 1,
+                EnvVarType::Data => 2,
+            },
+            ev.min,
+            ev.max,
+            escape(&ev.unit),
+            ev.initial_value,
+            ev.id,
+            ev.access_type,
+            if ev.access_nodes.is_empty() { VECTOR_XXX.to_string() } else { ev.access_nodes.join(",") }
+        );
+    }
+    for ev in &dbc.environment_variables {
+        if let Some(size) = ev.data_size {
+            let _ = writeln!(out, "ENVVAR_DATA_ {}: {};", ev.name, size);
+        }
+    }
+
+    if let Some(c) = &dbc.comment {
+        let _ = writeln!(out, "CM_ \"{}\";", escape(c));
+    }
+    for ecu in dbc.ecus.iter().filter(|e| e.name != NULL_SENDER) {
+        if let Some(c) = &ecu.comment {
+            let _ = writeln!(out, "CM_ BU_ {} \"{}\";", ecu.name, escape(c));
+        }
+    }
+    for (_, msg) in &messages {
+        if let Some(c) = &msg.comment {
+            let _ = writeln!(out, "CM_ BO_ {} \"{}\";", msg.id.to_dbc(), escape(c));
+        }
+        for signal in &msg.signals {
+            if let Some(c) = &signal.comment {
+                let _ = writeln!(out, "CM_ SG_ {} {} \"{}\";", msg.id.to_dbc(), signal.name, escape(c));
+            }
+        }
+    }
+    for ev in &dbc.environment_variables {
+        if let Some(c) = &ev.comment {
+            let _ = writeln!(out, "CM_ EV_ {} \"{}\";", ev.name, escape(c));
+        }
+    }
+
+    write_attributes(&mut out, dbc, &messages);
+
+    for (_, msg) in &messages {
+        for signal in &msg.signals {
+            if let Some(entries) = signal.signal_type.entries() {
+                let _ = write!(out, "VAL_ {} {}", msg.id.to_dbc(), signal.name);
+                write_value_descriptions(&mut out, entries);
+            }
+        }
+    }
+
+    for (_, msg) in &messages {
+        for group in &msg.signal_groups {
+            let _ = writeln!(out, "SIG_GROUP_ {} {} {} : {};", msg.id.to_dbc(), group.name, group.repetitions, group.signals.join(" "));
+        }
+    }
+
+    for (_, msg) in &messages {
+        for signal in &msg.signals {
+            let ty = match signal.value_type {
+                ValueType::Integer => continue,
+                ValueType::Float32 => 1,
+                ValueType::Float64 => 2,
+            };
+            let _ = writeln!(out, "SIG_VALTYPE_ {} {} : {};", msg.id.to_dbc(), signal.name, ty);
+        }
+    }
+
+    for (_, msg) in messages.iter().filter(|(_, m)| uses_extended_multiplexing(m)) {
+        for signal in &msg.signals {
+            if let Some(c) = signal.mux_condition() {
+                let ranges: Vec<String> = c.ranges.iter().map(|(min, max)| format!("{}-{}", min, max)).collect();
+                let _ = writeln!(out, "SG_MUL_VAL_ {} {} {} {};", msg.id.to_dbc(), signal.name, c.multiplexor, ranges.join(", "));
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{load_dbc_from_bytes, parse_signal, tree_dbc::SignalType, CanViewError};
+
+    const DBC: &str = r#"VERSION "1.2 \"beta\""
+
+NS_ :
+
+BS_:
+
+BU_: ECU DASH
+VAL_TABLE_ GearTable 0 "Park" 1 "Drive" ;
+
+BO_ 256 Status: 8 ECU
+ SG_ Gear : 0|4@1+ (1,0) [0|3] "" DASH
+ SG_ Mode : 4|4@1+ (2,-10) [-10|20] "" DASH
+ SG_ Enabled : 8|1@1+ (1,0) [0|1] "" DASH
+ SG_ Half : 9|1@1+ (0.5,0) [0|0.5] "" DASH
+ SG_ Lock : 10|1@1+ (1,0) [0|1] "" DASH
+ SG_ Temp : 23|12@0- (0.1,-40) [-244.8|164.7] "degC" DASH,ECU
+ SG_ Ratio : 32|32@1- (2,1) [0|0] "" Vector__XXX
+
+BO_ 2566844926 Muxed: 8 DASH
+ SG_ Mux M : 0|8@1+ (1,0) [0|255] "" ECU
+ SG_ Page m1M : 8|8@1+ (1,0) [0|255] "" ECU
+ SG_ Value m3 : 16|16@1+ (0.01,0) [0|655.35] "V" ECU
+
+BO_TX_BU_ 2566844926 : DASH,ECU;
+
+EV_ Ignition: 1 [0|2] "" 0 5 DUMMY_NODE_VECTOR3 ECU,DASH;
+EV_ Calibration: 2 [0|0] "" 0 6 DUMMY_NODE_VECTOR0 Vector__XXX;
+ENVVAR_DATA_ Calibration: 16;
+
+CM_ "Test network";
+CM_ BU_ ECU "Engine \"main\" controller, see C:\\ecu";
+CM_ BO_ 256 "Status of the ECU";
+CM_ SG_ 256 Temp "Coolant temperature";
+CM_ EV_ Ignition "Key position";
+BA_DEF_ BO_ "GenMsgCycleTime" INT 0 10000;
+BA_DEF_ EV_ "Simulated" INT 0 1;
+BA_DEF_DEF_ "GenMsgCycleTime" 0;
+BA_DEF_DEF_ "Simulated" 0;
+BA_ "GenMsgCycleTime" BO_ 256 100;
+BA_ "Simulated" EV_ Ignition 1;
+VAL_ 256 Gear 0 "Park" 1 "Drive" ;
+VAL_ 256 Mode 0 "Off" 1 "Eco" 2 "Sport" 15 "Invalid" ;
+VAL_ 256 Lock 0 "Unlocked" 1 "Locked" ;
+SIG_VALTYPE_ 256 Ratio : 1;
+SG_MUL_VAL_ 2566844926 Page Mux 1-1;
+SG_MUL_VAL_ 2566844926 Value Page 3-3;
+"#;
+
+    #[test]
+    fn write_and_load_round_trip() {
+        let dbc = load_dbc_from_bytes(DBC.as_bytes()).unwrap();
+        let written = write_dbc(&dbc);
+        let reloaded = load_dbc_from_bytes(written.as_bytes()).unwrap();
+        assert_eq!(reloaded, dbc, "{}", written);
+        assert_eq!(write_dbc(&reloaded), written);
+    }
+
+    #[test]
+    fn network_node_and_environment_variable_sections() {
+        let dbc = load_dbc_from_bytes(DBC.as_bytes()).unwrap();
+        assert_eq!(dbc.version, "1.2 \"beta\"");
+        assert_eq!(dbc.comment.as_deref(), Some("Test network"));
+        assert_eq!(dbc.ecus[0].comment.as_deref(), Some("Engine \"main\" controller, see C:\\ecu"));
+        assert_eq!(dbc.message_by_name("Muxed").unwrap().transmitters, ["DASH", "ECU"]);
+        assert_eq!(dbc.message_by_name("Status").unwrap().transmitters, Vec::<String>::new());
+
+        let ignition = &dbc.environment_variables[0];
+        assert_eq!((ignition.name.as_str(), ignition.var_type, ignition.max, ignition.id), ("Ignition", EnvVarType::Integer, 2, 5));
+        assert_eq!((ignition.access_type, &ignition.access_nodes), (3, &vec!["ECU".to_string(), "DASH".to_string()]));
+        assert_eq!(ignition.comment.as_deref(), Some("Key position"));
+        assert_eq!(ignition.attributes.get("Simulated"), Some(&AttributeValue::Int(1)));
+        let calibration = &dbc.environment_variables[1];
+        assert_eq!((calibration.var_type, calibration.data_size, calibration.access_nodes.len()), (EnvVarType::Data, Some(16), 0));
+
+        let written = write_dbc(&dbc);
+        assert!(written.starts_with("VERSION \"1.2 \\\"beta\\\"\""), "{}", written);
+        assert!(written.contains("CM_ BU_ ECU \"Engine \\\"main\\\" controller, see C:\\\\ecu\";"), "{}", written);
+    }
+
+    #[test]
+    fn one_bit_signals_keep_value_descriptions() {
+        let dbc = load_dbc_from_bytes(DBC.as_bytes()).unwrap();
+        let lock = &dbc.message_by_name("Status").unwrap().signals[4];
+        assert_eq!(lock.signal_type, SignalType::Enum { entries: vec![(0, "Unlocked".into()), (1, "Locked".into())], multi: 1.0, offset: 0.0 });
+        assert_eq!(parse_signal(lock, &[0, 0x04, 0, 0, 0, 0, 0, 0]).unwrap().to_string(), "1 (Locked)");
+    }
+
+    #[test]
+    fn escapes_in_parse_errors_are_shown_as_written() {
+        let line = r#"CM_ BO_ 256 "Status \"of\" the ECU" x;"#;
+        let text = DBC.replace(r#"CM_ BO_ 256 "Status of the ECU";"#, line);
+        match load_dbc_from_bytes(text.as_bytes()) {
+            Err(CanViewError::DbcError(e)) => assert_eq!(e.snippet, line, "{}", e),
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn enum_signals_keep_scaling() {
+        let dbc = load_dbc_from_bytes(DBC.as_bytes()).unwrap();
+        let msg = dbc.message_by_name("Status").unwrap();
+        let mode = &msg.signals[1];
+        assert_eq!(mode.signal_type.scaling(), (2.0, -10.0));
+        assert_eq!(mode.signal_type.entries().map(|e| e.len()), Some(4));
+        // Scaled 1 bit signals are numbers rather than booleans
+        assert_eq!(msg.signals[2].signal_type, SignalType::Bool);
+        assert_eq!(msg.signals[3].signal_type, SignalType::Linear { multi: 0.5, offset: 0.0 });
+
+        let written = write_dbc(&dbc);
+        assert!(written.contains(" SG_ Mode : 4|4@1+ (2,-10) [-10|20] \"\" DASH"), "{}", written);
+        assert!(written.contains(" SG_ Half : 9|1@1+ (0.5,0) [0|0.5] \"\" DASH"), "{}", written);
+
+        // The label is looked up by raw value, the range check uses the physical value
+        let parsed = parse_signal(mode, &[0x20, 0, 0, 0, 0, 0, 0, 0]).unwrap();
+        assert_eq!((parsed.raw, parsed.physical, parsed.to_string()), (2, -6.0, "2 (Sport)".to_string()));
+        assert!(parsed.is_valid());
+    }
+}
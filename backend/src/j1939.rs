@@ -0,0 +1,150 @@
+//! SAE J1939 identifier handling.
+//!
+//! J1939 packs a 3 bit priority, the 18 bit parameter group number (PGN) and
+//! the 8 bit source address of the sender into a 29 bit extended ID. For
+//! PDU1 groups (PDU format below 240) the low byte of the PGN is replaced by
+//! the destination address. DBC files define each parameter group once, with
+//! an example ID, so received frames are matched on their PGN alone.
+
+use crate::tree_dbc::CanId;
+
+/// PDU format values from here on are PDU2 (broadcast) parameter groups
+const PDU2_FORMAT_START: u32 = 240;
+
+/// Destination address meaning 'all nodes'
+pub const GLOBAL_ADDRESS: u8 = 0xFF;
+
+/// Fields of a 29 bit J1939 identifier
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct J1939Id {
+    /// Priority, 0 (highest) to 7
+    pub priority: u8,
+    /// Parameter group number, with the destination address removed for PDU1 groups
+    pub pgn: u32,
+    /// Address of the sending node
+    pub source_address: u8,
+    /// Address of the receiving node for PDU1 (peer to peer) groups, `None`
+    /// for PDU2 groups which are always broadcast
+    pub destination_address: Option<u8>,
+}
+
+impl J1939Id {
+    /// Splits a 29 bit identifier into its J1939 fields
+    pub fn from_raw(id: u32) -> Self {
+        let pdu_format = (id >> 16) & 0xFF;
+        let pdu_specific = ((id >> 8) & 0xFF) as u8;
+        let (pgn, destination_address) = if pdu_format < PDU2_FORMAT_START {
+            ((id >> 8) & 0x3FF00, Some(pdu_specific))
+        } else {
+            ((id >> 8) & 0x3FFFF, None)
+        };
+        Self {
+            priority: ((id >> 26) & 0x07) as u8,
+            pgn,
+            source_address: (id & 0xFF) as u8,
+            destination_address,
+        }
+    }
+
+    /// J1939 fields of a CAN ID, `None` for standard (11 bit) IDs
+    pub fn from_can_id(id: CanId) -> Option<Self> {
+        match id {
+            CanId::Extended(raw) => Some(Self::from_raw(raw)),
+            CanId::Standard(_) => None,
+        }
+    }
+
+    /// Builds the 29 bit identifier back from its fields
+    pub fn to_raw(&self) -> u32 {
+        let mut id = ((self.priority as u32 & 0x07) << 26) | ((self.pgn & 0x3FFFF) << 8) | self.source_address as u32;
+        if let Some(da) = self.destination_address {
+            id = (id & !0xFF00) | ((da as u32) << 8);
+        }
+        id
+    }
+}
+
+impl std::fmt::Display for J1939Id {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PGN {} (0x{:05X}), SA 0x{:02X}", self.pgn, self.pgn, self.source_address)?;
+        match self.destination_address {
+            Some(GLOBAL_ADDRESS) => write!(f, ", DA global")?,
+            Some(da) => write!(f, ", DA 0x{:02X}", da)?,
+            None => {},
+        }
+        write!(f, ", priority {}", self.priority)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_dbc_from_bytes;
+
+    #[test]
+    fn pdu1_round_trip() {
+        // Request (PGN 59904) from 0xF9 to 0x21
+        let id = J1939Id::from_raw(0x18EA21F9);
+        assert_eq!(id, J1939Id { priority: 6, pgn: 0xEA00, source_address: 0xF9, destination_address: Some(0x21) });
+        assert_eq!(id.to_raw(), 0x18EA21F9);
+        assert_eq!(id.to_string(), "PGN 59904 (0x0EA00), SA 0xF9, DA 0x21, priority 6");
+        let global = J1939Id { destination_address: Some(GLOBAL_ADDRESS), ..id };
+        assert_eq!(global.to_raw(), 0x18EAFFF9);
+        assert_eq!(global.to_string(), "PGN 59904 (0x0EA00), SA 0xF9, DA global, priority 6");
+    }
+
+    #[test]
+    fn pdu2_round_trip() {
+        // EEC1 (PGN 61444) from the engine
+        let id = J1939Id::from_raw(0x0CF00400);
+        assert_eq!(id, J1939Id { priority: 3, pgn: 0xF004, source_address: 0x00, destination_address: None });
+        assert_eq!(id.to_raw(), 0x0CF00400);
+        assert_eq!(id.to_string(), "PGN 61444 (0x0F004), SA 0x00, priority 3");
+        // Data page bit
+        let id = J1939Id::from_raw(0x19FEF117);
+        assert_eq!((id.pgn, id.source_address, id.destination_address), (0x1FEF1, 0x17, None));
+        assert_eq!(id.to_raw(), 0x19FEF117);
+    }
+
+    #[test]
+    fn from_can_id() {
+        assert_eq!(J1939Id::from_can_id(CanId::Extended(0x0CF00400)), Some(J1939Id::from_raw(0x0CF00400)));
+        assert_eq!(J1939Id::from_can_id(CanId::Standard(0x700)), None);
+    }
+
+    const DBC: &str = r#"VERSION ""
+
+NS_ :
+
+BS_:
+
+BU_: Engine Tool
+BO_ 2364540158 EEC1: 8 Engine
+ SG_ EngineSpeed : 24|16@1+ (0.125,0) [0|8031.875] "rpm" Vector__XXX
+
+BO_ 2565668862 TP_CM: 8 Tool
+ SG_ Control : 0|8@1+ (1,0) [0|255] "" Vector__XXX
+
+BO_ 256 Local: 8 Tool
+ SG_ Value : 0|8@1+ (1,0) [0|255] "" Vector__XXX
+
+BA_DEF_  "ProtocolType" STRING ;
+BA_DEF_DEF_  "ProtocolType" "J1939";
+"#;
+
+    #[test]
+    fn message_by_pgn() {
+        let dbc = load_dbc_from_bytes(DBC.as_bytes()).unwrap();
+        assert_eq!(dbc.message_by_pgn(0xF004).unwrap().name, "EEC1");
+        assert_eq!(dbc.message_by_pgn(0xEC00).unwrap().name, "TP_CM");
+        assert_eq!(dbc.message_by_pgn(0xF005), None);
+        assert!(!dbc.is_j1939(dbc.message_by_name("Local").unwrap()));
+
+        // Frames match by PGN, whatever their priority, source and destination
+        for (frame, name) in [(0x0CF00400, "EEC1"), (0x18F00417, "EEC1"), (0x1CEC00F9, "TP_CM"), (0x1CEC21F9, "TP_CM")] {
+            assert_eq!(dbc.message_for_frame(CanId::Extended(frame)).map(|m| m.name.as_str()), Some(name), "{:X}", frame);
+        }
+        assert_eq!(dbc.message_for_frame(CanId::Extended(0x18F00500)), None);
+        assert_eq!(dbc.message_for_frame(CanId::Standard(256)).unwrap().name, "Local");
+    }
+}
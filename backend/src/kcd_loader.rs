@@ -0,0 +1,249 @@
+//! Loader for Kayak KCD (XML) CAN definition files.
+//!
+//! Each `<Bus>` of a KCD file is a separate network, so like ARXML clusters
+//! one bus is loaded at a time. `<Multiplex>` elements become the multiplexor
+//! signal of their message, and the signals of each `<MuxGroup>` are multiplexed
+//! on the group's `count`.
+
+use std::collections::HashMap;
+
+use roxmltree::{Document, Node};
+
+use crate::{
+    add_loader_attribute_definitions, ecu_entry, msb0_to_dbc_start_bit, physical_range,
+    tree_dbc::{AttributeValue, ByteOrder, CanId, Message, MuxCondition, Multiplexing, Signal, SignalType, TreeDbc, ValueType},
+    CanResult, CanViewError, NULL_SENDER,
+};
+
+/// Largest number of values a `<LabelGroup>` may cover, as each value gets its own entry
+const MAX_LABEL_GROUP_VALUES: u64 = 1024;
+
+fn err<T>(msg: String) -> CanResult<T> {
+    Err(CanViewError::KcdError(msg))
+}
+
+fn elements<'a, 'i: 'a>(node: Node<'a, 'i>, tag: &'a str) -> impl Iterator<Item = Node<'a, 'i>> + 'a {
+    node.children().filter(move |c| c.is_element() && c.tag_name().name() == tag)
+}
+
+fn element<'a, 'i: 'a>(node: Node<'a, 'i>, tag: &'a str) -> Option<Node<'a, 'i>> {
+    elements(node, tag).next()
+}
+
+fn parse_u64(s: &str) -> Option<u64> {
+    let s = s.trim();
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+fn attr_u64(node: Node, name: &str) -> Option<u64> {
+    node.attribute(name).and_then(parse_u64)
+}
+
+fn attr_f64(node: Node, name: &str) -> Option<f64> {
+    node.attribute(name).and_then(|v| v.trim().parse().ok())
+}
+
+fn notes(node: Node) -> Option<String> {
+    element(node, "Notes").and_then(|n| n.text()).map(|t| t.trim().to_string()).filter(|t| !t.is_empty())
+}
+
+/// Node names referenced by `<NodeRef>`s inside `parent` (A `<Producer>` or `<Consumer>`)
+fn node_refs(parent: Option<Node>, nodes: &HashMap<String, String>) -> Vec<String> {
+    let mut res: Vec<String> = Vec::new();
+    for r in parent.into_iter().flat_map(|p| elements(p, "NodeRef")) {
+        if let Some(name) = r.attribute("id").and_then(|id| nodes.get(id)) {
+            if !res.contains(name) {
+                res.push(name.clone())
+            }
+        }
+    }
+    res
+}
+
+/// Lists the names of all buses in a KCD file
+pub fn kcd_buses(bytes: &[u8]) -> CanResult<Vec<String>> {
+    let s = std::str::from_utf8(bytes).map_err(|e| CanViewError::KcdError(format!("KCD is not valid UTF-8: {}", e)))?;
+    let doc = Document::parse(s).map_err(|e| CanViewError::KcdError(format!("Invalid XML: {}", e)))?;
+    Ok(elements(doc.root_element(), "Bus").map(|b| b.attribute("name").unwrap_or_default().to_string()).collect())
+}
+
+/// Loads the bus named `bus` from a KCD file. If `bus` is `None` the file
+/// must contain exactly one bus.
+pub fn load_kcd_from_bytes(bytes: &[u8], bus: Option<&str>) -> CanResult<TreeDbc> {
+    let s = std::str::from_utf8(bytes).map_err(|e| CanViewError::KcdError(format!("KCD is not valid UTF-8: {}", e)))?;
+    let doc = Document::parse(s).map_err(|e| CanViewError::KcdError(format!("Invalid XML: {}", e)))?;
+    let root = doc.root_element();
+    if root.tag_name().name() != "NetworkDefinition" {
+        return err(format!("Expected NetworkDefinition root element, found {}", root.tag_name().name()))
+    }
+    let buses: Vec<Node> = elements(root, "Bus").collect();
+    let bus_node = match bus {
+        Some(name) => match buses.iter().find(|b| b.attribute("name") == Some(name)) {
+            Some(b) => *b,
+            None => return err(format!("No bus named {}", name)),
+        },
+        None => match buses.as_slice() {
+            [b] => *b,
+            [] => return err("KCD contains no buses".into()),
+            _ => {
+                let names: Vec<&str> = buses.iter().filter_map(|b| b.attribute("name")).collect();
+                return err(format!("KCD contains multiple buses, select one of: {}", names.join(", ")))
+            }
+        },
+    };
+
+    let mut dbc = TreeDbc::default();
+    let mut nodes = HashMap::new();
+    for node in elements(root, "Node") {
+        if let (Some(id), Some(name)) = (node.attribute("id"), node.attribute("name")) {
+            nodes.insert(id.to_string(), name.to_string());
+            ecu_entry(&mut dbc, name);
+        }
+    }
+
+    for message in elements(bus_node, "Message") {
+        let name = message.attribute("name").unwrap_or_default();
+        let raw_id = match attr_u64(message, "id") {
+            Some(id) if id <= u64::from(u32::MAX) => id as u32,
+            _ => return err(format!("Message {} has no valid id", name)),
+        };
+        let mut msg = Message {
+            id: CanId::new(raw_id, message.attribute("format") == Some("extended")),
+            name: name.to_string(),
+            comment: notes(message),
+            length_bytes: match message.attribute("length") {
+                // "auto" length is derived from the signals below
+                Some("auto") | None => 0,
+                Some(_) => attr_u64(message, "length").unwrap_or(8),
+            },
+            ..Default::default()
+        };
+        if let Some(interval) = attr_u64(message, "interval") {
+            msg.attributes.insert("GenMsgCycleTime".into(), AttributeValue::Int(interval as i64));
+        }
+
+        for signal in elements(message, "Signal") {
+            msg.signals.push(load_signal(signal, &nodes, Multiplexing::Plain)?);
+        }
+        for mux in elements(message, "Multiplex") {
+            let mut mux_signal = load_signal(mux, &nodes, Multiplexing::Multiplexor)?;
+            // A multiplexor is always a plain integer
+            mux_signal.signal_type = SignalType::Linear { multi: 1.0, offset: 0.0 };
+            let mux_name = mux_signal.name.clone();
+            msg.signals.push(mux_signal);
+            for group in elements(mux, "MuxGroup") {
+                let count = match attr_u64(group, "count") {
+                    Some(c) => c,
+                    None => return err(format!("MuxGroup of {} has no count", mux_name)),
+                };
+                let condition = MuxCondition { multiplexor: mux_name.clone(), ranges: vec![(count, count)] };
+                for signal in elements(group, "Signal") {
+                    msg.signals.push(load_signal(signal, &nodes, Multiplexing::Multiplexed(condition.clone()))?);
+                }
+            }
+        }
+
+        if msg.length_bytes == 0 {
+            let last_bit = msg.signals.iter()
+                .flat_map(|s| crate::signal_parser::signal_bit_positions(s.start_bit, s.length_bits, s.order))
+                .max();
+            msg.length_bytes = last_bit.map(|b| b / 8 + 1).unwrap_or(0);
+        }
+
+        let sender = node_refs(element(message, "Producer"), &nodes).into_iter().next();
+        let ecu_idx = ecu_entry(&mut dbc, sender.as_deref().unwrap_or(NULL_SENDER));
+        dbc.ecus[ecu_idx].messages.push(msg);
+    }
+    add_loader_attribute_definitions(&mut dbc);
+    dbc.reindex();
+    Ok(dbc)
+}
+
+fn load_signal(signal: Node, nodes: &HashMap<String, String>, multiplexing: Multiplexing) -> CanResult<Signal> {
+    let name = signal.attribute("name").unwrap_or_default().to_string();
+    let offset = match attr_u64(signal, "offset") {
+        Some(o) => o,
+        None => return err(format!("Signal {} has no offset", name)),
+    };
+    let length = attr_u64(signal, "length").unwrap_or(1);
+    let order = match signal.attribute("endianess") {
+        Some("big") => ByteOrder::BigEndian,
+        _ => ByteOrder::LittleEndian,
+    };
+    let start_bit = match order {
+        ByteOrder::LittleEndian => offset,
+        ByteOrder::BigEndian => msb0_to_dbc_start_bit(offset),
+    };
+
+    let value = element(signal, "Value");
+    let value_type = match (value.and_then(|v| v.attribute("type")), length) {
+        (Some("single"), 32) => ValueType::Float32,
+        (Some("double"), 64) => ValueType::Float64,
+        (Some(t @ ("single" | "double")), _) => return err(format!("Signal {} is {} but {} bits long", name, t, length)),
+        _ => ValueType::Integer,
+    };
+    let signed = value_type != ValueType::Integer || value.and_then(|v| v.attribute("type")) == Some("signed");
+    let multi = value.and_then(|v| attr_f64(v, "slope")).unwrap_or(1.0);
+    let intercept = value.and_then(|v| attr_f64(v, "intercept")).unwrap_or(0.0);
+
+    let mut labels = Vec::new();
+    if let Some(set) = element(signal, "LabelSet") {
+        for label in set.children().filter(|c| c.is_element()) {
+            let text = label.attribute("name").unwrap_or_default().to_string();
+            match label.tag_name().name() {
+                "Label" => if let Some(v) = attr_u64(label, "value") {
+                    labels.push((v as i64, text))
+                },
+                "LabelGroup" => if let (Some(from), Some(to)) = (attr_u64(label, "from"), attr_u64(label, "to")) {
+                    let count = to.saturating_sub(from).saturating_add(1);
+                    if count > MAX_LABEL_GROUP_VALUES {
+                        return err(format!("Label group {} of signal {} covers {} values, at most {} are supported",
+                            text, name, count, MAX_LABEL_GROUP_VALUES))
+                    }
+                    labels.extend((from..=to).map(|v| (v as i64, text.clone())))
+                },
+                _ => {}
+            }
+        }
+    }
+    // 1 bit signals with labels are enums rather than bools, so the labels are kept
+    let signal_type = if !labels.is_empty() && value_type == ValueType::Integer {
+        labels.sort_by_key(|l| l.0);
+        SignalType::Enum { entries: labels, multi, offset: intercept }
+    } else if length == 1 && multi == 1.0 && intercept == 0.0 {
+        SignalType::Bool
+    } else {
+        SignalType::Linear { multi, offset: intercept }
+    };
+
+    let (min, max) = match value_type {
+        ValueType::Integer => {
+            let (min, max) = physical_range(&SignalType::Linear { multi, offset: intercept }, length, signed);
+            (
+                value.and_then(|v| attr_f64(v, "min")).unwrap_or(min),
+                value.and_then(|v| attr_f64(v, "max")).unwrap_or(max),
+            )
+        }
+        _ => (0.0, 0.0),
+    };
+
+    Ok(Signal {
+        name,
+        comment: notes(signal),
+        signal_type,
+        order,
+        start_bit,
+        length_bits: length,
+        unit: value.and_then(|v| v.attribute("unit")).unwrap_or_default().to_string(),
+        min,
+        max,
+        signed,
+        value_type,
+        multiplexing,
+        receivers: node_refs(element(signal, "Consumer"), nodes),
+        ..Default::default()
+    })
+}
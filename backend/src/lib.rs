@@ -3,44 +3,178 @@ use can_dbc::Signal;
 use can_dbc::*;
 pub use tree_dbc::TreeDbc;
 pub mod tree_dbc;
+pub mod j1939;
+pub use j1939::J1939Id;
 pub type CanResult<T> = Result<T, CanViewError>;
 pub mod signal_parser;
 pub use signal_parser::*;
+pub mod message_decoder;
+pub use message_decoder::{decode_message, DbcDecoder, DecodedMessage, DecodedSignal, MessageDecoder};
+pub mod signal_encoder;
+pub use signal_encoder::*;
+pub mod dbc_writer;
+pub use dbc_writer::write_dbc;
+pub mod dbc_editor;
+pub use dbc_editor::DbcEditor;
+pub mod dbc_lint;
+pub use dbc_lint::{lint_dbc, Diagnostic, Severity};
+pub mod dbc_search;
+pub use dbc_search::{search_dbc, SearchField, SearchResult, SearchTarget};
+pub mod dbc_merge;
+pub use dbc_merge::{merge_dbcs, ConflictSubject, DbcConflict, MergedDbc};
+pub mod arxml_loader;
+pub use arxml_loader::{arxml_can_clusters, load_arxml_from_bytes};
+pub mod kcd_loader;
+pub use kcd_loader::{kcd_buses, load_kcd_from_bytes};
+pub mod sym_loader;
+pub use sym_loader::load_sym_from_bytes;
+pub mod rust_codegen;
+pub use rust_codegen::generate_rust;
 
 
-#[derive(Debug, Clone)]
-pub enum CanViewError {
-    DbcError(String),
-    SignalParseError(String)
+/// Error found while parsing a DBC file
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DbcParseError {
+    /// File the DBC was loaded from, if known
+    pub file: Option<String>,
+    /// Line of the error, starting at 1. 0 if the error has no location
+    pub line: usize,
+    /// Column of the error in characters, starting at 1
+    pub column: usize,
+    /// Text of the line containing the error, cut down to the part around the error if the line is long
+    pub snippet: String,
+    /// Column of the error within `snippet`, starting at 1
+    pub snippet_column: usize,
+    pub reason: String,
 }
 
-impl ToString for CanViewError {
-    fn to_string(&self) -> String {
-        format!("{:?}", self)
+/// Longest snippet shown for an error, DBC lines can be very long
+const MAX_SNIPPET_CHARS: usize = 120;
+
+impl DbcParseError {
+    /// Locates the error at `rest`, which must be a subslice of `input`
+    fn at(input: &str, rest: &str, reason: String) -> Self {
+        // Point at the offending token rather than the whitespace before it
+        let rest = rest.trim_start();
+        let reason = if rest.is_empty() {
+            format!("{} at end of file", reason)
+        } else {
+            reason
+        };
+        let offset = (rest.as_ptr() as usize).saturating_sub(input.as_ptr() as usize).min(input.len());
+        let before = &input[..offset];
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let line_end = input[offset..].find('\n').map(|i| offset + i).unwrap_or(input.len());
+        let column = input[line_start..offset].chars().count() + 1;
+        let (snippet, snippet_column) = Self::snippet(input[line_start..line_end].trim_end(), column);
+        Self {
+            file: None,
+            line: before.matches('\n').count() + 1,
+            column,
+            snippet,
+            snippet_column,
+            reason,
+        }
+    }
+
+    /// Cuts a window of at most [MAX_SNIPPET_CHARS] around `column` out of a line,
+    /// returning it with the column of the error inside the window
+    fn snippet(line: &str, column: usize) -> (String, usize) {
+        let len = line.chars().count();
+        if len <= MAX_SNIPPET_CHARS {
+            return (line.to_string(), column)
+        }
+        let start = column.saturating_sub(1 + MAX_SNIPPET_CHARS / 2).min(len - MAX_SNIPPET_CHARS);
+        let end = start + MAX_SNIPPET_CHARS;
+        let mut snippet = String::new();
+        if start > 0 {
+            snippet.push_str("...");
+        }
+        snippet.extend(line.chars().skip(start).take(MAX_SNIPPET_CHARS));
+        if end < len {
+            snippet.push_str("...");
+        }
+        let prefix = if start > 0 { 3 } else { 0 };
+        (snippet, column - start + prefix)
     }
-}
 
-impl<'a> From<can_dbc::Error<'a>> for CanViewError {
-    fn from(e: can_dbc::Error<'a>) -> Self {
+    fn from_can_dbc(input: &str, e: can_dbc::Error) -> Self {
         match e {
-            can_dbc::Error::Incomplete(_curr_dbc, _left_over) => {
-                Self::DbcError("Incomplete DBC!".into())
+            can_dbc::Error::Incomplete(_curr_dbc, rest) => {
+                let statement = rest.split_whitespace().next().unwrap_or_default();
+                Self::at(input, rest, format!("Cannot parse statement starting with '{}'", statement))
             },
-            can_dbc::Error::Nom(nom_err) => {
-                Self::DbcError(format!("DBC Parse nom error: {}", nom_err))
+            can_dbc::Error::Nom(nom::Err::Error(e) | nom::Err::Failure(e)) => {
+                Self::at(input, e.input, format!("Syntax error ({:?})", e.code))
             },
-            can_dbc::Error::MultipleMultiplexors => {
-                Self::DbcError("Multiple identical Multiplexors exist in DBC!".into())
+            can_dbc::Error::Nom(nom::Err::Incomplete(_)) => {
+                Self::at(input, &input[input.len()..], "Incomplete input".into())
+            },
+            can_dbc::Error::MultipleMultiplexors => Self {
+                reason: "Multiple identical Multiplexors exist in DBC".into(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for DbcParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file)?;
+        }
+        if self.line == 0 {
+            return write!(f, "{}", self.reason)
+        }
+        write!(f, "{}:{}: {}", self.line, self.column, self.reason)?;
+        if self.snippet.is_empty() {
+            return Ok(())
+        }
+        writeln!(f)?;
+        writeln!(f, "    {}", self.snippet)?;
+        write!(f, "    {:>width$}", "^", width = self.snippet_column)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum CanViewError {
+    DbcError(DbcParseError),
+    /// A database file could not be read
+    IoError { file: String, reason: String },
+    SignalParseError(String),
+    SignalEncodeError(String),
+    ValueOutOfRange { signal: String, value: f64, min: f64, max: f64 },
+    ValidationError(String),
+    ArxmlError(String),
+    KcdError(String),
+    SymError(String)
+}
+
+impl std::fmt::Display for CanViewError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CanViewError::DbcError(e) => write!(f, "DBC error: {}", e),
+            CanViewError::IoError { file, reason } => write!(f, "Cannot read {}: {}", file, reason),
+            CanViewError::SignalParseError(e) => write!(f, "Cannot decode signal: {}", e),
+            CanViewError::SignalEncodeError(e) => write!(f, "Cannot encode signal: {}", e),
+            CanViewError::ValueOutOfRange { signal, value, min, max } => {
+                write!(f, "Value {} of {} is outside of its range ({} to {})", value, signal, min, max)
             },
+            CanViewError::ValidationError(e) => write!(f, "Invalid DBC: {}", e),
+            CanViewError::ArxmlError(e) => write!(f, "ARXML error: {}", e),
+            CanViewError::KcdError(e) => write!(f, "KCD error: {}", e),
+            CanViewError::SymError(e) => write!(f, "SYM error: {}", e),
         }
     }
 }
 
+impl std::error::Error for CanViewError {}
+
 fn locate_signal_comment(dbc: &DBC, msg_id: &MessageId, sig_name: &String) -> Option<String> {
     for c in dbc.comments() {
         if let Comment::Signal { message_id, signal_name, comment } = c {
             if message_id == msg_id && signal_name == sig_name {
-                return Some(comment.clone())
+                return Some(unescape(comment))
             }
         }
     }
@@ -51,40 +185,393 @@ fn locate_message_comment(dbc: &DBC, msg_id: &MessageId) -> Option<String> {
     for c in dbc.comments() {
         if let Comment::Message { message_id, comment } = c {
             if message_id == msg_id {
-                return Some(comment.clone())
+                return Some(unescape(comment))
             }
         }
     }
     None
 }
 
+fn locate_node_comment(dbc: &DBC, name: &str) -> Option<String> {
+    dbc.comments().iter().find_map(|c| match c {
+        Comment::Node { node_name, comment } if node_name == name => Some(unescape(comment)),
+        _ => None
+    })
+}
+
+fn to_value_table(entries: &[ValDescription]) -> tree_dbc::ValueTable {
+    let mut x: tree_dbc::ValueTable = Vec::new();
+    for e in entries {
+        x.push((*e.a() as i64, unescape(e.b())))
+    }
+    x.sort_by(|e,f| e.0.partial_cmp(&f.0).unwrap());
+    x
+}
+
 fn get_signal_type(dbc: &DBC, message: &MessageId, signal: &Signal) -> tree_dbc::SignalType {
-    if signal.signal_size == 1 {
-        return tree_dbc::SignalType::Bool
+    let (multi, offset) = (signal.factor, signal.offset);
+    if dbc.extended_value_type_for_signal(*message, signal.name()).is_some_and(|t| *t != SignalExtendedValueType::SignedOrUnsignedInteger) {
+        // Value descriptions cannot apply to IEEE values
+        return tree_dbc::SignalType::Linear { multi, offset }
     }
-    // Iterate over all value descriptions
+    // Iterate over all value descriptions. 1 bit signals with them are enums
+    // too, so their labels are kept
     let m = *message;
     let v = dbc.value_descriptions_for_signal(m, signal.name());
     if let Some(enum_entries) = v {
-        let mut x: Vec<(i64, String)> = Vec::new();
-        for e in enum_entries {
-            x.push((*e.a() as i64, e.b().to_string()))
+        tree_dbc::SignalType::Enum { entries: to_value_table(enum_entries), multi, offset }
+    } else if signal.signal_size == 1 && multi == 1.0 && offset == 0.0 {
+        // A bool cannot be scaled, so scaled 1 bit signals are kept as numbers
+        tree_dbc::SignalType::Bool
+    } else {
+        tree_dbc::SignalType::Linear { multi, offset }
+    }
+}
+
+fn get_signal_multiplexing(dbc: &DBC, message: &Message, signal: &Signal) -> tree_dbc::Multiplexing {
+    // Extended multiplexing (SG_MUL_VAL_) overrides the plain m<n> indicator
+    let ext: Vec<&ExtendedMultiplex> = dbc.extended_multiplex().iter()
+        .filter(|e| e.message_id() == message.message_id() && e.signal_name() == signal.name())
+        .collect();
+    let condition = |v: u64| {
+        if !ext.is_empty() {
+            tree_dbc::MuxCondition {
+                multiplexor: ext[0].multiplexor_signal_name().clone(),
+                ranges: ext.iter()
+                    .flat_map(|e| e.mappings().iter().map(|m| (*m.min_value(), *m.max_value())))
+                    .collect()
+            }
+        } else {
+            let mux_name = message.signals().iter()
+                .find(|s| *s.multiplexer_indicator() == MultiplexIndicator::Multiplexor)
+                .map(|s| s.name().clone())
+                .unwrap_or_default();
+            tree_dbc::MuxCondition { multiplexor: mux_name, ranges: vec![(v, v)] }
+        }
+    };
+    match signal.multiplexer_indicator() {
+        MultiplexIndicator::Plain => tree_dbc::Multiplexing::Plain,
+        // Some tools only mark nested multiplexors with M, relying on SG_MUL_VAL_ for the rest
+        MultiplexIndicator::Multiplexor if !ext.is_empty() => tree_dbc::Multiplexing::MultiplexorAndMultiplexed(condition(0)),
+        MultiplexIndicator::Multiplexor => tree_dbc::Multiplexing::Multiplexor,
+        MultiplexIndicator::MultiplexedSignal(v) => tree_dbc::Multiplexing::Multiplexed(condition(*v)),
+        MultiplexIndicator::MultiplexorAndMultiplexedSignal(v) => tree_dbc::Multiplexing::MultiplexorAndMultiplexed(condition(*v)),
+    }
+}
+
+/// Parses the body of a `BA_DEF_` entry, e.g. `"GenMsgCycleTime" INT 0 1000`,
+/// which can-dbc leaves unparsed
+fn parse_attribute_definition(object: tree_dbc::AttributeObject, raw: &str) -> Option<tree_dbc::AttributeDefinition> {
+    let raw = raw.trim().strip_prefix('"')?;
+    let (name, rest) = raw.split_once('"')?;
+    let rest = rest.trim();
+    let (kind, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let nums: Vec<&str> = args.split_whitespace().collect();
+    let num = |idx: usize| nums.get(idx).and_then(|n| n.parse::<f64>().ok()).unwrap_or_default();
+    let value_type = match kind {
+        "INT" => tree_dbc::AttributeValueType::Int { min: num(0) as i64, max: num(1) as i64 },
+        "HEX" => tree_dbc::AttributeValueType::Hex { min: num(0) as i64, max: num(1) as i64 },
+        "FLOAT" => tree_dbc::AttributeValueType::Float { min: num(0), max: num(1) },
+        "STRING" => tree_dbc::AttributeValueType::String,
+        "ENUM" => tree_dbc::AttributeValueType::Enum(
+            args.split(',')
+                .map(|e| unescape(e.trim().trim_matches('"')))
+                .filter(|e| !e.is_empty())
+                .collect()
+        ),
+        _ => return None
+    };
+    Some(tree_dbc::AttributeDefinition { name: name.to_string(), object, value_type, default: None })
+}
+
+fn get_attribute_definitions(dbc: &DBC) -> Vec<tree_dbc::AttributeDefinition> {
+    let mut defs = Vec::new();
+    for d in dbc.attribute_definitions() {
+        let parsed = match d {
+            AttributeDefinition::Plain(raw) => parse_attribute_definition(tree_dbc::AttributeObject::Network, raw),
+            AttributeDefinition::Node(raw) => parse_attribute_definition(tree_dbc::AttributeObject::Node, raw),
+            AttributeDefinition::Message(raw) => parse_attribute_definition(tree_dbc::AttributeObject::Message, raw),
+            AttributeDefinition::Signal(raw) => parse_attribute_definition(tree_dbc::AttributeObject::Signal, raw),
+            AttributeDefinition::EnvironmentVariable(raw) => parse_attribute_definition(tree_dbc::AttributeObject::EnvironmentVariable, raw),
+        };
+        if let Some(mut def) = parsed {
+            def.default = dbc.attribute_defaults().iter()
+                .find(|a| *a.attribute_name() == def.name)
+                .map(|a| convert_attribute_value(Some(&def), a.attribute_value()));
+            defs.push(def);
+        }
+    }
+    defs
+}
+
+/// Converts a can-dbc attribute value, using the definition to tell integers from floats
+fn convert_attribute_value(def: Option<&tree_dbc::AttributeDefinition>, v: &AttributeValue) -> tree_dbc::AttributeValue {
+    match v {
+        AttributeValue::AttributeValueU64(x) => tree_dbc::AttributeValue::Int(*x as i64),
+        AttributeValue::AttributeValueI64(x) => tree_dbc::AttributeValue::Int(*x),
+        AttributeValue::AttributeValueF64(x) => match def.map(|d| &d.value_type) {
+            Some(tree_dbc::AttributeValueType::Float { .. }) | None => tree_dbc::AttributeValue::Float(*x),
+            Some(_) => tree_dbc::AttributeValue::Int(*x as i64),
+        },
+        AttributeValue::AttributeValueCharString(x) => tree_dbc::AttributeValue::String(unescape(x)),
+    }
+}
+
+/// Collects the attribute values set on one DBC object. `select` returns the
+/// value if the attribute entry belongs to the object
+fn get_attributes<F>(dbc: &DBC, defs: &[tree_dbc::AttributeDefinition], select: F) -> tree_dbc::Attributes
+    where F: Fn(&AttributeValuedForObjectType) -> Option<&AttributeValue> {
+    let mut res = tree_dbc::Attributes::new();
+    for a in dbc.attribute_values() {
+        if let Some(v) = select(a.attribute_value()) {
+            let def = defs.iter().find(|d| d.name == *a.attribute_name());
+            res.insert(a.attribute_name().clone(), convert_attribute_value(def, v));
+        }
+    }
+    res
+}
+
+/// Name of the ECU owning messages without a transmitting node (`Vector__XXX` in DBC files)
+pub(crate) const NULL_SENDER: &str = "NULL SENDER";
+
+/// Returns the index of an ECU, creating it if it doesn't exist yet
+pub(crate) fn ecu_entry(dbc: &mut TreeDbc, name: &str) -> usize {
+    match dbc.ecus.iter().position(|e| e.name == name) {
+        Some(idx) => idx,
+        None => {
+            dbc.ecus.push(tree_dbc::Ecu { name: name.to_string(), ..Default::default() });
+            dbc.ecus.len() - 1
+        }
+    }
+}
+
+/// Loaders for other formats store cycle times and start values using the Vector
+/// attribute names. This adds the matching `BA_DEF_`s so the result can be written as a DBC.
+pub(crate) fn add_loader_attribute_definitions(dbc: &mut TreeDbc) {
+    let defs = [
+        ("GenMsgCycleTime", tree_dbc::AttributeObject::Message, tree_dbc::AttributeValueType::Int { min: 0, max: i64::from(u32::MAX) }),
+        ("GenSigStartValue", tree_dbc::AttributeObject::Signal, tree_dbc::AttributeValueType::Int { min: i64::MIN, max: i64::MAX }),
+    ];
+    for (name, object, value_type) in defs {
+        let used = dbc.messages().any(|m| match object {
+            tree_dbc::AttributeObject::Message => m.attributes.contains_key(name),
+            _ => m.signals.iter().any(|s| s.attributes.contains_key(name)),
+        });
+        if used && dbc.attribute_definition(name).is_none() {
+            dbc.attribute_definitions.push(tree_dbc::AttributeDefinition {
+                name: name.to_string(),
+                object,
+                value_type,
+                default: Some(tree_dbc::AttributeValue::Int(0)),
+            });
         }
-        x.sort_by(|e,f| e.0.partial_cmp(&f.0).unwrap());
+    }
+}
 
-        tree_dbc::SignalType::Enum(x)
+/// Physical value range covered by the raw range of the signal
+pub(crate) fn physical_range(signal_type: &tree_dbc::SignalType, length: u64, signed: bool) -> (f64, f64) {
+    let length = length.min(64) as i32;
+    let (raw_min, raw_max) = if signed {
+        (-(2f64.powi(length - 1)), 2f64.powi(length - 1) - 1.0)
     } else {
-        tree_dbc::SignalType::Linear { multi: signal.factor as f32, offset: signal.offset as f32 }
+        (0.0, 2f64.powi(length) - 1.0)
+    };
+    let (multi, offset) = signal_type.scaling();
+    let a = raw_min * multi + offset;
+    let b = raw_max * multi + offset;
+    (a.min(b), a.max(b))
+}
+
+/// KCD and SYM files number the bits of Motorola signals sequentially starting from the
+/// most significant bit of the first byte, and give the position of the signal's MSB.
+/// This converts that into the DBC start bit.
+pub(crate) fn msb0_to_dbc_start_bit(offset: u64) -> u64 {
+    8 * (offset / 8) + (7 - (offset % 8))
+}
+
+/// File formats which can be loaded into a [TreeDbc]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DatabaseFormat {
+    #[default]
+    Dbc,
+    Arxml,
+    Kcd,
+    Sym,
+}
+
+impl DatabaseFormat {
+    /// Picks the format from a file extension. Unknown extensions are treated as DBC.
+    pub fn from_path(path: &str) -> Self {
+        let ext = std::path::Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match ext.as_deref() {
+            Some("arxml") => Self::Arxml,
+            Some("kcd") => Self::Kcd,
+            Some("sym") => Self::Sym,
+            _ => Self::Dbc,
+        }
     }
 }
 
+/// Loads a database of any supported format. `network` selects the ARXML cluster
+/// or KCD bus to load, and is ignored for formats with a single network.
+pub fn load_database_from_bytes(b: &[u8], format: DatabaseFormat, network: Option<&str>) -> CanResult<TreeDbc> {
+    match format {
+        DatabaseFormat::Dbc => load_dbc_from_bytes(b),
+        DatabaseFormat::Arxml => load_arxml_from_bytes(b, network),
+        DatabaseFormat::Kcd => load_kcd_from_bytes(b, network),
+        DatabaseFormat::Sym => load_sym_from_bytes(b),
+    }
+}
+
+/// Reads and loads a database file, picking the format from its extension.
+/// Errors name the file they occurred in.
+pub fn load_database_from_file(path: &str, network: Option<&str>) -> CanResult<TreeDbc> {
+    let bytes = std::fs::read(path).map_err(|e| CanViewError::IoError { file: path.to_string(), reason: e.to_string() })?;
+    load_database_from_bytes(&bytes, DatabaseFormat::from_path(path), network).map_err(|e| match e {
+        CanViewError::DbcError(mut parse_error) => {
+            parse_error.file = Some(path.to_string());
+            CanViewError::DbcError(parse_error)
+        },
+        CanViewError::ArxmlError(e) => CanViewError::ArxmlError(format!("{}: {}", path, e)),
+        CanViewError::KcdError(e) => CanViewError::KcdError(format!("{}: {}", path, e)),
+        CanViewError::SymError(e) => CanViewError::SymError(format!("{}: {}", path, e)),
+        e => e
+    })
+}
+
+/// DBC files are usually written with the Windows-1252 code page rather than UTF-8,
+/// so if the file isn't valid UTF-8 each byte is taken as a Latin-1 character
+fn decode_dbc_text(b: &[u8]) -> std::borrow::Cow<'_, str> {
+    match std::str::from_utf8(b) {
+        Ok(s) => std::borrow::Cow::Borrowed(s),
+        Err(_) => std::borrow::Cow::Owned(b.iter().map(|c| *c as char).collect()),
+    }
+}
+
+/// Stand-ins for the `\"` and `\\` escapes in DBC strings, which can-dbc does not
+/// understand. They are as long as the escapes, so error positions stay the same
+const QUOTE_ESCAPE: &str = "\u{1}\u{2}";
+const BACKSLASH_ESCAPE: &str = "\u{1}\u{3}";
+
+/// Replaces escapes inside quoted strings with stand-ins, which [unescape]
+/// turns back into the escaped characters once the file is parsed
+fn hide_escapes(text: &str) -> std::borrow::Cow<'_, str> {
+    if !text.contains('\\') {
+        return std::borrow::Cow::Borrowed(text)
+    }
+    let mut res = String::with_capacity(text.len());
+    let mut in_string = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_string && c == '\\' {
+            match chars.peek() {
+                Some('"') => res.push_str(QUOTE_ESCAPE),
+                Some('\\') => res.push_str(BACKSLASH_ESCAPE),
+                // Lone backslashes are kept, E.g. in Windows paths
+                _ => {
+                    res.push(c);
+                    continue
+                }
+            }
+            chars.next();
+            continue
+        }
+        if c == '"' {
+            in_string = !in_string;
+        }
+        res.push(c);
+    }
+    std::borrow::Cow::Owned(res)
+}
+
+fn unescape(s: &str) -> String {
+    s.replace(QUOTE_ESCAPE, "\"").replace(BACKSLASH_ESCAPE, "\\")
+}
+
+fn get_environment_variables(dbc: &DBC, defs: &[tree_dbc::AttributeDefinition]) -> Vec<tree_dbc::EnvironmentVariable> {
+    dbc.environment_variables().iter().map(|ev| {
+        let name = ev.env_var_name();
+        let data_size = dbc.environment_variable_data().iter()
+            .find(|d| d.env_var_name() == name)
+            .map(|d| *d.data_size());
+        tree_dbc::EnvironmentVariable {
+            name: name.clone(),
+            // can-dbc reads data variables as integers, they are told apart by their ENVVAR_DATA_ entry
+            var_type: match (ev.env_var_type(), data_size) {
+                (EnvType::EnvTypeFloat, _) => tree_dbc::EnvVarType::Float,
+                (_, Some(_)) => tree_dbc::EnvVarType::Data,
+                _ => tree_dbc::EnvVarType::Integer,
+            },
+            min: *ev.min(),
+            max: *ev.max(),
+            unit: unescape(ev.unit()),
+            initial_value: *ev.initial_value(),
+            id: *ev.ev_id(),
+            access_type: match ev.access_type() {
+                AccessType::DummyNodeVector0 => 0,
+                AccessType::DummyNodeVector1 => 1,
+                AccessType::DummyNodeVector2 => 2,
+                AccessType::DummyNodeVector3 => 3,
+            },
+            access_nodes: ev.access_nodes().iter()
+                .filter_map(|n| match n {
+                    AccessNode::AccessNodeName(n) if n != "Vector__XXX" => Some(n.clone()),
+                    _ => None
+                })
+                .collect(),
+            data_size,
+            comment: dbc.comments().iter().find_map(|c| match c {
+                Comment::EnvVar { env_var_name, comment } if env_var_name == name => Some(unescape(comment)),
+                _ => None
+            }),
+            attributes: get_attributes(dbc, defs, |a| match a {
+                AttributeValuedForObjectType::EnvVariableAttributeValue(n, v) if n == name => Some(v),
+                _ => None
+            }),
+        }
+    }).collect()
+}
+
 pub fn load_dbc_from_bytes(b: &[u8]) -> CanResult<TreeDbc> {
-    let dbc = can_dbc::DBC::from_slice(b)?;
+    let decoded = decode_dbc_text(b);
+    let text = hide_escapes(&decoded);
+    let dbc = can_dbc::DBC::try_from(text.as_ref()).map_err(|e| {
+        let mut e = DbcParseError::from_can_dbc(&text, e);
+        e.snippet = e.snippet.replace(QUOTE_ESCAPE, "\\\"").replace(BACKSLASH_ESCAPE, "\\\\");
+        CanViewError::DbcError(e)
+    })?;
     let mut finished_dbc = tree_dbc::TreeDbc::default();
+    finished_dbc.version = unescape(&dbc.version().0);
+    finished_dbc.comment = dbc.comments().iter().find_map(|c| match c {
+        Comment::Plain { comment } => Some(unescape(comment)),
+        _ => None
+    });
+    let defs = get_attribute_definitions(&dbc);
+    let node_attributes = |node_name: &String| get_attributes(&dbc, &defs, |a| match a {
+        AttributeValuedForObjectType::NetworkNodeAttributeValue(node, v) if node == node_name => Some(v),
+        _ => None
+    });
+    // Declared nodes first, so nodes which only receive are kept
+    for name in dbc.nodes().iter().flat_map(|n| n.0.iter()) {
+        if !finished_dbc.ecus.iter().any(|e| e.name == *name) {
+            finished_dbc.ecus.push(tree_dbc::Ecu {
+                name: name.clone(),
+                comment: locate_node_comment(&dbc, name),
+                messages: Vec::new(),
+                attributes: node_attributes(name),
+            });
+        }
+    }
+    finished_dbc.value_tables = dbc.value_tables().iter()
+        .map(|t| (t.value_table_name().clone(), to_value_table(t.value_descriptions())))
+        .collect();
     for message in dbc.messages() {
         let mut signal_array = Vec::new();
         for signal in message.signals() {
-            let sig = tree_dbc::Signal {
+            let mut sig = tree_dbc::Signal {
                 name: signal.name().clone(),
                 comment: locate_signal_comment(&dbc, message.message_id(), signal.name()),
                 signal_type: get_signal_type(&dbc, message.message_id(), signal),
@@ -94,24 +581,64 @@ pub fn load_dbc_from_bytes(b: &[u8]) -> CanResult<TreeDbc> {
                 },
                 start_bit: signal.start_bit,
                 length_bits: signal.signal_size,
-                unit: signal.unit().clone(),
-                min: signal.min as f32,
-                max: signal.max as f32,
-                signed: *signal.value_type() == ValueType::Signed
+                unit: unescape(signal.unit()),
+                min: signal.min,
+                max: signal.max,
+                signed: *signal.value_type() == ValueType::Signed,
+                value_type: match dbc.extended_value_type_for_signal(*message.message_id(), signal.name()) {
+                    Some(SignalExtendedValueType::IEEEfloat32Bit) => tree_dbc::ValueType::Float32,
+                    Some(SignalExtendedValueType::IEEEdouble64bit) => tree_dbc::ValueType::Float64,
+                    _ => tree_dbc::ValueType::Integer,
+                },
+                multiplexing: get_signal_multiplexing(&dbc, message, signal),
+                receivers: signal.receivers().iter()
+                    .filter(|r| r.as_str() != "Vector__XXX")
+                    .cloned()
+                    .collect(),
+                attributes: get_attributes(&dbc, &defs, |a| match a {
+                    AttributeValuedForObjectType::SignalAttributeValue(id, name, v) if id == message.message_id() && name == signal.name() => Some(v),
+                    _ => None
+                }),
+                value_table: None,
             };
+            // DBC files have no syntax to reference a VAL_TABLE_ from a signal, tools copy the
+            // table into the signal's VAL_ instead. So find the table by its values.
+            if let Some(values) = sig.signal_type.entries() {
+                sig.value_table = finished_dbc.matching_value_table(values).map(String::from);
+            }
             signal_array.push(sig);
         }
         // Done with all signals
         let msg = tree_dbc::Message {
-            id: message.message_id().0,
+            id: tree_dbc::CanId::from_dbc(message.message_id().0),
             name: message.message_name().clone(),
             comment: locate_message_comment(&dbc, message.message_id()),
             length_bytes: *message.message_size(),
             signals: signal_array,
+            attributes: get_attributes(&dbc, &defs, |a| match a {
+                AttributeValuedForObjectType::MessageDefinitionAttributeValue(id, Some(v)) if id == message.message_id() => Some(v),
+                _ => None
+            }),
+            signal_groups: dbc.signal_groups().iter()
+                .filter(|g| g.message_id() == message.message_id())
+                .map(|g| tree_dbc::SignalGroup {
+                    name: g.signal_group_name().clone(),
+                    repetitions: *g.repetitions(),
+                    signals: g.signal_names().clone(),
+                })
+                .collect(),
+            transmitters: dbc.message_transmitters().iter()
+                .filter(|t| t.message_id() == message.message_id())
+                .flat_map(|t| t.transmitter())
+                .filter_map(|t| match t {
+                    Transmitter::NodeName(name) => Some(name.clone()),
+                    Transmitter::VectorXXX => None,
+                })
+                .collect(),
         };
         let sender_name: String = match message.transmitter() {
             Transmitter::NodeName(name) => name.clone(),
-            Transmitter::VectorXXX => "NULL SENDER".to_string(),
+            Transmitter::VectorXXX => NULL_SENDER.to_string(),
         };
         let mut add_ecu = true;
         for ecu in finished_dbc.ecus.iter_mut() {
@@ -123,11 +650,53 @@ pub fn load_dbc_from_bytes(b: &[u8]) -> CanResult<TreeDbc> {
         }
         if add_ecu {
             let ecu = tree_dbc::Ecu {
+                attributes: node_attributes(&sender_name),
+                comment: locate_node_comment(&dbc, &sender_name),
                 name: sender_name,
-                messages: vec![msg]
+                messages: vec![msg],
             };
             finished_dbc.ecus.push(ecu);
         }
     }
+    finished_dbc.attributes = get_attributes(&dbc, &defs, |a| match a {
+        AttributeValuedForObjectType::RawAttributeValue(v) => Some(v),
+        _ => None
+    });
+    finished_dbc.environment_variables = get_environment_variables(&dbc, &defs);
+    finished_dbc.attribute_definitions = defs;
+    finished_dbc.reindex();
     Ok(finished_dbc)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Position of the caret in the last line of the error message, relative to the snippet
+    fn caret_target(e: &DbcParseError) -> char {
+        let text = e.to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        let caret = lines[2].find('^').unwrap();
+        lines[1][caret..].chars().next().unwrap()
+    }
+
+    #[test]
+    fn error_in_short_line() {
+        let input = "VERSION \"\"\nBO_ 100 Msg: 8 ECU\n SG_ % : 0|8@1+ (1,0) [0|0] \"\" ECU\n";
+        let e = DbcParseError::at(input, &input[input.find('%').unwrap()..], "Bad name".into());
+        assert_eq!((e.line, e.column, e.snippet_column), (3, 6, 6));
+        assert_eq!(e.snippet, " SG_ % : 0|8@1+ (1,0) [0|0] \"\" ECU");
+        assert_eq!(caret_target(&e), '%');
+    }
+
+    #[test]
+    fn error_in_long_line() {
+        for (before, after) in [(10, 300), (200, 300), (300, 5)] {
+            let input = format!("{}%{}\nnext", "a".repeat(before), "b".repeat(after));
+            let e = DbcParseError::at(&input, &input[before..], "Bad".into());
+            assert_eq!(e.column, before + 1);
+            assert!(e.snippet.chars().count() <= MAX_SNIPPET_CHARS + 6, "{}", e.snippet);
+            assert_eq!(caret_target(&e), '%', "{}", e);
+        }
+    }
+}
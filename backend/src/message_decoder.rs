@@ -0,0 +1,464 @@
+//! Precompiled decoders for high frame rates.
+//!
+//! [parse_signal] works out the bit positions of a signal and searches its
+//! value descriptions on every call. A [MessageDecoder] does that work once
+//! per message: each signal becomes a byte range with a shift and mask, enum
+//! labels go into a hash map and multiplexor chains are resolved to signal
+//! indices, so a whole frame is decoded in a single pass.
+
+use std::collections::HashMap;
+
+use crate::signal_parser::{scale_integer, sign_extend, whole_scaling, ValueCheck};
+use crate::tree_dbc::{ByteOrder, CanId, Message, Multiplexing, Signal, SignalType, TreeDbc, ValueType};
+use crate::{ParsedSignal, ParsedValue, SignalParseError, SignalParseResult};
+
+/// Location of a signal's bits within the payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BitField {
+    order: ByteOrder,
+    /// First payload byte holding bits of the signal
+    first_byte: usize,
+    /// Number of bytes the signal spans, at most 9
+    byte_count: usize,
+    /// Shift which moves the signal's LSB to bit 0 of the bytes read
+    shift: u32,
+    mask: u64,
+}
+
+impl BitField {
+    fn new(start_bit: u64, length_bits: u64, order: ByteOrder) -> SignalParseResult<Self> {
+        if length_bits == 0 || length_bits > 64 {
+            return Err(SignalParseError::InvalidLength)
+        }
+        let (first, last, shift) = match order {
+            ByteOrder::LittleEndian => {
+                let last = start_bit + length_bits - 1;
+                (start_bit / 8, last / 8, start_bit % 8)
+            },
+            ByteOrder::BigEndian => {
+                // Motorola signals are contiguous when bits are counted MSB first within each byte
+                let msb = (start_bit / 8) * 8 + 7 - start_bit % 8;
+                let lsb = msb + length_bits - 1;
+                (msb / 8, lsb / 8, 7 - lsb % 8)
+            },
+        };
+        Ok(Self {
+            order,
+            first_byte: first as usize,
+            byte_count: (last - first + 1) as usize,
+            shift: shift as u32,
+            mask: u64::MAX >> (64 - length_bits),
+        })
+    }
+
+    /// Number of payload bytes needed to read the signal
+    fn required_len(&self) -> usize {
+        self.first_byte + self.byte_count
+    }
+
+    fn extract(&self, raw: &[u8]) -> SignalParseResult<u64> {
+        let bytes = raw.get(self.first_byte..self.required_len()).ok_or(SignalParseError::RangeTooBig)?;
+        let value = match self.order {
+            ByteOrder::LittleEndian => bytes.iter().rev().fold(0u128, |acc, b| (acc << 8) | *b as u128),
+            ByteOrder::BigEndian => bytes.iter().fold(0u128, |acc, b| (acc << 8) | *b as u128),
+        };
+        Ok((value >> self.shift) as u64 & self.mask)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Conversion {
+    Bool,
+    Linear { multi: f64, offset: f64, whole: Option<(i128, i128)> },
+    Enum { labels: HashMap<i64, String>, multi: f64, offset: f64, whole: Option<(i128, i128)> },
+    Float32 { multi: f64, offset: f64 },
+    Float64 { multi: f64, offset: f64 },
+}
+
+/// Multiplexor conditions from a signal up to the top level switch, as the
+/// index of each multiplexor signal and its allowed value ranges
+type MuxChain = Vec<(usize, Vec<(u64, u64)>)>;
+
+/// Decode plan of a single signal
+#[derive(Debug, Clone, PartialEq)]
+struct SignalDecoder {
+    bits: SignalParseResult<BitField>,
+    length_bits: u64,
+    signed: bool,
+    conversion: Conversion,
+    unit: Option<String>,
+    check: ValueCheck,
+    /// `None` if the chain is broken, so the signal is never present
+    mux_chain: Option<MuxChain>,
+}
+
+impl SignalDecoder {
+    fn new(msg: &Message, signal: &Signal) -> Self {
+        let bits = match (signal.value_type, signal.length_bits) {
+            (ValueType::Integer, _) | (ValueType::Float32, 32) | (ValueType::Float64, 64) =>
+                BitField::new(signal.start_bit, signal.length_bits, signal.order),
+            _ => Err(SignalParseError::InvalidLength),
+        };
+        let (multi, offset) = signal.signal_type.scaling();
+        let conversion = match (signal.value_type, &signal.signal_type) {
+            (ValueType::Float32, _) => Conversion::Float32 { multi, offset },
+            (ValueType::Float64, _) => Conversion::Float64 { multi, offset },
+            (ValueType::Integer, SignalType::Bool) => Conversion::Bool,
+            (ValueType::Integer, SignalType::Linear { .. }) => Conversion::Linear { multi, offset, whole: whole_scaling(multi, offset) },
+            (ValueType::Integer, SignalType::Enum { entries, .. }) => {
+                let mut labels = HashMap::with_capacity(entries.len());
+                for (value, label) in entries {
+                    // Like parse_signal, the first description of a value wins
+                    labels.entry(*value).or_insert_with(|| label.clone());
+                }
+                Conversion::Enum { labels, multi, offset, whole: whole_scaling(multi, offset) }
+            },
+        };
+        Self {
+            bits,
+            length_bits: signal.length_bits,
+            signed: signal.signed,
+            conversion,
+            unit: Some(signal.unit.clone()).filter(|u| !u.is_empty()),
+            check: ValueCheck::new(signal),
+            mux_chain: resolve_mux_chain(msg, signal),
+        }
+    }
+
+    fn decode(&self, raw: &[u8]) -> SignalParseResult<ParsedSignal> {
+        let bits = self.bits.as_ref().map_err(Clone::clone)?.extract(raw)?;
+        let data = match self.signed {
+            true => sign_extend(bits, self.length_bits) as i128,
+            false => bits as i128
+        };
+        let (raw, physical, exact, value) = match &self.conversion {
+            Conversion::Bool => (data, data as f64, Some(data), ParsedValue::Bool(data != 0)),
+            Conversion::Linear { multi, offset, whole } => {
+                let (physical, exact) = scale_integer(data, *multi, *offset, *whole);
+                (data, physical, exact, ParsedValue::Number(self.unit.clone()))
+            },
+            Conversion::Enum { labels, multi, offset, whole } => {
+                let label = i64::try_from(data).ok().and_then(|v| labels.get(&v)).cloned();
+                let (physical, exact) = scale_integer(data, *multi, *offset, *whole);
+                (data, physical, exact, ParsedValue::Enum(label))
+            },
+            Conversion::Float32 { multi, offset } => {
+                let physical = f32::from_bits(bits as u32) as f64 * multi + offset;
+                (bits as i128, physical, None, ParsedValue::Number(self.unit.clone()))
+            },
+            Conversion::Float64 { multi, offset } => {
+                let physical = f64::from_bits(bits) * multi + offset;
+                (bits as i128, physical, None, ParsedValue::Number(self.unit.clone()))
+            },
+        };
+        let status = self.check.status(bits, physical, &value);
+        Ok(ParsedSignal { raw, physical, exact, value, status })
+    }
+}
+
+/// Resolves the multiplexor chain of a signal to signal indices, following
+/// the same rules as [Message::is_signal_active]
+fn resolve_mux_chain(msg: &Message, signal: &Signal) -> Option<MuxChain> {
+    let mut chain = Vec::new();
+    let mut current = signal;
+    // Bound the walk by the signal count, so a cyclic DBC cannot hang us
+    for _ in 0..=msg.signals.len() {
+        let cond = match current.mux_condition() {
+            Some(c) => c,
+            None => return Some(chain)
+        };
+        let parent = msg.signals.iter().position(|s| s.name == cond.multiplexor)?;
+        chain.push((parent, cond.ranges.clone()));
+        current = &msg.signals[parent];
+    }
+    None
+}
+
+/// Signal decoded from a frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedSignal {
+    /// Index of the signal in the message's `signals`
+    pub index: usize,
+    pub value: SignalParseResult<ParsedSignal>,
+}
+
+/// All signals of a frame, decoded with [decode_message] or a [MessageDecoder]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DecodedMessage {
+    pub id: CanId,
+    /// Payload length defined by the database
+    pub expected_len: u64,
+    /// Length of the decoded payload
+    pub payload_len: usize,
+    /// Active multiplexors as (signal index, value), starting from the top
+    /// level switch. See [Message::active_mux_chain]
+    pub mux_chain: Vec<(usize, u64)>,
+    /// Whether the message has multiplexed signals. If `mux_chain` is empty
+    /// for such a message, its multiplexor could not be read
+    pub multiplexed: bool,
+    /// Every signal present in the frame, in the order of the message's `signals`
+    pub signals: Vec<DecodedSignal>,
+}
+
+impl DecodedMessage {
+    /// Returns true if the payload length differs from the database. Signals
+    /// past the end of a short payload fail with [SignalParseError::RangeTooBig]
+    pub fn length_mismatch(&self) -> bool {
+        self.payload_len as u64 != self.expected_len
+    }
+
+    /// Decoded value of the signal at `index` of the message's `signals`,
+    /// `None` if the signal is not present in the frame
+    pub fn signal(&self, index: usize) -> Option<&SignalParseResult<ParsedSignal>> {
+        self.signals.iter().find(|s| s.index == index).map(|s| &s.value)
+    }
+
+    /// Number of signals which decoded, but whose value is not valid
+    pub fn invalid_count(&self) -> usize {
+        self.signals.iter().filter(|s| matches!(&s.value, Ok(p) if !p.is_valid())).count()
+    }
+
+    /// Signals which could not be decoded, as (signal index, error)
+    pub fn errors(&self) -> impl Iterator<Item = (usize, &SignalParseError)> {
+        self.signals.iter().filter_map(|s| s.value.as_ref().err().map(|e| (s.index, e)))
+    }
+}
+
+/// Decodes every signal of `msg` present in the frame `raw`.
+///
+/// This compiles the message on every call, keep a [MessageDecoder] (or
+/// [DbcDecoder]) around when decoding the same message repeatedly
+pub fn decode_message(msg: &Message, raw: &[u8]) -> DecodedMessage {
+    MessageDecoder::new(msg).decode(raw)
+}
+
+/// Message compiled for fast decoding. Build it once per message and reuse
+/// it for every received frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageDecoder {
+    id: CanId,
+    length_bytes: u64,
+    signals: Vec<SignalDecoder>,
+    /// Index of every multiplexor switch, whose values are read before any other signal
+    multiplexors: Vec<usize>,
+    /// Index of the top level multiplexor switch
+    root_multiplexor: Option<usize>,
+    nested_multiplexors: Vec<NestedMultiplexor>,
+}
+
+/// Multiplexor switch which is itself multiplexed by `parent`
+#[derive(Debug, Clone, PartialEq)]
+struct NestedMultiplexor {
+    index: usize,
+    parent: usize,
+    /// Values of the parent for which this multiplexor is present
+    ranges: Vec<(u64, u64)>,
+}
+
+impl MessageDecoder {
+    pub fn new(msg: &Message) -> Self {
+        let nested_multiplexors = msg.signals.iter().enumerate().filter_map(|(idx, s)| match &s.multiplexing {
+            Multiplexing::MultiplexorAndMultiplexed(c) => {
+                let parent = msg.signals.iter().position(|p| p.name == c.multiplexor)?;
+                Some(NestedMultiplexor { index: idx, parent, ranges: c.ranges.clone() })
+            },
+            _ => None,
+        }).collect();
+        Self {
+            id: msg.id,
+            length_bytes: msg.length_bytes,
+            signals: msg.signals.iter().map(|s| SignalDecoder::new(msg, s)).collect(),
+            multiplexors: msg.signals.iter().enumerate().filter(|(_, s)| s.is_multiplexor()).map(|(idx, _)| idx).collect(),
+            root_multiplexor: msg.signals.iter().position(|s| s.multiplexing == Multiplexing::Multiplexor),
+            nested_multiplexors,
+        }
+    }
+
+    pub fn id(&self) -> CanId {
+        self.id
+    }
+
+    /// Decodes every signal present in the frame, taking multiplexing into account
+    pub fn decode(&self, raw: &[u8]) -> DecodedMessage {
+        // Multiplexor values by signal index. Unreadable multiplexors stay None,
+        // so signals depending on them are treated as not present
+        let mut mux_values = vec![None; self.signals.len()];
+        for idx in &self.multiplexors {
+            if let Ok(bits) = &self.signals[*idx].bits {
+                mux_values[*idx] = bits.extract(raw).ok();
+            }
+        }
+        let in_ranges = |v: u64, ranges: &[(u64, u64)]| ranges.iter().any(|(min, max)| v >= *min && v <= *max);
+        let is_active = |chain: &MuxChain| chain.iter().all(|(mux, ranges)| {
+            mux_values[*mux].is_some_and(|v| in_ranges(v, ranges))
+        });
+        let signals = self.signals.iter()
+            .enumerate()
+            .filter(|(_, s)| s.mux_chain.as_ref().is_some_and(is_active))
+            .map(|(index, s)| DecodedSignal { index, value: s.decode(raw) })
+            .collect();
+
+        let mut mux_chain: Vec<(usize, u64)> = Vec::new();
+        let mut current = self.root_multiplexor;
+        while let Some(mux) = current {
+            let value = match mux_values[mux] {
+                Some(v) => v,
+                None => break
+            };
+            if mux_chain.iter().any(|(idx, _)| *idx == mux) {
+                break;
+            }
+            mux_chain.push((mux, value));
+            current = self.nested_multiplexors.iter()
+                .find(|n| n.parent == mux && in_ranges(value, &n.ranges))
+                .map(|n| n.index);
+        }
+
+        DecodedMessage {
+            id: self.id,
+            expected_len: self.length_bytes,
+            payload_len: raw.len(),
+            mux_chain,
+            multiplexed: self.root_multiplexor.is_some(),
+            signals,
+        }
+    }
+}
+
+/// Decoders for every message of a database, looked up by CAN ID
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DbcDecoder {
+    messages: HashMap<CanId, MessageDecoder>,
+}
+
+impl DbcDecoder {
+    /// If several messages share an ID the first one is used, like [TreeDbc::message_by_id]
+    pub fn new(dbc: &TreeDbc) -> Self {
+        let mut messages = HashMap::new();
+        for m in dbc.messages() {
+            messages.entry(m.id).or_insert_with(|| MessageDecoder::new(m));
+        }
+        Self { messages }
+    }
+
+    pub fn message(&self, id: CanId) -> Option<&MessageDecoder> {
+        self.messages.get(&id)
+    }
+
+    /// Decodes a frame, `None` if the database has no message with its ID
+    pub fn decode(&self, id: CanId, raw: &[u8]) -> Option<DecodedMessage> {
+        self.message(id).map(|m| m.decode(raw))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{load_dbc_from_bytes, multiplexor_values, parse_signal, tree_dbc::Ecu};
+
+    const DBC: &str = r#"VERSION ""
+
+NS_ :
+
+BS_:
+
+BU_: ECU OTHER
+BO_ 256 First: 8 ECU
+ SG_ A : 0|8@1+ (1,0) [0|0] "" Vector__XXX
+ SG_ B : 8|8@1+ (1,0) [0|0] "" Vector__XXX
+ SG_ C : 16|8@1+ (1,0) [0|0] "" Vector__XXX
+"#;
+
+    #[test]
+    fn duplicate_ids_use_first_message() {
+        let mut dbc = load_dbc_from_bytes(DBC.as_bytes()).unwrap();
+        let mut second = dbc.message_by_id(CanId::new(256, false)).unwrap().clone();
+        second.name = "Second".into();
+        second.signals.truncate(1);
+        second.signals[0].start_bit = 32;
+        dbc.ecus.push(Ecu { name: "LATER".into(), messages: vec![second], ..Default::default() });
+        dbc.reindex();
+
+        let msg = dbc.message_by_id(CanId::new(256, false)).unwrap();
+        assert_eq!(msg.name, "First");
+        let decoded = DbcDecoder::new(&dbc).decode(msg.id, &[1, 2, 3, 0, 5, 0, 0, 0]).unwrap();
+        let values: Vec<(&str, String)> = decoded.signals.iter()
+            .map(|d| (msg.signals[d.index].name.as_str(), d.value.as_ref().unwrap().to_string()))
+            .collect();
+        assert_eq!(values, [("A", "1".to_string()), ("B", "2".to_string()), ("C", "3".to_string())]);
+    }
+
+    const MIXED_DBC: &str = r#"VERSION ""
+
+NS_ :
+
+BS_:
+
+BU_: ECU
+BO_ 300 Mixed: 12 ECU
+ SG_ Unsigned : 0|12@1+ (0.1,5) [0|0] "V" Vector__XXX
+ SG_ Motorola : 23|12@0+ (1,0) [0|0] "" Vector__XXX
+ SG_ Signed : 32|10@1- (2,-1) [0|0] "" Vector__XXX
+ SG_ SignedMotorola : 47|14@0- (0.5,0) [-100|100] "" Vector__XXX
+ SG_ Gear : 56|4@1+ (1,0) [0|0] "" Vector__XXX
+ SG_ Flag : 60|1@1+ (1,0) [0|0] "" Vector__XXX
+ SG_ Float : 64|32@1- (1,0) [0|0] "" Vector__XXX
+
+BO_ 301 Double: 8 ECU
+ SG_ Double : 7|64@0- (2,1) [0|0] "" Vector__XXX
+
+BO_ 302 Muxed: 8 ECU
+ SG_ Mux M : 0|2@1+ (1,0) [0|0] "" Vector__XXX
+ SG_ Sub m1M : 4|2@1+ (1,0) [0|0] "" Vector__XXX
+ SG_ A m0 : 8|16@1- (1,0) [0|0] "" Vector__XXX
+ SG_ B m1 : 8|8@1+ (1,0) [0|0] "" Vector__XXX
+ SG_ C m2 : 23|16@0+ (1,0) [0|0] "" Vector__XXX
+ SG_ F m3 : 32|32@1- (1,0) [0|0] "" Vector__XXX
+
+BO_ 303 BadFloat: 2 ECU
+ SG_ Short : 8|16@1- (1,0) [0|0] "" Vector__XXX
+
+VAL_ 300 Gear 0 "Park" 1 "Reverse" 2 "Neutral" 3 "Drive" ;
+SIG_VALTYPE_ 300 Float : 1;
+SIG_VALTYPE_ 301 Double : 2;
+SIG_VALTYPE_ 302 F : 1;
+SIG_VALTYPE_ 303 Short : 1;
+SG_MUL_VAL_ 302 Sub Mux 1-1;
+SG_MUL_VAL_ 302 C Sub 2-2;
+SG_MUL_VAL_ 302 F Sub 3-3;
+"#;
+
+    #[test]
+    fn decoder_matches_parse_signal() {
+        let dbc = load_dbc_from_bytes(MIXED_DBC.as_bytes()).unwrap();
+        // Small LCG, so the payloads are the same on every run
+        let mut seed = 0x2545F4914F6CDD1Du64;
+        let mut next_byte = || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 56) as u8
+        };
+        let mut decoded_count: HashMap<&str, usize> = HashMap::new();
+        for msg in dbc.messages() {
+            let decoder = MessageDecoder::new(msg);
+            for i in 0..4000 {
+                // Every 8th payload is short, so some signals are outside of it
+                let len = if i % 8 == 0 { i / 8 % (msg.length_bytes as usize + 1) } else { msg.length_bytes as usize };
+                let raw: Vec<u8> = (0..len).map(|_| next_byte()).collect();
+                let decoded = decoder.decode(&raw);
+
+                let expected: Vec<(usize, String)> = msg.active_signals(&multiplexor_values(msg, &raw)).into_iter()
+                    .map(|s| (msg.signals.iter().position(|x| x.name == s.name).unwrap(), format!("{:?}", parse_signal(s, &raw))))
+                    .collect();
+                let actual: Vec<(usize, String)> = decoded.signals.iter().map(|d| (d.index, format!("{:?}", d.value))).collect();
+                assert_eq!(actual, expected, "{} {:02X?}", msg.name, raw);
+                for d in &decoded.signals {
+                    *decoded_count.entry(msg.signals[d.index].name.as_str()).or_default() += 1;
+                }
+            }
+        }
+        // Every signal was present in some of the payloads
+        for signal in dbc.messages().flat_map(|m| &m.signals) {
+            assert!(decoded_count.get(signal.name.as_str()).is_some_and(|c| *c > 20), "{} {:?}", signal.name, decoded_count);
+        }
+    }
+}
@@ -0,0 +1,670 @@
+//! Generates typed Rust message structs from a DBC.
+//!
+//! Every message becomes a struct with one field per signal, `decode(&[u8])`
+//! and `encode()` methods and `ID` / `EXTENDED` / `LENGTH` constants.
+//! Value descriptions become enums (Shared `VAL_TABLE_`s become one enum used
+//! by all of their signals), and signals without scaling keep their integer
+//! type. Multiplexed signals are `Option`s, which are `None` when the frame's
+//! multiplexor values do not select them.
+//!
+//! The generated code has no dependencies and uses the same bit numbering as
+//! [crate::parse_signal]. It can be written out with the `codegen` subcommand
+//! of canviewer, or from a build script:
+//!
+//! ```ignore
+//! let dbc = backend::load_database_from_file("vehicle.dbc", None)?;
+//! let code = backend::generate_rust(&dbc)?;
+//! std::fs::write(format!("{}/vehicle.rs", std::env::var("OUT_DIR")?), code)?;
+//! // and in the crate: include!(concat!(env!("OUT_DIR"), "/vehicle.rs"));
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use crate::signal_parser::signal_bit_positions;
+use crate::tree_dbc::{ByteOrder, Message, Signal, SignalType, TreeDbc, ValueTable, ValueType};
+use crate::{CanResult, CanViewError};
+
+/// Helpers shared by all generated messages
+const PRELUDE: &str = r#"/// Error decoding or encoding a generated CAN message
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CanError {
+    /// The payload is shorter than the last byte used by the message's signals
+    PayloadTooShort { expected: usize, actual: usize },
+    /// A physical value is outside of the signal's [min|max] range
+    OutOfRange { signal: &'static str, value: f64, min: f64, max: f64 },
+    /// A raw value does not fit in the bits of the signal
+    Overflow { signal: &'static str, raw: i64 },
+    /// No message is defined for the CAN ID
+    UnknownId { id: u32, extended: bool },
+}
+
+impl std::fmt::Display for CanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CanError::PayloadTooShort { expected, actual } => write!(f, "Payload is {} bytes, expected at least {}", actual, expected),
+            CanError::OutOfRange { signal, value, min, max } => write!(f, "{}: value {} is outside of range [{}|{}]", signal, value, min, max),
+            CanError::Overflow { signal, raw } => write!(f, "{}: raw value {} does not fit in the signal", signal, raw),
+            CanError::UnknownId { id, extended: true } => write!(f, "No message with extended ID 0x{:08X}", id),
+            CanError::UnknownId { id, extended: false } => write!(f, "No message with ID 0x{:03X}", id),
+        }
+    }
+}
+
+impl std::error::Error for CanError {}
+
+/// Reads the raw bits of a signal. Intel (little endian) signals start at their
+/// LSB, Motorola (big endian) signals start at their MSB and continue in
+/// 'sawtooth' order (7..0 of byte n, then 15..8 of byte n+1, etc)
+#[allow(dead_code)]
+fn read_bits(data: &[u8], start_bit: u32, length: u32, big_endian: bool) -> u64 {
+    let mut res = 0u64;
+    let mut pos = start_bit;
+    for i in 0..length {
+        let p = if big_endian { pos } else { start_bit + length - 1 - i };
+        res = (res << 1) | ((data[p as usize / 8] >> (p % 8)) & 0x01) as u64;
+        if big_endian {
+            pos = if pos & 0x07 == 0 { pos + 15 } else { pos - 1 };
+        }
+    }
+    res
+}
+
+/// Writes the lowest `length` bits of `value` at the position of a signal,
+/// with the same bit numbering as [read_bits]
+#[allow(dead_code)]
+fn write_bits(data: &mut [u8], start_bit: u32, length: u32, big_endian: bool, value: u64) {
+    let mut pos = start_bit;
+    for i in 0..length {
+        let p = if big_endian { pos } else { start_bit + length - 1 - i };
+        let bit = ((value >> (length - 1 - i)) & 0x01) as u8;
+        let byte = &mut data[p as usize / 8];
+        *byte = (*byte & !(1 << (p % 8))) | (bit << (p % 8));
+        if big_endian {
+            pos = if pos & 0x07 == 0 { pos + 15 } else { pos - 1 };
+        }
+    }
+}
+
+/// Sign extends the lowest `length` bits of `value`
+#[allow(dead_code)]
+fn sign_extend(value: u64, length: u32) -> i64 {
+    if length >= 64 {
+        return value as i64
+    }
+    let shift = 64 - length;
+    ((value << shift) as i64) >> shift
+}
+
+/// Converts a raw integer into the bit pattern of a signal, checking it fits
+#[allow(dead_code)]
+fn to_bits(raw: i64, length: u32, signed: bool, signal: &'static str) -> Result<u64, CanError> {
+    let fits = match (signed, length >= 64) {
+        (_, true) => signed || raw >= 0,
+        (true, false) => raw >= -(1i64 << (length - 1)) && raw < (1i64 << (length - 1)),
+        (false, false) => raw >= 0 && (raw as u64) < (1u64 << length),
+    };
+    if !fits {
+        return Err(CanError::Overflow { signal, raw })
+    }
+    if length >= 64 {
+        Ok(raw as u64)
+    } else {
+        Ok((raw as u64) & ((1u64 << length) - 1))
+    }
+}
+"#;
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+    "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where", "while",
+    "abstract", "become", "box", "do", "final", "macro", "override", "priv", "try", "typeof", "unsized",
+    "virtual", "yield",
+];
+
+/// Splits a DBC name into words, on underscores and other separators
+fn words(name: &str) -> Vec<&str> {
+    name.split(|c: char| !c.is_ascii_alphanumeric()).filter(|w| !w.is_empty()).collect()
+}
+
+/// Converts a DBC name into an UpperCamelCase type or variant name
+fn camel_case(name: &str, prefix: &str) -> String {
+    let mut res = String::new();
+    for word in words(name) {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            res.push(first.to_ascii_uppercase());
+            // ALL_CAPS words become Capitalized, mixed case words are kept
+            if word.chars().any(|c| c.is_ascii_lowercase()) {
+                res.extend(chars);
+            } else {
+                res.extend(chars.map(|c| c.to_ascii_lowercase()));
+            }
+        }
+    }
+    if res.is_empty() || res.starts_with(|c: char| c.is_ascii_digit()) || res == "Self" {
+        res.insert_str(0, prefix);
+    }
+    res
+}
+
+/// Converts a DBC name into a snake_case field name
+fn snake_case(name: &str) -> String {
+    let mut res = String::new();
+    for word in words(name) {
+        if !res.is_empty() {
+            res.push('_');
+        }
+        let chars: Vec<char> = word.chars().collect();
+        for (i, c) in chars.iter().enumerate() {
+            // Split camelCase words, keeping acronyms together (ABSCtrl -> abs_ctrl)
+            let boundary = i > 0 && c.is_ascii_uppercase() && (
+                !chars[i - 1].is_ascii_uppercase() ||
+                chars.get(i + 1).is_some_and(|n| n.is_ascii_lowercase())
+            );
+            if boundary {
+                res.push('_');
+            }
+            res.push(c.to_ascii_lowercase());
+        }
+    }
+    if res.is_empty() || res.starts_with(|c: char| c.is_ascii_digit()) {
+        res.insert_str(0, "s_");
+    }
+    if KEYWORDS.contains(&res.as_str()) {
+        res.push('_');
+    }
+    res
+}
+
+/// Makes `name` unique within `used` by appending a number
+fn unique(name: String, used: &mut HashSet<String>) -> String {
+    let mut candidate = name.clone();
+    let mut n = 2;
+    while used.contains(&candidate) {
+        candidate = format!("{}{}", name, n);
+        n += 1;
+    }
+    used.insert(candidate.clone());
+    candidate
+}
+
+/// Rust literal of a DBC float, kept as it is written in the DBC
+fn float_lit(v: f64) -> String {
+    if v.is_nan() {
+        return "f64::NAN".into()
+    } else if v.is_infinite() {
+        return if v > 0.0 { "f64::INFINITY".into() } else { "f64::NEG_INFINITY".into() }
+    }
+    let s = format!("{}", v);
+    if s.contains('.') || s.contains('e') {
+        s
+    } else {
+        format!("{}.0", s)
+    }
+}
+
+fn string_lit(s: &str) -> String {
+    format!("{:?}", s)
+}
+
+fn write_doc(out: &mut String, indent: &str, text: &str) {
+    for line in text.lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            let _ = writeln!(out, "{}///", indent);
+        } else {
+            let _ = writeln!(out, "{}/// {}", indent, line);
+        }
+    }
+}
+
+/// Smallest integer type holding a signal without scaling
+fn integer_type(length: u64, signed: bool) -> (&'static str, u64) {
+    match (length, signed) {
+        (0..=8, false) => ("u8", 8),
+        (9..=16, false) => ("u16", 16),
+        (17..=32, false) => ("u32", 32),
+        (_, false) => ("u64", 64),
+        (0..=8, true) => ("i8", 8),
+        (9..=16, true) => ("i16", 16),
+        (17..=32, true) => ("i32", 32),
+        (_, true) => ("i64", 64),
+    }
+}
+
+/// How the value of a signal is represented in its generated field
+enum FieldKind {
+    Bool,
+    /// Unscaled integer, with its Rust type and that type's width in bits
+    Integer(&'static str, u64),
+    /// Scaled number, as f64
+    Scaled { multi: f64, offset: f64 },
+    /// Index of the generated enum type
+    Enum(usize),
+}
+
+struct Field<'a> {
+    signal: &'a Signal,
+    name: String,
+    kind: FieldKind,
+}
+
+/// Enum type generated for a set of value descriptions
+struct EnumType {
+    name: String,
+    doc: String,
+    /// Raw value, variant name and label
+    variants: Vec<(i64, String, String)>,
+}
+
+impl EnumType {
+    fn new(name: String, doc: String, table: &ValueTable) -> Self {
+        let mut used: HashSet<String> = ["Other".to_string()].into_iter().collect();
+        let mut seen = HashSet::new();
+        let variants = table.iter()
+            .filter(|(raw, _)| seen.insert(*raw))
+            .map(|(raw, label)| (*raw, unique(camel_case(label, "V"), &mut used), label.clone()))
+            .collect();
+        Self { name, doc, variants }
+    }
+
+    fn write(&self, out: &mut String) {
+        write_doc(out, "", &self.doc);
+        let _ = writeln!(out, "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]");
+        let _ = writeln!(out, "pub enum {} {{", self.name);
+        for (raw, variant, label) in &self.variants {
+            let _ = writeln!(out, "    /// {}: {}", raw, label.split_whitespace().collect::<Vec<_>>().join(" "));
+            let _ = writeln!(out, "    {},", variant);
+        }
+        let _ = writeln!(out, "    /// Raw value without a description");
+        let _ = writeln!(out, "    Other(i64),");
+        let _ = writeln!(out, "}}\n");
+        let _ = writeln!(out, "impl {} {{", self.name);
+        let _ = writeln!(out, "    pub fn from_raw(raw: i64) -> Self {{");
+        let _ = writeln!(out, "        match raw {{");
+        for (raw, variant, _) in &self.variants {
+            let _ = writeln!(out, "            {} => Self::{},", raw, variant);
+        }
+        let _ = writeln!(out, "            other => Self::Other(other),");
+        let _ = writeln!(out, "        }}");
+        let _ = writeln!(out, "    }}\n");
+        let _ = writeln!(out, "    pub fn raw(self) -> i64 {{");
+        let _ = writeln!(out, "        match self {{");
+        for (raw, variant, _) in &self.variants {
+            let _ = writeln!(out, "            Self::{} => {},", variant, raw);
+        }
+        let _ = writeln!(out, "            Self::Other(raw) => raw,");
+        let _ = writeln!(out, "        }}");
+        let _ = writeln!(out, "    }}");
+        let _ = writeln!(out, "}}\n");
+        let _ = writeln!(out, "impl Default for {} {{", self.name);
+        let _ = writeln!(out, "    fn default() -> Self {{");
+        let _ = writeln!(out, "        Self::from_raw(0)");
+        let _ = writeln!(out, "    }}");
+        let _ = writeln!(out, "}}\n");
+    }
+}
+
+fn invalid<T>(msg: &Message, signal: &Signal, reason: &str) -> CanResult<T> {
+    Err(CanViewError::ValidationError(format!("{}.{} {}", msg.name, signal.name, reason)))
+}
+
+/// Checks the layout of a signal can be generated, returning the number of
+/// payload bytes it needs
+fn check_signal(msg: &Message, signal: &Signal) -> CanResult<u64> {
+    if signal.length_bits == 0 || signal.length_bits > 64 {
+        return invalid(msg, signal, &format!("has an invalid length of {} bits", signal.length_bits))
+    }
+    match (signal.value_type, signal.length_bits) {
+        (ValueType::Float32, 32) | (ValueType::Float64, 64) | (ValueType::Integer, _) => {},
+        _ => return invalid(msg, signal, "is an IEEE float with an invalid length"),
+    }
+    let last_byte = signal_bit_positions(signal.start_bit, signal.length_bits, signal.order)
+        .iter()
+        .map(|p| p / 8)
+        .max()
+        .unwrap_or(0);
+    if last_byte >= msg.length_bytes {
+        return invalid(msg, signal, &format!("does not fit in the {} byte message", msg.length_bytes))
+    }
+    if let Some(c) = signal.mux_condition() {
+        if !msg.signals.iter().any(|s| s.name == c.multiplexor && s.is_multiplexor()) {
+            return invalid(msg, signal, &format!("is multiplexed by {}, which is not a multiplexor", c.multiplexor))
+        }
+    }
+    Ok(last_byte + 1)
+}
+
+/// Generates Rust source code with a typed struct for every message of the DBC.
+///
+/// Fails if a signal cannot be decoded, E.g. because it does not fit in its message.
+pub fn generate_rust(dbc: &TreeDbc) -> CanResult<String> {
+    let mut type_names: HashSet<String> = ["CanError", "Frame"].into_iter().map(String::from).collect();
+    let mut enums: Vec<EnumType> = Vec::new();
+    let mut table_enums: HashMap<&str, usize> = HashMap::new();
+    let mut messages = Vec::new();
+
+    // Vector's editor keeps signals which are not part of any message in this pseudo message
+    for msg in dbc.messages().filter(|m| m.name != "VECTOR__INDEPENDENT_SIG_MSG") {
+        let struct_name = unique(camel_case(&msg.name, "M"), &mut type_names);
+        let mut field_names = HashSet::new();
+        let mut fields = Vec::new();
+        let mut min_length = 0;
+        for signal in &msg.signals {
+            min_length = min_length.max(check_signal(msg, signal)?);
+            let kind = match &signal.signal_type {
+                _ if signal.value_type != ValueType::Integer => {
+                    let (multi, offset) = signal.signal_type.scaling();
+                    FieldKind::Scaled { multi, offset }
+                },
+                SignalType::Bool => FieldKind::Bool,
+                SignalType::Linear { multi, offset } if *multi != 1.0 || *offset != 0.0 => {
+                    FieldKind::Scaled { multi: *multi, offset: *offset }
+                },
+                SignalType::Enum { entries: table, .. } if !table.is_empty() => {
+                    let shared = signal.value_table.as_deref().filter(|t| dbc.value_tables.get(*t) == Some(table));
+                    let idx = match shared.and_then(|t| table_enums.get(t)) {
+                        Some(idx) => *idx,
+                        None => {
+                            let (name, doc) = match shared {
+                                Some(t) => (camel_case(t, "E"), format!("Value table {}", t)),
+                                None => (
+                                    format!("{}{}", struct_name, camel_case(&signal.name, "S")),
+                                    format!("Values of {}.{}", msg.name, signal.name),
+                                ),
+                            };
+                            enums.push(EnumType::new(unique(name, &mut type_names), doc, table));
+                            if let Some(t) = shared {
+                                table_enums.insert(t, enums.len() - 1);
+                            }
+                            enums.len() - 1
+                        }
+                    };
+                    FieldKind::Enum(idx)
+                },
+                // Unscaled numbers, and enums without any values
+                _ => {
+                    let (ty, width) = integer_type(signal.length_bits, signal.signed);
+                    FieldKind::Integer(ty, width)
+                },
+            };
+            fields.push(Field { signal, name: unique(snake_case(&signal.name), &mut field_names), kind });
+        }
+        messages.push((msg, struct_name, fields, min_length));
+    }
+
+    let mut out = String::new();
+    out.push_str("// Generated by canviewer from a CAN database. Do not edit.\n\n");
+    out.push_str(PRELUDE);
+    out.push('\n');
+    for e in &enums {
+        e.write(&mut out);
+    }
+    for (msg, struct_name, fields, min_length) in &messages {
+        write_message(&mut out, msg, struct_name, fields, *min_length, &enums);
+    }
+    write_frame_enum(&mut out, &messages);
+    Ok(out)
+}
+
+fn field_type(field: &Field, enums: &[EnumType]) -> String {
+    let ty = match &field.kind {
+        FieldKind::Bool => "bool".to_string(),
+        FieldKind::Integer(ty, _) => ty.to_string(),
+        FieldKind::Scaled { .. } => "f64".to_string(),
+        FieldKind::Enum(idx) => enums[*idx].name.clone(),
+    };
+    match field.signal.mux_condition() {
+        Some(_) => format!("Option<{}>", ty),
+        None => ty,
+    }
+}
+
+/// Name of the local holding the raw value of a multiplexor during decoding
+fn mux_var(fields: &[Field], multiplexor: &str) -> String {
+    let field = fields.iter().find(|f| f.signal.name == multiplexor).map_or("unknown", |f| f.name.as_str());
+    format!("mux_{}", field)
+}
+
+fn read_expr(signal: &Signal) -> String {
+    format!(
+        "read_bits(data, {}, {}, {})",
+        signal.start_bit,
+        signal.length_bits,
+        signal.order == ByteOrder::BigEndian
+    )
+}
+
+/// Expression reading the field's value from `data`
+fn decode_expr(field: &Field, enums: &[EnumType]) -> String {
+    let signal = field.signal;
+    let raw = read_expr(signal);
+    let signed_raw = if signal.signed {
+        format!("sign_extend({}, {})", raw, signal.length_bits)
+    } else {
+        format!("{} as i64", raw)
+    };
+    match &field.kind {
+        FieldKind::Bool => format!("{} != 0", raw),
+        FieldKind::Integer("i64", _) => format!("sign_extend({}, {})", raw, signal.length_bits),
+        FieldKind::Integer(ty, _) if signal.signed => format!("sign_extend({}, {}) as {}", raw, signal.length_bits, ty),
+        FieldKind::Integer("u64", _) => raw,
+        FieldKind::Integer(ty, _) => format!("{} as {}", raw, ty),
+        FieldKind::Enum(idx) => format!("{}::from_raw({})", enums[*idx].name, signed_raw),
+        FieldKind::Scaled { multi, offset } => {
+            let value = match signal.value_type {
+                ValueType::Float32 => format!("f32::from_bits({} as u32) as f64", raw),
+                ValueType::Float64 => format!("f64::from_bits({})", raw),
+                ValueType::Integer if signal.signed => format!("sign_extend({}, {}) as f64", raw, signal.length_bits),
+                ValueType::Integer => format!("{} as f64", raw),
+            };
+            scale_expr(&value, *multi, *offset)
+        },
+    }
+}
+
+fn scale_expr(value: &str, multi: f64, offset: f64) -> String {
+    match (multi == 1.0, offset == 0.0) {
+        (true, true) => value.to_string(),
+        (true, false) => format!("{} + {}", value, float_lit(offset)),
+        (false, true) => format!("{} * {}", value, float_lit(multi)),
+        (false, false) => format!("{} * {} + {}", value, float_lit(multi), float_lit(offset)),
+    }
+}
+
+fn unscale_expr(value: &str, multi: f64, offset: f64) -> String {
+    match (multi == 1.0, offset == 0.0) {
+        (true, true) => value.to_string(),
+        (true, false) => format!("({} - {})", value, float_lit(offset)),
+        (false, true) => format!("({} / {})", value, float_lit(multi)),
+        (false, false) => format!("(({} - {}) / {})", value, float_lit(offset), float_lit(multi)),
+    }
+}
+
+/// Statements checking `value` and writing it into `data`
+fn encode_stmts(out: &mut String, indent: &str, field: &Field) {
+    let signal = field.signal;
+    let name = string_lit(&signal.name);
+    let has_range = signal.min != signal.max;
+    let range_check = |out: &mut String, physical: &str| {
+        if has_range {
+            let _ = writeln!(out, "{}if !({}..={}).contains(&{}) {{", indent, float_lit(signal.min), float_lit(signal.max), physical);
+            let _ = writeln!(
+                out,
+                "{}    return Err(CanError::OutOfRange {{ signal: {}, {}, min: {}, max: {} }})",
+                indent, name, if physical == "value" { "value".to_string() } else { format!("value: {}", physical) }, float_lit(signal.min), float_lit(signal.max)
+            );
+            let _ = writeln!(out, "{}}}", indent);
+        }
+    };
+    let raw = match &field.kind {
+        FieldKind::Bool => "value as u64".to_string(),
+        FieldKind::Enum(_) => format!("to_bits(value.raw(), {}, {}, {})?", signal.length_bits, signal.signed, name),
+        FieldKind::Integer(ty, width) => {
+            range_check(out, "(value as f64)");
+            match (*width == signal.length_bits, *ty) {
+                (true, "u64") => "value".to_string(),
+                // Only the lowest bits are written, so no sign handling is needed
+                (true, _) => "value as u64".to_string(),
+                (_, "i64") => format!("to_bits(value, {}, {}, {})?", signal.length_bits, signal.signed, name),
+                _ => format!("to_bits(value as i64, {}, {}, {})?", signal.length_bits, signal.signed, name),
+            }
+        },
+        FieldKind::Scaled { multi, offset } => {
+            range_check(out, "value");
+            let raw = unscale_expr("value", *multi, *offset);
+            match signal.value_type {
+                ValueType::Float32 => format!("({} as f32).to_bits() as u64", raw),
+                ValueType::Float64 => format!("{}.to_bits()", raw),
+                ValueType::Integer => format!("to_bits({}.round() as i64, {}, {}, {})?", raw, signal.length_bits, signal.signed, name),
+            }
+        },
+    };
+    let _ = writeln!(
+        out,
+        "{}write_bits(&mut data, {}, {}, {}, {});",
+        indent,
+        signal.start_bit,
+        signal.length_bits,
+        signal.order == ByteOrder::BigEndian,
+        raw
+    );
+}
+
+fn write_message(out: &mut String, msg: &Message, struct_name: &str, fields: &[Field], min_length: u64, enums: &[EnumType]) {
+    match &msg.comment {
+        Some(c) => write_doc(out, "", &format!("{} ({})\n\n{}", msg.name, msg.id, c)),
+        None => write_doc(out, "", &format!("{} ({})", msg.name, msg.id)),
+    }
+    let _ = writeln!(out, "#[derive(Debug, Clone, Copy, PartialEq, Default)]");
+    let _ = writeln!(out, "pub struct {} {{", struct_name);
+    for field in fields {
+        let signal = field.signal;
+        let mut doc = signal.name.clone();
+        if !signal.unit.is_empty() {
+            let _ = write!(doc, " [{}]", signal.unit);
+        }
+        if signal.min != signal.max {
+            let _ = write!(doc, ", range {} to {}", signal.min, signal.max);
+        }
+        if let Some(c) = signal.mux_condition() {
+            let _ = write!(doc, ", present when {}", c);
+        }
+        if let Some(c) = &signal.comment {
+            let _ = write!(doc, "\n\n{}", c);
+        }
+        write_doc(out, "    ", &doc);
+        let _ = writeln!(out, "    pub {}: {},", field.name, field_type(field, enums));
+    }
+    let _ = writeln!(out, "}}\n");
+
+    let _ = writeln!(out, "impl {} {{", struct_name);
+    let _ = writeln!(out, "    pub const ID: u32 = 0x{:X};", msg.id.raw());
+    let _ = writeln!(out, "    pub const EXTENDED: bool = {};", msg.id.is_extended());
+    let _ = writeln!(out, "    pub const LENGTH: usize = {};\n", msg.length_bytes);
+
+    // Decoding
+    let _ = writeln!(out, "    /// Decodes the message from a frame payload");
+    let _ = writeln!(out, "    pub fn decode(data: &[u8]) -> Result<Self, CanError> {{");
+    if min_length > 0 {
+        if min_length == 1 {
+            let _ = writeln!(out, "        if data.is_empty() {{");
+        } else {
+            let _ = writeln!(out, "        if data.len() < {} {{", min_length);
+        }
+        let _ = writeln!(out, "            return Err(CanError::PayloadTooShort {{ expected: {}, actual: data.len() }})", min_length);
+        let _ = writeln!(out, "        }}");
+    } else {
+        let _ = writeln!(out, "        let _ = data;");
+    }
+    let mut muxes: Vec<&str> = Vec::new();
+    for c in fields.iter().flat_map(|f| msg.mux_chain(f.signal)) {
+        if !muxes.contains(&c.multiplexor.as_str()) {
+            muxes.push(&c.multiplexor);
+        }
+    }
+    for mux in fields.iter().filter(|f| muxes.contains(&f.signal.name.as_str())) {
+        let _ = writeln!(out, "        let {} = {};", mux_var(fields, &mux.signal.name), read_expr(mux.signal));
+    }
+    let _ = writeln!(out, "        Ok(Self {{");
+    for field in fields {
+        let chain = msg.mux_chain(field.signal);
+        let value = decode_expr(field, enums);
+        if chain.is_empty() {
+            let _ = writeln!(out, "            {}: {},", field.name, value);
+        } else {
+            let conditions: Vec<String> = chain.iter().map(|c| {
+                let var = mux_var(fields, &c.multiplexor);
+                let ranges: Vec<String> = c.ranges.iter().map(|(min, max)| {
+                    if min == max {
+                        format!("{} == {}", var, min)
+                    } else {
+                        format!("({}..={}).contains(&{})", min, max, var)
+                    }
+                }).collect();
+                if ranges.len() == 1 || chain.len() == 1 {
+                    ranges.join(" || ")
+                } else {
+                    format!("({})", ranges.join(" || "))
+                }
+            }).collect();
+            let _ = writeln!(out, "            {}: if {} {{ Some({}) }} else {{ None }},", field.name, conditions.join(" && "), value);
+        }
+    }
+    let _ = writeln!(out, "        }})");
+    let _ = writeln!(out, "    }}\n");
+
+    // Encoding
+    let _ = writeln!(out, "    /// Encodes the message into a frame payload. Multiplexed signals which are");
+    let _ = writeln!(out, "    /// `None` are left as 0, the multiplexor values are not checked.");
+    let _ = writeln!(out, "    pub fn encode(&self) -> Result<[u8; {}], CanError> {{", msg.length_bytes);
+    if fields.is_empty() {
+        let _ = writeln!(out, "        Ok([0u8; {}])", msg.length_bytes);
+    } else {
+        let _ = writeln!(out, "        let mut data = [0u8; {}];", msg.length_bytes);
+        for field in fields {
+            if field.signal.mux_condition().is_some() {
+                let _ = writeln!(out, "        if let Some(value) = self.{} {{", field.name);
+                encode_stmts(out, "            ", field);
+                let _ = writeln!(out, "        }}");
+            } else {
+                let _ = writeln!(out, "        {{");
+                let _ = writeln!(out, "            let value = self.{};", field.name);
+                encode_stmts(out, "            ", field);
+                let _ = writeln!(out, "        }}");
+            }
+        }
+        let _ = writeln!(out, "        Ok(data)");
+    }
+    let _ = writeln!(out, "    }}");
+    let _ = writeln!(out, "}}\n");
+}
+
+/// Writes the `Frame` enum, which decodes any message of the DBC by its ID
+fn write_frame_enum(out: &mut String, messages: &[(&Message, String, Vec<Field>, u64)]) {
+    if messages.is_empty() {
+        return
+    }
+    let _ = writeln!(out, "/// Any message of the database");
+    let _ = writeln!(out, "#[derive(Debug, Clone, Copy, PartialEq)]");
+    let _ = writeln!(out, "pub enum Frame {{");
+    for (_, name, _, _) in messages {
+        let _ = writeln!(out, "    {}({}),", name, name);
+    }
+    let _ = writeln!(out, "}}\n");
+    let _ = writeln!(out, "impl Frame {{");
+    let _ = writeln!(out, "    /// Decodes a frame by its CAN ID");
+    let _ = writeln!(out, "    pub fn decode(id: u32, extended: bool, data: &[u8]) -> Result<Self, CanError> {{");
+    let _ = writeln!(out, "        match (id, extended) {{");
+    let mut seen = HashSet::new();
+    // Like TreeDbc::message_by_id, the first message with an ID is used
+    for (msg, name, _, _) in messages.iter().filter(|(m, _, _, _)| seen.insert(m.id)) {
+        let _ = writeln!(out, "            (0x{:X}, {}) => {}::decode(data).map(Self::{}),", msg.id.raw(), msg.id.is_extended(), name, name);
+    }
+    let _ = writeln!(out, "            _ => Err(CanError::UnknownId {{ id, extended }}),");
+    let _ = writeln!(out, "        }}");
+    let _ = writeln!(out, "    }}");
+    let _ = writeln!(out, "}}");
+}
@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+
+use crate::{CanResult, CanViewError, ParsedSignal, ParsedValue};
+use crate::tree_dbc::{ByteOrder, Message, MuxValues, Signal, SignalType, ValueType};
+use crate::signal_parser::signal_bit_positions;
+
+/// Physical value of a signal to be encoded into a frame
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub enum SignalValue {
+    /// Physical (scaled) value, factor and offset are removed during encoding
+    Physical(f64),
+    /// Exact physical value, for integer signals up to 64 bits. With a whole
+    /// number factor and offset it is encoded without going through f64
+    Exact(i128),
+    Bool(bool),
+    /// Name of an enum entry, looked up in the signal's value descriptions
+    Label(String),
+}
+
+impl From<&ParsedSignal> for SignalValue {
+    fn from(p: &ParsedSignal) -> Self {
+        match p {
+            ParsedSignal { value: ParsedValue::Bool(b), .. } => Self::Bool(*b),
+            ParsedSignal { exact: Some(v), .. } => Self::Exact(*v),
+            ParsedSignal { physical, .. } => Self::Physical(*physical),
+        }
+    }
+}
+
+fn encode_error(signal: &Signal, reason: &str) -> CanViewError {
+    CanViewError::SignalEncodeError(format!("{}: {}", signal.name, reason))
+}
+
+/// Writes the lowest `length_bits` bits of `value` into the payload at the
+/// signal's position, following the same bit numbering as [crate::extract_raw_bits]
+pub fn insert_raw_bits(start_bit: u64, length_bits: u64, order: ByteOrder, value: u64, raw: &mut [u8]) -> CanResult<()> {
+    if length_bits == 0 || length_bits > 64 {
+        return Err(CanViewError::SignalEncodeError(format!("Invalid signal length {}", length_bits)))
+    }
+    let positions = signal_bit_positions(start_bit, length_bits, order);
+    if positions.iter().any(|p| (p / 8) as usize >= raw.len()) {
+        return Err(CanViewError::SignalEncodeError("Signal does not fit in payload".into()))
+    }
+    // Positions are ordered MSB first
+    for (idx, p) in positions.iter().enumerate() {
+        let bit = (value >> (length_bits - 1 - idx as u64)) & 0x01;
+        let byte = &mut raw[(p / 8) as usize];
+        *byte = (*byte & !(1 << (p % 8))) | ((bit as u8) << (p % 8));
+    }
+    Ok(())
+}
+
+/// Converts a raw integer into the bit pattern of a signal, checking it fits
+fn raw_integer_bits(signal: &Signal, raw: i128) -> CanResult<u64> {
+    let bits = signal.length_bits;
+    if bits == 0 || bits > 64 {
+        return Err(encode_error(signal, &format!("invalid signal length {}", bits)))
+    }
+    let fits = if signal.signed {
+        let lim = 1i128 << (bits - 1);
+        raw >= -lim && raw < lim
+    } else {
+        raw >= 0 && raw < (1i128 << bits)
+    };
+    if !fits {
+        return Err(encode_error(signal, &format!("raw value {} does not fit in {} bits", raw, bits)))
+    }
+    Ok((raw as u64) & (u64::MAX >> (64 - bits)))
+}
+
+/// Removes factor and offset from an exact physical value, if they are whole
+/// numbers. Returns `None` if the value has to be scaled as a float instead
+fn unscale_exact(signal: &Signal, value: i128, multi: f64, offset: f64) -> CanResult<Option<i128>> {
+    let whole = |v: f64| v.fract() == 0.0 && v.abs() < 2f64.powi(100);
+    if !whole(multi) || !whole(offset) || multi == 0.0 {
+        return Ok(None)
+    }
+    let (multi, offset) = (multi as i128, offset as i128);
+    match value.checked_sub(offset) {
+        Some(v) if v % multi == 0 => Ok(Some(v / multi)),
+        Some(_) => Err(encode_error(signal, &format!("{} is not a multiple of the factor {} from the offset {}", value, multi, offset))),
+        None => Err(encode_error(signal, &format!("{} is out of range", value))),
+    }
+}
+
+/// Converts a physical value into the raw bit pattern of a signal, applying
+/// inverse scaling, enum name lookup and the min/max range check
+pub fn signal_raw_value(signal: &Signal, value: &SignalValue) -> CanResult<u64> {
+    let physical = match value {
+        SignalValue::Physical(v) => Some(*v),
+        SignalValue::Exact(v) => Some(*v as f64),
+        _ => None,
+    };
+    if let Some(v) = physical {
+        if !v.is_finite() {
+            return Err(encode_error(signal, &format!("{} is not a finite value", v)))
+        }
+        // DBC uses [0|0] to mark a signal without a range limit
+        if signal.min != signal.max && (v < signal.min || v > signal.max) {
+            return Err(CanViewError::ValueOutOfRange {
+                signal: signal.name.clone(),
+                value: v,
+                min: signal.min,
+                max: signal.max
+            })
+        }
+    }
+
+    match (&signal.signal_type, value) {
+        (_, SignalValue::Bool(b)) => raw_integer_bits(signal, *b as i128),
+        (SignalType::Enum { entries, .. }, SignalValue::Label(l)) => {
+            match entries.iter().find(|(_, name)| name == l) {
+                Some((raw, _)) => raw_integer_bits(signal, *raw as i128),
+                None => Err(encode_error(signal, &format!("'{}' is not a valid enum entry", l)))
+            }
+        },
+        (_, SignalValue::Label(l)) => Err(encode_error(signal, &format!("cannot encode label '{}' into a non enum signal", l))),
+        (SignalType::Linear { multi, offset } | SignalType::Enum { multi, offset, .. }, SignalValue::Exact(v)) if signal.value_type == ValueType::Integer => {
+            match unscale_exact(signal, *v, *multi, *offset)? {
+                Some(raw) => raw_integer_bits(signal, raw),
+                None => raw_integer_bits(signal, ((*v as f64 - offset) / multi).round() as i128),
+            }
+        },
+        (SignalType::Linear { multi, offset } | SignalType::Enum { multi, offset, .. }, SignalValue::Physical(_) | SignalValue::Exact(_)) => {
+            let scaled = (physical.unwrap_or_default() - offset) / multi;
+            match (signal.value_type, signal.length_bits) {
+                (ValueType::Integer, _) => raw_integer_bits(signal, scaled.round() as i128),
+                (ValueType::Float32, 32) => Ok((scaled as f32).to_bits() as u64),
+                (ValueType::Float64, 64) => Ok(scaled.to_bits()),
+                _ => Err(encode_error(signal, "invalid length for IEEE value")),
+            }
+        },
+        (_, SignalValue::Exact(v)) => raw_integer_bits(signal, *v),
+        (_, SignalValue::Physical(v)) => raw_integer_bits(signal, v.round() as i128),
+    }
+}
+
+/// Encodes the physical value of a signal into an existing frame payload
+pub fn encode_signal(signal: &Signal, value: &SignalValue, raw: &mut [u8]) -> CanResult<()> {
+    let bits = signal_raw_value(signal, value)?;
+    insert_raw_bits(signal.start_bit, signal.length_bits, signal.order, bits, raw)
+        .map_err(|e| match e {
+            CanViewError::SignalEncodeError(reason) => encode_error(signal, &reason),
+            other => other
+        })
+}
+
+/// Builds the payload of a message from a set of physical signal values,
+/// keyed by signal name. Signals without a value are left as 0.
+///
+/// Multiplexors which are not given a value are set to the first value that
+/// makes the given multiplexed signals present. Signals which cannot be
+/// present together with the other values are rejected.
+pub fn encode_message(msg: &Message, values: &HashMap<String, SignalValue>) -> CanResult<Vec<u8>> {
+    for name in values.keys() {
+        if !msg.signals.iter().any(|s| &s.name == name) {
+            return Err(CanViewError::SignalEncodeError(format!("Message {} has no signal {}", msg.name, name)))
+        }
+    }
+    let given = || msg.signals.iter().filter_map(|s| values.get(&s.name).map(|v| (s, v)));
+
+    let mut mux_values = MuxValues::new();
+    for (signal, value) in given().filter(|(s, _)| s.is_multiplexor()) {
+        mux_values.insert(signal.name.clone(), signal_raw_value(signal, value)?);
+    }
+    // Multiplexor values implied by the multiplexed signals
+    let mut implied = MuxValues::new();
+    for (signal, _) in given() {
+        for cond in msg.mux_chain(signal) {
+            match mux_values.get(&cond.multiplexor) {
+                Some(v) if cond.matches(*v) => {},
+                Some(v) => return Err(encode_error(signal, &format!("not present when {} is {}", cond.multiplexor, v))),
+                None => {
+                    let v = cond.ranges.first().map_or(0, |r| r.0);
+                    mux_values.insert(cond.multiplexor.clone(), v);
+                    implied.insert(cond.multiplexor.clone(), v);
+                }
+            }
+        }
+    }
+    for (signal, _) in given() {
+        if !msg.is_signal_active(signal, &mux_values) {
+            return Err(encode_error(signal, "not present with the given multiplexor values"))
+        }
+    }
+
+    let mut payload = vec![0u8; msg.length_bytes as usize];
+    for signal in &msg.signals {
+        if let Some(value) = values.get(&signal.name) {
+            encode_signal(signal, value, &mut payload)?;
+        } else if let Some(raw) = implied.get(&signal.name) {
+            insert_raw_bits(signal.start_bit, signal.length_bits, signal.order, *raw, &mut payload)?;
+        }
+    }
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{load_dbc_from_bytes, multiplexor_values, parse_signal};
+
+    const DBC: &str = r#"VERSION ""
+
+NS_ :
+
+BS_:
+
+BU_: ECU
+BO_ 256 Plain: 8 ECU
+ SG_ Speed : 0|16@1+ (0.01,0) [0|655.35] "km/h" Vector__XXX
+ SG_ Temp : 23|12@0- (0.5,-40) [-1000|1000] "degC" Vector__XXX
+ SG_ Gear : 32|4@1+ (1,0) [0|15] "" Vector__XXX
+
+BO_ 512 Muxed: 8 ECU
+ SG_ Mux M : 0|8@1+ (1,0) [0|0] "" Vector__XXX
+ SG_ A m1 : 8|16@1+ (1,0) [0|0] "" Vector__XXX
+ SG_ B m2 : 8|16@1+ (1,0) [0|0] "" Vector__XXX
+ SG_ C m2 : 24|8@1- (1,0) [0|0] "" Vector__XXX
+
+VAL_ 256 Gear 0 "Park" 1 "Reverse" 2 "Neutral" 3 "Drive" ;
+"#;
+
+    fn message(name: &str) -> Message {
+        load_dbc_from_bytes(DBC.as_bytes()).unwrap().message_by_name(name).unwrap().clone()
+    }
+
+    fn values(entries: &[(&str, SignalValue)]) -> HashMap<String, SignalValue> {
+        entries.iter().map(|(n, v)| (n.to_string(), v.clone())).collect()
+    }
+
+    #[test]
+    fn encode_parse_round_trip() {
+        let msg = message("Plain");
+        let input = values(&[
+            ("Speed", SignalValue::Physical(123.45)),
+            ("Temp", SignalValue::Physical(-12.5)),
+            ("Gear", SignalValue::Label("Drive".into())),
+        ]);
+        let payload = encode_message(&msg, &input).unwrap();
+        let parsed: Vec<String> = msg.signals.iter().map(|s| parse_signal(s, &payload).unwrap().to_string()).collect();
+        assert_eq!(parsed, ["123.45 km/h", "-12.5 degC", "3 (Drive)"]);
+        for signal in &msg.signals {
+            let p = parse_signal(signal, &payload).unwrap();
+            let mut again = vec![0u8; 8];
+            encode_signal(signal, &SignalValue::from(&p), &mut again).unwrap();
+            assert_eq!(parse_signal(signal, &again).unwrap(), p);
+        }
+    }
+
+    #[test]
+    fn encode_sets_implied_multiplexor() {
+        let msg = message("Muxed");
+        let payload = encode_message(&msg, &values(&[("B", SignalValue::Exact(0x1234)), ("C", SignalValue::Exact(-3))])).unwrap();
+        let mux = multiplexor_values(&msg, &payload);
+        assert_eq!(mux.get("Mux"), Some(&2));
+        let active: Vec<&str> = msg.active_signals(&mux).iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(active, ["Mux", "B", "C"]);
+        assert_eq!(payload, [0x02, 0x34, 0x12, 0xFD, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn encode_is_deterministic() {
+        let msg = message("Muxed");
+        let input = values(&[("Mux", SignalValue::Exact(1)), ("A", SignalValue::Exact(0xBEEF))]);
+        let first = encode_message(&msg, &input).unwrap();
+        for _ in 0..20 {
+            assert_eq!(encode_message(&msg, &input.clone()).unwrap(), first);
+        }
+        assert_eq!(first, [0x01, 0xEF, 0xBE, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn encode_rejects_signals_of_other_mux_groups() {
+        let msg = message("Muxed");
+        let conflicting = values(&[("A", SignalValue::Exact(1)), ("B", SignalValue::Exact(2))]);
+        assert!(encode_message(&msg, &conflicting).is_err());
+        let inactive = values(&[("Mux", SignalValue::Exact(1)), ("C", SignalValue::Exact(2))]);
+        assert!(encode_message(&msg, &inactive).is_err());
+        assert!(encode_message(&msg, &values(&[("Nope", SignalValue::Exact(0))])).is_err());
+    }
+
+    #[test]
+    fn exact_64_bit_scaling() {
+        let mut msg = message("Plain");
+        let signal = &mut msg.signals[0];
+        signal.length_bits = 64;
+        signal.signal_type = SignalType::Linear { multi: 1.0, offset: 1000.0 };
+        signal.min = 0.0;
+        signal.max = 0.0;
+        // Not representable as f64, so this only round trips if the scaling is exact
+        let value = u64::MAX as i128 - 3 + 1000;
+        let mut payload = vec![0u8; 8];
+        encode_signal(signal, &SignalValue::Exact(value), &mut payload).unwrap();
+        assert_eq!(payload, [0xFC, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+        let parsed = parse_signal(signal, &payload).unwrap();
+        assert_eq!(parsed.exact, Some(value));
+        // The raw value shown with "Show raw signal values"
+        assert_eq!(parsed.raw, u64::MAX as i128 - 3);
+
+        signal.signed = true;
+        signal.signal_type = SignalType::Linear { multi: 2.0, offset: 0.0 };
+        let value = i64::MIN as i128 * 2;
+        encode_signal(signal, &SignalValue::Exact(value), &mut payload).unwrap();
+        let parsed = parse_signal(signal, &payload).unwrap();
+        assert_eq!((parsed.raw, parsed.exact), (i64::MIN as i128, Some(value)));
+        assert!(encode_signal(signal, &SignalValue::Exact(value - 2), &mut payload).is_err());
+    }
+
+    #[test]
+    fn raw_value_of_scaled_signal() {
+        let msg = message("Plain");
+        let payload = encode_message(&msg, &values(&[("Temp", SignalValue::Physical(-12.5))])).unwrap();
+        assert_eq!(parse_signal(&msg.signals[1], &payload).unwrap().raw, 55);
+    }
+
+    #[test]
+    fn invalid_signal_length() {
+        let mut msg = message("Plain");
+        for length in [0, 65] {
+            let signal = &mut msg.signals[2];
+            signal.length_bits = length;
+            assert!(signal_raw_value(signal, &SignalValue::Exact(0)).is_err());
+            assert!(signal_raw_value(signal, &SignalValue::Bool(true)).is_err());
+            signal.signed = true;
+            assert!(signal_raw_value(signal, &SignalValue::Exact(0)).is_err());
+        }
+    }
+
+    #[test]
+    fn non_finite_values() {
+        // Temp has a range, A is unlimited ([0|0])
+        for signal in [&message("Plain").signals[1], &message("Muxed").signals[1]] {
+            for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+                match signal_raw_value(signal, &SignalValue::Physical(value)) {
+                    Err(CanViewError::SignalEncodeError(e)) => assert!(e.contains("not a finite value"), "{}", e),
+                    other => panic!("{} encoded {} as {:?}", signal.name, value, other),
+                }
+            }
+        }
+    }
+}
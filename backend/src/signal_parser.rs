@@ -1,66 +1,426 @@
-use bitreader::BitReader;
-
-use crate::tree_dbc::Signal;
+use crate::tree_dbc::{ByteOrder, Message, MuxValues, Signal, SignalType, ValueType};
 
 
 pub type SignalParseResult<T> = std::result::Result<T, SignalParseError>;
 
+/// Decoded value of a signal, together with its raw value and validity
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct ParsedSignal {
+    /// Raw integer read from the frame, sign extended for signed signals.
+    /// For IEEE float signals this is the bit pattern of the float
+    pub raw: i128,
+    /// Physical value, after applying factor and offset
+    pub physical: f64,
+    /// Exact physical value of integer signals whose factor and offset are
+    /// whole numbers. Unlike `physical` this does not lose precision above 2^53
+    pub exact: Option<i128>,
+    pub value: ParsedValue,
+    pub status: SignalStatus,
+}
+
+impl ParsedSignal {
+    pub fn is_valid(&self) -> bool {
+        self.status == SignalStatus::Valid
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
-pub enum ParsedSignal {
-    Enum(i64, String),
+pub enum ParsedValue {
+    /// Description of the enum value, `None` if the DBC does not describe it
+    Enum(Option<String>),
     Bool(bool),
-    Number(f32, Option<String>)
+    /// Number, with the unit of the signal
+    Number(Option<String>),
+}
+
+/// Validity of a decoded signal value
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum SignalStatus {
+    #[default]
+    Valid,
+    /// Physical value is below the signal's minimum
+    BelowMin,
+    /// Physical value is above the signal's maximum
+    AboveMax,
+    /// Enum value which has no value description
+    UndefinedEnum,
+    /// Value marking a sensor error or unavailable data. Either a raw value of
+    /// all ones outside of the signal's range or value descriptions, or a NaN float
+    ErrorValue,
 }
 
-impl ToString for ParsedSignal {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for SignalStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ParsedSignal::Enum(r,e) => format!("{} ({})", r, e),
-            ParsedSignal::Bool(b) => format!("{}", b),
-            ParsedSignal::Number(raw, unit) => {
+            SignalStatus::Valid => write!(f, "Valid"),
+            SignalStatus::BelowMin => write!(f, "Below minimum"),
+            SignalStatus::AboveMax => write!(f, "Above maximum"),
+            SignalStatus::UndefinedEnum => write!(f, "Undefined enum value"),
+            SignalStatus::ErrorValue => write!(f, "Error value"),
+        }
+    }
+}
+
+impl std::fmt::Display for ParsedSignal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.value {
+            ParsedValue::Enum(Some(e)) => write!(f, "{} ({})", self.raw, e),
+            ParsedValue::Enum(None) => write!(f, "{} (undefined)", self.raw),
+            ParsedValue::Bool(b) => write!(f, "{}", b),
+            ParsedValue::Number(unit) => {
+                match self.exact {
+                    Some(v) => write!(f, "{}", v)?,
+                    None => write!(f, "{}", self.physical)?,
+                }
                 if let Some(u) = &unit {
-                    format!("{} {}", raw, u)
-                } else {
-                    format!("{}", raw)
+                    write!(f, " {}", u)?;
                 }
+                Ok(())
             },
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SignalParseError {
-    RangeTooBig
+    RangeTooBig,
+    InvalidLength
 }
 
-pub fn parse_signal(signal: &Signal, raw: &[u8]) -> SignalParseResult<ParsedSignal> {
-    if signal.length_bits+signal.start_bit > (raw.len()*8) as u64 {
+/// Returns the frame bit position (byte * 8 + bit within byte) of every bit
+/// of the signal, starting with the most significant bit.
+///
+/// Bit numbering follows the DBC convention: For Intel (little endian) signals
+/// `start_bit` is the LSB and the signal grows towards higher bit numbers.
+/// For Motorola (big endian) signals `start_bit` is the MSB and the signal
+/// continues in 'sawtooth' order (7..0 of byte n, then 15..8 of byte n+1, etc).
+pub(crate) fn signal_bit_positions(start_bit: u64, length_bits: u64, order: ByteOrder) -> Vec<u64> {
+    let mut positions = Vec::with_capacity(length_bits as usize);
+    match order {
+        ByteOrder::LittleEndian => {
+            for i in (0..length_bits).rev() {
+                positions.push(start_bit + i);
+            }
+        },
+        ByteOrder::BigEndian => {
+            let mut pos = start_bit;
+            for _ in 0..length_bits {
+                positions.push(pos);
+                if pos & 0x07 == 0 {
+                    pos += 15;
+                } else {
+                    pos -= 1;
+                }
+            }
+        }
+    }
+    positions
+}
+
+/// Extracts the raw (unscaled) bits of a signal from a frame payload.
+///
+/// The returned value holds the signal in its lowest `length_bits` bits, with
+/// no sign extension applied.
+pub fn extract_raw_bits(start_bit: u64, length_bits: u64, order: ByteOrder, raw: &[u8]) -> SignalParseResult<u64> {
+    if length_bits == 0 || length_bits > 64 {
+        return Err(SignalParseError::InvalidLength)
+    }
+    let positions = signal_bit_positions(start_bit, length_bits, order);
+    if positions.iter().any(|p| (p / 8) as usize >= raw.len()) {
         return Err(SignalParseError::RangeTooBig)
     }
+    let mut res: u64 = 0;
+    for p in positions {
+        let bit = (raw[(p / 8) as usize] >> (p % 8)) & 0x01;
+        res = (res << 1) | bit as u64;
+    }
+    Ok(res)
+}
 
-    let mut r = BitReader::new(raw);
+/// Sign extends the lowest `length_bits` bits of `value` to a full i64
+pub(crate) fn sign_extend(value: u64, length_bits: u64) -> i64 {
+    if length_bits >= 64 {
+        return value as i64
+    }
+    let shift = 64 - length_bits;
+    ((value << shift) as i64) >> shift
+}
 
-    let _ignored = r.skip(signal.start_bit);
-    let data = match signal.signed {
-        true => r.read_i64(signal.length_bits as u8).unwrap(),
-        false => r.read_u64(signal.length_bits as u8).unwrap() as i64
-    };
+/// Factor and offset as integers, if both are whole numbers. Scaling with
+/// them is exact, unlike going through f64
+pub(crate) fn whole_scaling(multi: f64, offset: f64) -> Option<(i128, i128)> {
+    // 2^100 keeps the conversion to i128 exact and far from overflowing
+    let whole = |v: f64| v.fract() == 0.0 && v.abs() < 2f64.powi(100);
+    (whole(multi) && whole(offset)).then_some((multi as i128, offset as i128))
+}
 
-    // Now process signal
-    match &signal.signal_type {
-        crate::tree_dbc::SignalType::Bool => {
-            Ok(ParsedSignal::Bool(data != 0))
-        },
-        crate::tree_dbc::SignalType::Linear { multi, offset } => {
-            Ok(ParsedSignal::Number(((data as f32)*multi) + offset, Some(signal.unit.clone())))
-        },
-        crate::tree_dbc::SignalType::Enum(entries) => {
-            for x in entries {
-                if x.0 == data {
-                    return Ok(ParsedSignal::Enum(data as i64, x.1.clone()))
-                }
+/// Applies factor and offset to a raw integer. The exact result is also
+/// returned if factor and offset are whole numbers and it does not overflow
+pub(crate) fn scale_integer(raw: i128, multi: f64, offset: f64, whole: Option<(i128, i128)>) -> (f64, Option<i128>) {
+    let exact = whole.and_then(|(m, o)| raw.checked_mul(m).and_then(|v| v.checked_add(o)));
+    match exact {
+        Some(v) => (v as f64, Some(v)),
+        None => (raw as f64 * multi + offset, None),
+    }
+}
+
+/// Range and error value checks of a signal, see [SignalStatus]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ValueCheck {
+    min: f64,
+    max: f64,
+    /// Allowed overshoot of the range
+    tolerance: f64,
+    /// Raw value with all bits set, for integer signals of 2 bits or more
+    all_ones: Option<u64>,
+}
+
+impl ValueCheck {
+    pub(crate) fn new(signal: &Signal) -> Self {
+        let tolerance = match (&signal.signal_type, signal.value_type) {
+            // Allow half a raw step, so float rounding of the scaling is not reported
+            (SignalType::Linear { multi, .. } | SignalType::Enum { multi, .. }, ValueType::Integer) => multi.abs() / 2.0,
+            _ => 0.0,
+        };
+        let all_ones = (signal.length_bits >= 2 && signal.length_bits <= 64 && signal.value_type == ValueType::Integer)
+            .then(|| u64::MAX >> (64 - signal.length_bits));
+        Self { min: signal.min, max: signal.max, tolerance, all_ones }
+    }
+
+    /// Checks a decoded value against the signal's range and value descriptions
+    pub(crate) fn status(&self, bits: u64, physical: f64, value: &ParsedValue) -> SignalStatus {
+        if physical.is_nan() {
+            return SignalStatus::ErrorValue
+        }
+        // DBC uses [0|0] to mark a signal without a range limit
+        let has_range = self.min != self.max;
+        let below = has_range && physical < self.min - self.tolerance;
+        let above = has_range && physical > self.max + self.tolerance;
+        // All ones is commonly used for 'error' or 'not available', unless the DBC gives it a meaning
+        let undescribed = match value {
+            ParsedValue::Enum(label) => label.is_none(),
+            ParsedValue::Number(_) => below || above,
+            ParsedValue::Bool(_) => false,
+        };
+        if self.all_ones == Some(bits) && undescribed {
+            SignalStatus::ErrorValue
+        } else if below {
+            SignalStatus::BelowMin
+        } else if above {
+            SignalStatus::AboveMax
+        } else if matches!(value, ParsedValue::Enum(None)) {
+            SignalStatus::UndefinedEnum
+        } else {
+            SignalStatus::Valid
+        }
+    }
+}
+
+pub fn parse_signal(signal: &Signal, raw: &[u8]) -> SignalParseResult<ParsedSignal> {
+    // The length is checked before the payload is read, like in MessageDecoder
+    match (signal.value_type, signal.length_bits) {
+        (ValueType::Integer, _) | (ValueType::Float32, 32) | (ValueType::Float64, 64) => {},
+        _ => return Err(SignalParseError::InvalidLength)
+    }
+    let bits = extract_raw_bits(signal.start_bit, signal.length_bits, signal.order, raw)?;
+    let unit = Some(signal.unit.clone()).filter(|u| !u.is_empty());
+    let (raw, physical, exact, value) = match signal.value_type {
+        ValueType::Integer => {
+            let data = match signal.signed {
+                true => sign_extend(bits, signal.length_bits) as i128,
+                false => bits as i128
+            };
+            match &signal.signal_type {
+                SignalType::Bool => (data, data as f64, Some(data), ParsedValue::Bool(data != 0)),
+                SignalType::Linear { multi, offset } => {
+                    let (physical, exact) = scale_integer(data, *multi, *offset, whole_scaling(*multi, *offset));
+                    (data, physical, exact, ParsedValue::Number(unit))
+                },
+                SignalType::Enum { entries, multi, offset } => {
+                    let label = entries.iter().find(|x| x.0 as i128 == data).map(|x| x.1.clone());
+                    let (physical, exact) = scale_integer(data, *multi, *offset, whole_scaling(*multi, *offset));
+                    (data, physical, exact, ParsedValue::Enum(label))
+                },
             }
-            Ok(ParsedSignal::Enum(data as i64, "INVALID VALUE".into()))
         },
+        // IEEE float or double signal
+        ValueType::Float32 | ValueType::Float64 => {
+            let value = match signal.value_type {
+                ValueType::Float32 => f32::from_bits(bits as u32) as f64,
+                _ => f64::from_bits(bits),
+            };
+            let (multi, offset) = signal.signal_type.scaling();
+            let physical = (value * multi) + offset;
+            (bits as i128, physical, None, ParsedValue::Number(unit))
+        },
+    };
+    let status = ValueCheck::new(signal).status(bits, physical, &value);
+    Ok(ParsedSignal { raw, physical, exact, value, status })
+}
+
+/// Reads the current values of all multiplexor switches of a message.
+///
+/// Multiplexors which cannot be read from the payload are left out, so any
+/// signal depending on them is treated as not present.
+pub fn multiplexor_values(msg: &Message, raw: &[u8]) -> MuxValues {
+    let mut values = MuxValues::new();
+    for mux in msg.signals.iter().filter(|s| s.is_multiplexor()) {
+        if let Ok(v) = extract_raw_bits(mux.start_bit, mux.length_bits, mux.order, raw) {
+            values.insert(mux.name.clone(), v);
+        }
     }
-}
\ No newline at end of file
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_dbc_from_bytes;
+
+    fn signal(start_bit: u64, length_bits: u64, order: ByteOrder, signed: bool) -> Signal {
+        Signal {
+            name: "Test".into(),
+            start_bit,
+            length_bits,
+            order,
+            signed,
+            signal_type: SignalType::Linear { multi: 1.0, offset: 0.0 },
+            ..Default::default()
+        }
+    }
+
+    fn raw_value(signal: &Signal, payload: &[u8]) -> i128 {
+        parse_signal(signal, payload).unwrap().raw
+    }
+
+    #[test]
+    fn intel_crosses_byte_boundary() {
+        let s = signal(4, 12, ByteOrder::LittleEndian, false);
+        assert_eq!(raw_value(&s, &[0x30, 0x12]), 0x123);
+        let s = signal(12, 16, ByteOrder::LittleEndian, false);
+        assert_eq!(raw_value(&s, &[0x00, 0xD0, 0xBC, 0x0A]), 0xABCD);
+    }
+
+    #[test]
+    fn motorola_crosses_byte_boundary() {
+        let s = signal(7, 16, ByteOrder::BigEndian, false);
+        assert_eq!(raw_value(&s, &[0x12, 0x34]), 0x1234);
+        // MSB is bit 3 of byte 0, the signal continues with all of byte 1
+        let s = signal(3, 12, ByteOrder::BigEndian, false);
+        assert_eq!(raw_value(&s, &[0xA5, 0x3C]), 0x53C);
+    }
+
+    #[test]
+    fn single_bit_signals() {
+        let mut s = signal(9, 1, ByteOrder::LittleEndian, false);
+        s.signal_type = SignalType::Bool;
+        assert_eq!(parse_signal(&s, &[0x00, 0x02]).unwrap().value, ParsedValue::Bool(true));
+        assert_eq!(parse_signal(&s, &[0xFF, 0xFD]).unwrap().value, ParsedValue::Bool(false));
+        let s = signal(15, 1, ByteOrder::BigEndian, false);
+        assert_eq!(raw_value(&s, &[0x00, 0x80]), 1);
+    }
+
+    #[test]
+    fn full_64_bit_signals() {
+        let payload = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x88];
+        let s = signal(0, 64, ByteOrder::LittleEndian, false);
+        assert_eq!(raw_value(&s, &payload), u64::from_le_bytes(payload) as i128);
+        let s = signal(7, 64, ByteOrder::BigEndian, false);
+        assert_eq!(raw_value(&s, &payload), u64::from_be_bytes(payload) as i128);
+        let s = signal(0, 64, ByteOrder::LittleEndian, true);
+        assert_eq!(raw_value(&s, &payload), i64::from_le_bytes(payload) as i128);
+    }
+
+    #[test]
+    fn signed_signals() {
+        let s = signal(0, 8, ByteOrder::LittleEndian, true);
+        assert_eq!(raw_value(&s, &[0xFE]), -2);
+        assert_eq!(raw_value(&s, &[0x7F]), 127);
+        let s = signal(3, 12, ByteOrder::BigEndian, true);
+        assert_eq!(raw_value(&s, &[0x08, 0x00]), -2048);
+        let s = signal(4, 12, ByteOrder::LittleEndian, true);
+        assert_eq!(raw_value(&s, &[0xF0, 0xFF]), -1);
+    }
+
+    #[test]
+    fn signal_outside_payload() {
+        let s = signal(7, 16, ByteOrder::BigEndian, false);
+        assert!(matches!(parse_signal(&s, &[0x12]), Err(SignalParseError::RangeTooBig)));
+        let s = signal(0, 0, ByteOrder::LittleEndian, false);
+        assert!(matches!(parse_signal(&s, &[0x12]), Err(SignalParseError::InvalidLength)));
+    }
+
+    fn float_signal(length_bits: u64, order: ByteOrder, value_type: ValueType) -> Signal {
+        let start_bit = match order {
+            ByteOrder::LittleEndian => 0,
+            ByteOrder::BigEndian => 7,
+        };
+        Signal { value_type, signed: true, ..signal(start_bit, length_bits, order, true) }
+    }
+
+    #[test]
+    fn float_signals() {
+        let s = float_signal(32, ByteOrder::LittleEndian, ValueType::Float32);
+        assert_eq!(parse_signal(&s, &1.5f32.to_le_bytes()).unwrap().physical, 1.5);
+        let s = float_signal(32, ByteOrder::BigEndian, ValueType::Float32);
+        assert_eq!(parse_signal(&s, &(-273.15f32).to_be_bytes()).unwrap().physical, -273.15f32 as f64);
+        let mut s = float_signal(32, ByteOrder::LittleEndian, ValueType::Float32);
+        s.signal_type = SignalType::Linear { multi: 2.0, offset: 1.0 };
+        assert_eq!(parse_signal(&s, &0.25f32.to_le_bytes()).unwrap().physical, 1.5);
+        let nan = parse_signal(&float_signal(32, ByteOrder::LittleEndian, ValueType::Float32), &f32::NAN.to_le_bytes()).unwrap();
+        assert!(nan.physical.is_nan());
+        assert_eq!(nan.status, SignalStatus::ErrorValue);
+    }
+
+    #[test]
+    fn double_signals_are_bit_exact() {
+        for value in [0.1, -1e300, std::f64::consts::PI, f64::MIN_POSITIVE] {
+            let s = float_signal(64, ByteOrder::LittleEndian, ValueType::Float64);
+            let parsed = parse_signal(&s, &f64::to_le_bytes(value)).unwrap();
+            assert_eq!(parsed.physical.to_bits(), value.to_bits());
+            assert_eq!(parsed.raw, value.to_bits() as i128);
+            let s = float_signal(64, ByteOrder::BigEndian, ValueType::Float64);
+            assert_eq!(parse_signal(&s, &f64::to_be_bytes(value)).unwrap().physical.to_bits(), value.to_bits());
+        }
+    }
+
+    #[test]
+    fn float_signal_of_wrong_length() {
+        let s = float_signal(16, ByteOrder::LittleEndian, ValueType::Float32);
+        assert!(matches!(parse_signal(&s, &[0; 8]), Err(SignalParseError::InvalidLength)));
+        let s = float_signal(32, ByteOrder::LittleEndian, ValueType::Float64);
+        assert!(matches!(parse_signal(&s, &[0; 8]), Err(SignalParseError::InvalidLength)));
+        // The length is reported even if the signal does not fit into the payload
+        assert!(matches!(parse_signal(&s, &[0; 2]), Err(SignalParseError::InvalidLength)));
+    }
+
+    /// The motohawk example from cantools, with a frame encoded by cantools
+    const MOTOHAWK: &str = r#"VERSION ""
+
+NS_ :
+
+BS_:
+
+BU_: PCM1 FOO
+BO_ 496 ExampleMessage: 8 PCM1
+ SG_ Enable : 7|1@0- (1,0) [0|0] "-" Vector__XXX
+ SG_ AverageRadius : 6|6@0+ (0.1,0) [0|5] "m" Vector__XXX
+ SG_ Temperature : 0|12@0- (0.01,250) [229.52|270.47] "degK" PCM1,FOO
+
+VAL_ 496 Enable 0 "Disabled" 1 "Enabled" ;
+"#;
+
+    #[test]
+    fn reference_frame() {
+        let dbc = load_dbc_from_bytes(MOTOHAWK.as_bytes()).unwrap();
+        let msg = dbc.message_by_name("ExampleMessage").unwrap();
+        let payload = [0xC0, 0x06, 0xE0, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let decoded: Vec<String> = msg.signals.iter().map(|s| parse_signal(s, &payload).unwrap().to_string()).collect();
+        // Enable keeps its value descriptions, but is signed, so the set bit reads as
+        // -1 rather than the described 1. The lint reports this
+        assert_eq!(decoded, ["-1 (undefined)", "3.2 m", "250.55 degK"]);
+        assert_eq!(parse_signal(&msg.signals[0], &[0x40, 0, 0, 0, 0, 0, 0, 0]).unwrap().to_string(), "0 (Disabled)");
+        let lint: Vec<String> = crate::lint_dbc(&dbc).iter().map(|d| d.summary.clone()).collect();
+        assert!(lint.contains(&"Value description 1 (Enabled) is outside the raw range [-1|0]".to_string()), "{:?}", lint);
+    }
+}
@@ -0,0 +1,411 @@
+//! Loader for PEAK PCAN Symbol Editor (`.sym`) files.
+//!
+//! SYM files are line based:
+//!
+//! ```text
+//! FormatVersion=6.0 // Do not edit this line!
+//! {ENUMS}
+//! enum Gear(0="Park", 1="Drive")
+//! {SENDRECEIVE}
+//! [EngineData]
+//! ID=123h
+//! Type=Extended
+//! DLC=8
+//! CycleTime=100
+//! Mux=Page 0,8 1
+//! Var=Speed unsigned 8,16 /u:km/h /f:0.01 /e:Gear -m
+//! ```
+//!
+//! A message with multiplexed pages is written as one `[Name]` section per
+//! multiplexor value, each with its own `Mux=` line. Signals defined in the
+//! `{SIGNALS}` section are placed in messages with `Sig=<name> <start bit>`.
+
+use std::collections::HashMap;
+
+use crate::{
+    add_loader_attribute_definitions, ecu_entry, msb0_to_dbc_start_bit, physical_range,
+    tree_dbc::{AttributeValue, ByteOrder, CanId, Message, MuxCondition, Multiplexing, Signal, SignalType, TreeDbc, ValueType},
+    CanResult, CanViewError, NULL_SENDER,
+};
+
+/// Values and labels of an enum, sorted by value
+type EnumTable = Vec<(i64, String)>;
+
+fn err<T>(line: usize, msg: String) -> CanResult<T> {
+    Err(CanViewError::SymError(format!("line {}: {}", line, msg)))
+}
+
+/// SYM numbers are decimal, or hex when suffixed with `h`
+fn parse_int(s: &str) -> Option<i64> {
+    let s = s.trim();
+    match s.strip_suffix('h').or_else(|| s.strip_suffix('H')) {
+        Some(hex) => i64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// Splits off a trailing `// comment`, ignoring slashes inside quotes
+fn split_comment(line: &str) -> (&str, Option<&str>) {
+    let mut in_quotes = false;
+    let bytes = line.as_bytes();
+    for i in 0..bytes.len() {
+        match bytes[i] {
+            b'"' => in_quotes = !in_quotes,
+            b'/' if !in_quotes && bytes.get(i + 1) == Some(&b'/') => {
+                let comment = line[i + 2..].trim();
+                return (line[..i].trim_end(), Some(comment).filter(|c| !c.is_empty()))
+            }
+            _ => {}
+        }
+    }
+    (line, None)
+}
+
+/// Splits on whitespace, keeping quoted strings (E.g. `/ln:"long name"`) together
+fn tokenize(s: &str) -> Vec<String> {
+    let mut res = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    for c in s.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    res.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        res.push(current);
+    }
+    res
+}
+
+/// Parses an enum definition, E.g. `enum Gear(0="Park", 1="Drive")`
+fn parse_enum(def: &str, line: usize) -> CanResult<(String, EnumTable)> {
+    let def = def.trim().strip_prefix("enum").unwrap_or(def).trim();
+    let (name, rest) = match def.split_once('(') {
+        Some(x) => x,
+        None => return err(line, format!("Invalid enum definition {}", def)),
+    };
+    let body = rest.trim_end().strip_suffix(')').unwrap_or(rest);
+    let mut values = Vec::new();
+    let mut remaining = body;
+    while let Some((value, after)) = remaining.split_once('=') {
+        let value = value.trim().trim_start_matches(',').trim();
+        let after = after.trim_start();
+        let (label, next) = match after.strip_prefix('"').and_then(|a| a.split_once('"')) {
+            Some(x) => x,
+            None => return err(line, format!("Invalid label in enum {}", name.trim())),
+        };
+        match parse_int(value) {
+            Some(v) => values.push((v, label.to_string())),
+            None => return err(line, format!("Invalid value {} in enum {}", value, name.trim())),
+        }
+        remaining = next;
+    }
+    values.sort_by_key(|v| v.0);
+    Ok((name.trim().to_string(), values))
+}
+
+/// Signal definition, either from a `Var=` line or the `{SIGNALS}` section
+#[derive(Debug, Clone, Default)]
+struct SymSignal {
+    name: String,
+    kind: String,
+    length: u64,
+    order: ByteOrder,
+    unit: String,
+    factor: f64,
+    offset: f64,
+    min: Option<f64>,
+    max: Option<f64>,
+    enum_name: Option<String>,
+    comment: Option<String>,
+}
+
+impl SymSignal {
+    /// Builds a signal definition from its type, length and flags (E.g. `/u:km/h`, `-m`)
+    fn parse(name: &str, kind: &str, length: u64, flags: &[String], comment: Option<&str>) -> Self {
+        let mut sig = SymSignal {
+            name: name.to_string(),
+            kind: kind.to_string(),
+            length,
+            factor: 1.0,
+            comment: comment.map(String::from),
+            ..Default::default()
+        };
+        for flag in flags {
+            let float = |v: &str| v.parse::<f64>().ok();
+            if flag == "-m" {
+                sig.order = ByteOrder::BigEndian;
+            } else if let Some(v) = flag.strip_prefix("/u:") {
+                sig.unit = v.to_string();
+            } else if let Some(v) = flag.strip_prefix("/f:") {
+                sig.factor = float(v).unwrap_or(1.0);
+            } else if let Some(v) = flag.strip_prefix("/o:") {
+                sig.offset = float(v).unwrap_or(0.0);
+            } else if let Some(v) = flag.strip_prefix("/min:") {
+                sig.min = float(v);
+            } else if let Some(v) = flag.strip_prefix("/max:") {
+                sig.max = float(v);
+            } else if let Some(v) = flag.strip_prefix("/e:") {
+                sig.enum_name = Some(v.to_string());
+            }
+        }
+        sig
+    }
+
+    fn to_signal(&self, start: u64, enums: &HashMap<String, EnumTable>, line: usize) -> CanResult<Signal> {
+        let (value_type, signed) = match self.kind.as_str() {
+            "float" => (ValueType::Float32, true),
+            "double" => (ValueType::Float64, true),
+            "signed" => (ValueType::Integer, true),
+            _ => (ValueType::Integer, false),
+        };
+        let length = match (value_type, self.kind.as_str()) {
+            (ValueType::Float32, _) => 32,
+            (ValueType::Float64, _) => 64,
+            (_, "bit") => 1,
+            _ => self.length,
+        };
+        if length == 0 || length > 64 {
+            return err(line, format!("Signal {} has invalid length {}", self.name, length))
+        }
+        let (multi, offset) = (self.factor, self.offset);
+        // 1 bit signals with an enum are enums rather than bools, so the labels are kept
+        let signal_type = if let Some(enum_name) = &self.enum_name {
+            match enums.get(enum_name) {
+                Some(values) => SignalType::Enum { entries: values.clone(), multi, offset },
+                None => return err(line, format!("Signal {} uses undefined enum {}", self.name, enum_name)),
+            }
+        } else if length == 1 && multi == 1.0 && offset == 0.0 {
+            SignalType::Bool
+        } else {
+            SignalType::Linear { multi, offset }
+        };
+        let (min, max) = match value_type {
+            ValueType::Integer => {
+                let (min, max) = physical_range(&SignalType::Linear { multi, offset }, length, signed);
+                (self.min.unwrap_or(min), self.max.unwrap_or(max))
+            }
+            _ => (0.0, 0.0),
+        };
+        Ok(Signal {
+            name: self.name.clone(),
+            comment: self.comment.clone(),
+            signal_type,
+            order: self.order,
+            start_bit: match self.order {
+                ByteOrder::LittleEndian => start,
+                ByteOrder::BigEndian => msb0_to_dbc_start_bit(start),
+            },
+            length_bits: length,
+            unit: self.unit.clone(),
+            min,
+            max,
+            signed,
+            value_type,
+            ..Default::default()
+        })
+    }
+}
+
+/// Parses `<start>,<length>`
+fn parse_position(s: &str) -> Option<(u64, u64)> {
+    let (start, length) = s.split_once(',')?;
+    Some((start.trim().parse().ok()?, length.trim().parse().ok()?))
+}
+
+pub fn load_sym_from_bytes(bytes: &[u8]) -> CanResult<TreeDbc> {
+    let s = String::from_utf8_lossy(bytes);
+    let mut dbc = TreeDbc::default();
+    let mut enums: HashMap<String, EnumTable> = HashMap::new();
+    let mut defined_signals: HashMap<String, SymSignal> = HashMap::new();
+    let mut section = String::new();
+    // Message currently being built, and the multiplexor condition of its signals
+    let mut current: Option<Message> = None;
+    let mut mux: Option<MuxCondition> = None;
+    // ID and type of the current message, as `Type` may follow `ID`
+    let mut raw_id = (0u32, false);
+    let mut messages: Vec<Message> = Vec::new();
+    // Enum definitions may span multiple lines
+    let mut pending_enum: Option<(usize, String)> = None;
+
+    let lines: Vec<&str> = s.lines().collect();
+    for (idx, raw_line) in lines.iter().enumerate() {
+        let line_no = idx + 1;
+        let (line, comment) = split_comment(raw_line.trim());
+        let line = line.trim();
+
+        if let Some((start, mut def)) = pending_enum.take() {
+            def.push(' ');
+            def.push_str(line);
+            if line.ends_with(')') {
+                let (name, values) = parse_enum(&def, start)?;
+                enums.insert(name, values);
+            } else {
+                pending_enum = Some((start, def));
+            }
+            continue;
+        }
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('{') && line.ends_with('}') {
+            section = line.to_string();
+            continue;
+        }
+        if section == "{ENUMS}" {
+            if line.starts_with("enum") {
+                if line.ends_with(')') {
+                    let (name, values) = parse_enum(line, line_no)?;
+                    enums.insert(name, values);
+                } else {
+                    pending_enum = Some((line_no, line.to_string()));
+                }
+            }
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            if let Some(mut m) = current.take() {
+                m.id = CanId::new(raw_id.0, raw_id.1);
+                messages.push(m);
+            }
+            current = Some(Message { name: name.to_string(), length_bytes: 8, ..Default::default() });
+            mux = None;
+            raw_id = (0, false);
+            continue;
+        }
+        let (key, value) = match line.split_once('=') {
+            Some(kv) => kv,
+            None => continue,
+        };
+        let tokens = tokenize(value);
+
+        if section == "{SIGNALS}" && current.is_none() {
+            // Sig=<name> <type> <length> [flags]
+            if key == "Sig" && tokens.len() >= 3 {
+                let length = tokens[2].parse().unwrap_or(0);
+                let sig = SymSignal::parse(&tokens[0], &tokens[1], length, &tokens[3..], comment);
+                defined_signals.insert(sig.name.clone(), sig);
+            }
+            continue;
+        }
+        let msg = match current.as_mut() {
+            Some(m) => m,
+            None => continue,
+        };
+        match key {
+            "ID" => {
+                // ID ranges (123h-125h) only use the first ID
+                let id = value.split('-').next().and_then(parse_int);
+                match id {
+                    Some(id) if id >= 0 && id <= i64::from(u32::MAX) => raw_id.0 = id as u32,
+                    _ => return err(line_no, format!("Invalid ID {}", value)),
+                }
+            }
+            "Type" => raw_id.1 = value.trim().eq_ignore_ascii_case("extended"),
+            "DLC" | "Len" => msg.length_bytes = parse_int(value).unwrap_or(8).max(0) as u64,
+            "CycleTime" => if let Some(t) = parse_int(value) {
+                msg.attributes.insert("GenMsgCycleTime".into(), AttributeValue::Int(t));
+            },
+            "Mux" => {
+                // Mux=<name> <start>,<length> <value> [-m] [-t]
+                let (start, length) = match tokens.get(1).and_then(|t| parse_position(t)) {
+                    Some(p) => p,
+                    None => return err(line_no, format!("Invalid multiplexor {}", value)),
+                };
+                let mux_value = match tokens.get(2).and_then(|t| parse_int(t)) {
+                    Some(v) if v >= 0 => v as u64,
+                    _ => return err(line_no, format!("Invalid multiplexor value {}", value)),
+                };
+                let name = tokens[0].clone();
+                if !msg.signals.iter().any(|s| s.name == name) {
+                    let mut sig = SymSignal::parse(&name, "unsigned", length, &tokens[3..], comment)
+                        .to_signal(start, &enums, line_no)?;
+                    sig.multiplexing = Multiplexing::Multiplexor;
+                    msg.signals.push(sig);
+                }
+                mux = Some(MuxCondition { multiplexor: name, ranges: vec![(mux_value, mux_value)] });
+            }
+            "Var" => {
+                // Var=<name> <type> <start>,<length> [flags]
+                let (start, length) = match tokens.get(2).and_then(|t| parse_position(t)) {
+                    Some(p) => p,
+                    None => return err(line_no, format!("Invalid variable {}", value)),
+                };
+                let mut sig = SymSignal::parse(&tokens[0], &tokens[1], length, &tokens[3..], comment)
+                    .to_signal(start, &enums, line_no)?;
+                add_signal(msg, &mut sig, &mux);
+            }
+            "Sig" => {
+                // Sig=<name> <start> [-m]
+                let def = match tokens.first().and_then(|n| defined_signals.get(n)) {
+                    Some(d) => d,
+                    None => return err(line_no, format!("Undefined signal {}", value)),
+                };
+                let start = match tokens.get(1).and_then(|t| t.parse().ok()) {
+                    Some(s) => s,
+                    None => return err(line_no, format!("Invalid signal start {}", value)),
+                };
+                let mut def = def.clone();
+                if tokens.iter().any(|t| t == "-m") {
+                    def.order = ByteOrder::BigEndian;
+                }
+                let mut sig = def.to_signal(start, &enums, line_no)?;
+                add_signal(msg, &mut sig, &mux);
+            }
+            _ => {}
+        }
+    }
+    if let Some(mut m) = current.take() {
+        m.id = CanId::new(raw_id.0, raw_id.1);
+        messages.push(m);
+    }
+
+    // Pages of a multiplexed message are separate sections, merge them by ID
+    let ecu_idx = ecu_entry(&mut dbc, NULL_SENDER);
+    for msg in messages {
+        let target = &mut dbc.ecus[ecu_idx].messages;
+        match target.iter_mut().find(|m| m.id == msg.id) {
+            Some(existing) => {
+                for sig in msg.signals {
+                    merge_signal(existing, sig);
+                }
+            }
+            None => target.push(msg),
+        }
+    }
+    add_loader_attribute_definitions(&mut dbc);
+    dbc.reindex();
+    Ok(dbc)
+}
+
+fn add_signal(msg: &mut Message, sig: &mut Signal, mux: &Option<MuxCondition>) {
+    if let Some(cond) = mux {
+        sig.multiplexing = Multiplexing::Multiplexed(cond.clone());
+    }
+    merge_signal(msg, std::mem::take(sig));
+}
+
+/// Adds a signal to a message. A signal present on several multiplexor pages is
+/// only added once, with the multiplexor values combined.
+fn merge_signal(msg: &mut Message, sig: Signal) {
+    match msg.signals.iter_mut().find(|s| s.name == sig.name) {
+        Some(existing) => {
+            if let (Multiplexing::Multiplexed(a), Multiplexing::Multiplexed(b)) = (&mut existing.multiplexing, &sig.multiplexing) {
+                if a.multiplexor == b.multiplexor {
+                    for r in &b.ranges {
+                        if !a.ranges.contains(r) {
+                            a.ranges.push(*r);
+                        }
+                    }
+                }
+            }
+        }
+        None => msg.signals.push(sig),
+    }
+}
@@ -1,3 +1,7 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::j1939::J1939Id;
+
 
 
 #[derive(Debug, Clone, PartialEq, PartialOrd, Default)]
@@ -9,28 +13,126 @@ pub struct Signal {
     pub start_bit: u64,
     pub length_bits: u64,
     pub unit: String,
-    pub min: f32,
-    pub max: f32,
+    pub min: f64,
+    pub max: f64,
     pub signed: bool,
+    pub value_type: ValueType,
+    pub multiplexing: Multiplexing,
+    /// Names of the ECUs which consume this signal
+    pub receivers: Vec<String>,
+    pub attributes: Attributes,
+    /// Shared value table (`VAL_TABLE_`) the enum values of the signal come from
+    pub value_table: Option<String>,
 } 
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+impl Signal {
+    /// Returns true if this signal is a multiplexor switch of its message
+    pub fn is_multiplexor(&self) -> bool {
+        matches!(self.multiplexing, Multiplexing::Multiplexor | Multiplexing::MultiplexorAndMultiplexed(_))
+    }
+
+    /// Returns the multiplexor condition for the signal to be present, if any
+    pub fn mux_condition(&self) -> Option<&MuxCondition> {
+        match &self.multiplexing {
+            Multiplexing::Multiplexed(c) | Multiplexing::MultiplexorAndMultiplexed(c) => Some(c),
+            _ => None
+        }
+    }
+
+}
+
+/// Current raw values of the multiplexor switches within a frame, keyed by signal name
+pub type MuxValues = HashMap<String, u64>;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Multiplexing {
+    /// Normal signal, always present
+    #[default]
+    Plain,
+    /// Multiplexor switch of the message (`M`)
+    Multiplexor,
+    /// Signal only present when its multiplexor matches the condition (`m<n>`)
+    Multiplexed(MuxCondition),
+    /// Signal is multiplexed (`m<n>M`) and is itself a multiplexor
+    MultiplexorAndMultiplexed(MuxCondition),
+}
+
+/// Condition under which a multiplexed signal is present in a frame
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct MuxCondition {
+    /// Name of the multiplexor switch signal
+    pub multiplexor: String,
+    /// Inclusive ranges of multiplexor values (min, max). Simple multiplexing
+    /// uses a single range with min == max, extended multiplexing (`SG_MUL_VAL_`)
+    /// may define several
+    pub ranges: Vec<(u64, u64)>,
+}
+
+impl MuxCondition {
+    pub fn matches(&self, mux_value: u64) -> bool {
+        self.ranges.iter().any(|(min, max)| mux_value >= *min && mux_value <= *max)
+    }
+}
+
+impl std::fmt::Display for MuxCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ranges: Vec<String> = self.ranges.iter().map(|(min, max)| {
+            if min == max {
+                format!("{}", min)
+            } else {
+                format!("{}-{}", min, max)
+            }
+        }).collect();
+        write!(f, "{} = {}", self.multiplexor, ranges.join(", "))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
 pub enum ByteOrder {
+    #[default]
     LittleEndian,
     BigEndian
 }
 
-impl Default for ByteOrder {
-    fn default() -> Self {
-        Self::LittleEndian
-    }
+/// Encoding of the raw bits of a signal (`SIG_VALTYPE_`)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum ValueType {
+    /// Signed or unsigned integer, see [Signal::signed]
+    #[default]
+    Integer,
+    /// IEEE 754 single precision float, 32 bits
+    Float32,
+    /// IEEE 754 double precision float, 64 bits
+    Float64,
 }
 
+/// Enum values and their labels, sorted by value
+pub type ValueTable = Vec<(i64, String)>;
+
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum SignalType {
     Bool,
-    Linear { multi: f32, offset: f32 },
-    Enum(Vec<(i64, String)>)
+    Linear { multi: f64, offset: f64 },
+    /// Labels are looked up by raw value, the scaling gives the physical value
+    Enum { entries: ValueTable, multi: f64, offset: f64 }
+}
+
+impl SignalType {
+    /// Factor and offset of the signal, Bool signals are never scaled
+    pub fn scaling(&self) -> (f64, f64) {
+        match self {
+            Self::Bool => (1.0, 0.0),
+            Self::Linear { multi, offset } | Self::Enum { multi, offset, .. } => (*multi, *offset),
+        }
+    }
+
+    /// Value descriptions of an enum signal
+    pub fn entries(&self) -> Option<&ValueTable> {
+        match self {
+            Self::Enum { entries, .. } => Some(entries),
+            _ => None,
+        }
+    }
 }
 
 impl Default for SignalType {
@@ -39,22 +141,969 @@ impl Default for SignalType {
     }
 }
 
+/// Identifier of a CAN frame
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CanId {
+    /// 11 bit standard identifier
+    Standard(u32),
+    /// 29 bit extended identifier
+    Extended(u32),
+}
+
+impl CanId {
+    /// Bit set on DBC message IDs to mark an extended identifier
+    pub const DBC_EXTENDED_FLAG: u32 = 0x8000_0000;
+
+    /// Largest 11 bit standard identifier
+    pub const MAX_STANDARD: u32 = 0x7FF;
+    /// Largest 29 bit extended identifier
+    pub const MAX_EXTENDED: u32 = 0x1FFF_FFFF;
+
+    /// Bits above the 11 or 29 bit identifier are dropped
+    pub fn new(id: u32, extended: bool) -> Self {
+        if extended {
+            Self::Extended(id & Self::MAX_EXTENDED)
+        } else {
+            Self::Standard(id & Self::MAX_STANDARD)
+        }
+    }
+
+    /// Converts a DBC message ID, which has bit 31 set for extended frames.
+    ///
+    /// Unlike [Self::new], a standard ID above 0x7FF is kept as it is, so
+    /// the mistake is reported by [crate::dbc_lint] and written back unchanged.
+    pub fn from_dbc(raw: u32) -> Self {
+        if raw & Self::DBC_EXTENDED_FLAG != 0 {
+            Self::Extended(raw & Self::MAX_EXTENDED)
+        } else {
+            Self::Standard(raw)
+        }
+    }
+
+    /// Converts back to the DBC message ID representation
+    pub fn to_dbc(&self) -> u32 {
+        match self {
+            Self::Standard(id) => *id,
+            Self::Extended(id) => *id | Self::DBC_EXTENDED_FLAG,
+        }
+    }
+
+    /// Identifier as it appears on the bus, without the extended flag
+    pub fn raw(&self) -> u32 {
+        match self {
+            Self::Standard(id) | Self::Extended(id) => *id,
+        }
+    }
+
+    pub fn is_extended(&self) -> bool {
+        matches!(self, Self::Extended(_))
+    }
+}
+
+impl Default for CanId {
+    fn default() -> Self {
+        Self::Standard(0)
+    }
+}
+
+impl std::fmt::Display for CanId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Standard(id) => write!(f, "0x{:04X}", id),
+            Self::Extended(id) => write!(f, "0x{:08X} EXT", id),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, PartialOrd, Default)]
 pub struct Message {
-    pub id: u32,
+    pub id: CanId,
     pub name: String,
     pub comment: Option<String>,
     pub length_bytes: u64,
     pub signals: Vec<Signal>,
+    pub attributes: Attributes,
+    pub signal_groups: Vec<SignalGroup>,
+    /// All nodes which send the message (`BO_TX_BU_`), for messages with more
+    /// than one sender. Empty if the DBC only names the ECU it is listed under
+    pub transmitters: Vec<String>,
+}
+
+/// Signals of a message which belong together (`SIG_GROUP_`)
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct SignalGroup {
+    pub name: String,
+    pub repetitions: u64,
+    /// Names of the signals in the group
+    pub signals: Vec<String>,
+}
+
+impl Message {
+    /// Returns the top level multiplexor switch signal of the message, if it is multiplexed
+    pub fn multiplexor(&self) -> Option<&Signal> {
+        self.signals.iter().find(|s| s.multiplexing == Multiplexing::Multiplexor)
+    }
+
+    /// Returns true if the signal is present in a frame with the given multiplexor values.
+    ///
+    /// For nested multiplexing, the whole chain of multiplexors up to the top
+    /// level switch has to be active for the signal to be present.
+    pub fn is_signal_active(&self, signal: &Signal, mux_values: &MuxValues) -> bool {
+        let mut current = signal;
+        // Bound the walk by the signal count, so a cyclic DBC cannot hang us
+        for _ in 0..=self.signals.len() {
+            let cond = match current.mux_condition() {
+                Some(c) => c,
+                None => return true
+            };
+            match mux_values.get(&cond.multiplexor) {
+                Some(v) if cond.matches(*v) => {},
+                _ => return false
+            }
+            match self.signals.iter().find(|s| s.name == cond.multiplexor) {
+                Some(parent) => current = parent,
+                None => return false
+            }
+        }
+        false
+    }
+
+    /// Returns the multiplexor conditions which all have to hold for the signal
+    /// to be present, from the signal's own condition up to the top level switch
+    pub fn mux_chain<'a>(&'a self, signal: &'a Signal) -> Vec<&'a MuxCondition> {
+        let mut chain: Vec<&MuxCondition> = Vec::new();
+        let mut current = signal;
+        while let Some(cond) = current.mux_condition() {
+            if chain.iter().any(|c| c.multiplexor == cond.multiplexor) {
+                break;
+            }
+            chain.push(cond);
+            match self.signals.iter().find(|s| s.name == cond.multiplexor) {
+                Some(parent) => current = parent,
+                None => break
+            }
+        }
+        chain
+    }
+
+    /// Returns true if both signals can never be present in the same frame,
+    /// because a multiplexor common to both requires disjoint values
+    pub fn are_mutually_exclusive(&self, a: &Signal, b: &Signal) -> bool {
+        let chain_b = self.mux_chain(b);
+        self.mux_chain(a).iter().any(|ca| {
+            chain_b.iter().any(|cb| {
+                ca.multiplexor == cb.multiplexor && !ca.ranges.iter().any(|(a_min, a_max)| {
+                    cb.ranges.iter().any(|(b_min, b_max)| a_min <= b_max && b_min <= a_max)
+                })
+            })
+        })
+    }
+
+    /// Returns all ECUs which receive at least one signal of the message
+    pub fn receivers(&self) -> Vec<&str> {
+        let mut res: Vec<&str> = Vec::new();
+        for r in self.signals.iter().flat_map(|s| s.receivers.iter()) {
+            if !res.contains(&r.as_str()) {
+                res.push(r);
+            }
+        }
+        res
+    }
+
+    /// Splits `signals` (E.g. from [Message::active_signals]) by signal group, in the
+    /// order the groups are defined. Signals outside of any group come last, under `None`.
+    /// A signal in several groups is listed under each of them.
+    pub fn grouped_signals<'a>(&'a self, signals: &[&'a Signal]) -> Vec<(Option<&'a SignalGroup>, Vec<&'a Signal>)> {
+        let mut res = Vec::new();
+        for group in &self.signal_groups {
+            let members: Vec<&Signal> = signals.iter().copied().filter(|s| group.signals.contains(&s.name)).collect();
+            if !members.is_empty() {
+                res.push((Some(group), members));
+            }
+        }
+        let ungrouped: Vec<&Signal> = signals.iter().copied()
+            .filter(|s| !self.signal_groups.iter().any(|g| g.signals.contains(&s.name)))
+            .collect();
+        if !ungrouped.is_empty() {
+            res.push((None, ungrouped));
+        }
+        res
+    }
+
+    /// Returns all signals present in the frame for the given multiplexor values
+    pub fn active_signals(&self, mux_values: &MuxValues) -> Vec<&Signal> {
+        self.signals.iter().filter(|s| self.is_signal_active(s, mux_values)).collect()
+    }
+
+    /// Returns the chain of active multiplexors with their values, starting
+    /// from the top level multiplexor switch
+    pub fn active_mux_chain(&self, mux_values: &MuxValues) -> Vec<(&Signal, u64)> {
+        let mut chain = Vec::new();
+        let mut current = self.multiplexor();
+        while let Some(mux) = current {
+            let value = match mux_values.get(&mux.name) {
+                Some(v) => *v,
+                None => break
+            };
+            if chain.iter().any(|(s, _): &(&Signal, u64)| s.name == mux.name) {
+                break;
+            }
+            chain.push((mux, value));
+            current = self.signals.iter().find(|s| {
+                matches!(&s.multiplexing, Multiplexing::MultiplexorAndMultiplexed(c) if c.multiplexor == mux.name && c.matches(value))
+            });
+        }
+        chain
+    }
 }
 
+/// Network node (`BU_`). Nodes which only receive have no messages
 #[derive(Debug, Clone, PartialEq, PartialOrd, Default)]
 pub struct Ecu {
     pub name: String,
-    pub messages: Vec<Message>
+    pub comment: Option<String>,
+    pub messages: Vec<Message>,
+    pub attributes: Attributes,
+}
+
+/// Stable reference to a message, which stays valid when messages are added,
+/// removed or moved between ECUs. Messages sharing an ID are told apart by
+/// their order in the tree, so reordering those changes their handles
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct MessageHandle {
+    pub id: CanId,
+    /// Number of messages with the same ID before this one, in tree order
+    pub duplicate: usize,
+}
+
+impl From<CanId> for MessageHandle {
+    /// Handle of the first message with the ID
+    fn from(id: CanId) -> Self {
+        Self { id, duplicate: 0 }
+    }
+}
+
+/// Stable reference to a signal within a message
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct SignalHandle {
+    pub message: MessageHandle,
+    pub signal: String,
+}
+
+/// Lookup tables built by [TreeDbc::reindex]. Values are positions in the
+/// tree, which are checked against the requested ID or name on every lookup,
+/// falling back to walking the tree. Trees only compare equal if their
+/// indexes do, so a missing [TreeDbc::reindex] shows up in comparisons.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Default)]
+struct DbcIndex {
+    /// Positions of all messages with the ID, in tree order
+    by_id: BTreeMap<CanId, Vec<(usize, usize)>>,
+    by_name: BTreeMap<String, MessageHandle>,
+    /// Keyed by `Message.Signal` path
+    by_path: BTreeMap<String, SignalHandle>,
+    signal_pos: BTreeMap<SignalHandle, usize>,
+    /// J1939 messages keyed by PGN
+    by_pgn: BTreeMap<u32, MessageHandle>,
 }
 
 #[derive(Debug, Clone, PartialEq, PartialOrd, Default)]
 pub struct TreeDbc {
-    pub ecus: Vec<Ecu>
-}
\ No newline at end of file
+    pub ecus: Vec<Ecu>,
+    /// Attribute definitions (`BA_DEF_`) along with their defaults (`BA_DEF_DEF_`)
+    pub attribute_definitions: Vec<AttributeDefinition>,
+    /// Network wide attribute values
+    pub attributes: Attributes,
+    /// Shared value tables (`VAL_TABLE_`), keyed by name
+    pub value_tables: BTreeMap<String, ValueTable>,
+    /// Version string of the file (`VERSION`)
+    pub version: String,
+    /// Comment on the whole network (`CM_ "..."`)
+    pub comment: Option<String>,
+    /// Environment variables (`EV_`), used by simulation tools
+    pub environment_variables: Vec<EnvironmentVariable>,
+    index: DbcIndex,
+}
+
+impl TreeDbc {
+    /// Rebuilds the lookup indexes. Must be called after modifying `ecus`
+    /// directly, otherwise lookups fall back to walking the tree
+    pub fn reindex(&mut self) {
+        let mut index = DbcIndex::default();
+        for (ecu_idx, ecu) in self.ecus.iter().enumerate() {
+            for (msg_idx, msg) in ecu.messages.iter().enumerate() {
+                let positions = index.by_id.entry(msg.id).or_default();
+                let handle = MessageHandle { id: msg.id, duplicate: positions.len() };
+                positions.push((ecu_idx, msg_idx));
+                // First definition wins, matching how frames are decoded
+                index.by_name.entry(msg.name.clone()).or_insert(handle);
+                for (sig_idx, signal) in msg.signals.iter().enumerate() {
+                    let sig_handle = SignalHandle { message: handle, signal: signal.name.clone() };
+                    index.by_path.entry(format!("{}.{}", msg.name, signal.name)).or_insert_with(|| sig_handle.clone());
+                    index.signal_pos.insert(sig_handle, sig_idx);
+                }
+                if let Some(j1939) = self.j1939_id(msg) {
+                    index.by_pgn.entry(j1939.pgn).or_insert(handle);
+                }
+            }
+        }
+        self.index = index;
+    }
+
+    /// Iterates over all messages of all ECUs
+    pub fn messages(&self) -> impl Iterator<Item = &Message> {
+        self.ecus.iter().flat_map(|e| e.messages.iter())
+    }
+
+    /// Iterates over all messages of all ECUs along with their handles
+    pub fn messages_with_handles(&self) -> impl Iterator<Item = (MessageHandle, &Message)> {
+        let mut seen: HashMap<CanId, usize> = HashMap::new();
+        self.messages().map(move |msg| {
+            let count = seen.entry(msg.id).or_default();
+            *count += 1;
+            (MessageHandle { id: msg.id, duplicate: *count - 1 }, msg)
+        })
+    }
+
+    fn message_pos(&self, handle: MessageHandle) -> Option<(usize, usize)> {
+        if let Some((e, m)) = self.index.by_id.get(&handle.id).and_then(|p| p.get(handle.duplicate)) {
+            if self.ecus.get(*e).and_then(|ecu| ecu.messages.get(*m)).map(|msg| msg.id) == Some(handle.id) {
+                return Some((*e, *m))
+            }
+        }
+        self.ecus.iter().enumerate()
+            .flat_map(|(e, ecu)| ecu.messages.iter().enumerate().map(move |(m, msg)| (e, m, msg)))
+            .filter(|(_, _, msg)| msg.id == handle.id)
+            .nth(handle.duplicate)
+            .map(|(e, m, _)| (e, m))
+    }
+
+    /// Returns the handle of a message of the tree, or of a message equal to it
+    pub fn message_handle(&self, msg: &Message) -> Option<MessageHandle> {
+        let candidates: Vec<(MessageHandle, &Message)> = (0..).map_while(|duplicate| {
+            let handle = MessageHandle { id: msg.id, duplicate };
+            self.message(handle).map(|m| (handle, m))
+        }).collect();
+        candidates.iter().find(|(_, m)| std::ptr::eq(*m, msg))
+            .or_else(|| candidates.iter().find(|(_, m)| *m == msg))
+            .map(|(h, _)| *h)
+    }
+
+    pub fn signal_handle(&self, msg: &Message, signal: &Signal) -> Option<SignalHandle> {
+        self.message_handle(msg).map(|message| SignalHandle { message, signal: signal.name.clone() })
+    }
+
+    /// Returns the first message with the ID
+    pub fn message_by_id(&self, id: CanId) -> Option<&Message> {
+        self.message(id.into())
+    }
+
+    /// Returns true if the message is a J1939 parameter group. Either the
+    /// message's `VFrameFormat` is J1939PG, or the network's `ProtocolType`
+    /// is J1939 and the message has an extended ID
+    pub fn is_j1939(&self, msg: &Message) -> bool {
+        if !msg.id.is_extended() {
+            return false
+        }
+        match self.frame_format(msg) {
+            Some(f) if f == "J1939PG" => true,
+            _ => self.network_attribute("ProtocolType").is_some_and(|p| p.as_str() == Some("J1939")),
+        }
+    }
+
+    /// J1939 fields of a message's ID, `None` if it is not a J1939 message
+    pub fn j1939_id(&self, msg: &Message) -> Option<J1939Id> {
+        J1939Id::from_can_id(msg.id).filter(|_| self.is_j1939(msg))
+    }
+
+    /// Finds the J1939 message defining a parameter group
+    pub fn message_by_pgn(&self, pgn: u32) -> Option<&Message> {
+        if let Some(msg) = self.index.by_pgn.get(&pgn).and_then(|h| self.message(*h)) {
+            if self.j1939_id(msg).is_some_and(|j| j.pgn == pgn) {
+                return Some(msg)
+            }
+        }
+        self.messages().find(|m| self.j1939_id(m).is_some_and(|j| j.pgn == pgn))
+    }
+
+    /// Finds the message describing a received frame. IDs defined in the
+    /// database match first, other extended IDs are matched to J1939
+    /// messages by PGN, whatever their priority and source address
+    pub fn message_for_frame(&self, id: CanId) -> Option<&Message> {
+        self.message_by_id(id).or_else(|| J1939Id::from_can_id(id).and_then(|j| self.message_by_pgn(j.pgn)))
+    }
+
+    pub fn message(&self, handle: MessageHandle) -> Option<&Message> {
+        self.message_pos(handle).map(|(e, m)| &self.ecus[e].messages[m])
+    }
+
+    pub fn message_mut(&mut self, handle: MessageHandle) -> Option<&mut Message> {
+        let (e, m) = self.message_pos(handle)?;
+        Some(&mut self.ecus[e].messages[m])
+    }
+
+    pub fn message_by_name(&self, name: &str) -> Option<&Message> {
+        if let Some(msg) = self.index.by_name.get(name).and_then(|h| self.message(*h)) {
+            if msg.name == name {
+                return Some(msg)
+            }
+        }
+        self.messages().find(|m| m.name == name)
+    }
+
+    /// Returns the ECU transmitting the message
+    pub fn transmitter(&self, handle: MessageHandle) -> Option<&Ecu> {
+        self.message_pos(handle).map(|(e, _)| &self.ecus[e])
+    }
+
+    pub fn signal(&self, handle: &SignalHandle) -> Option<&Signal> {
+        let msg = self.message(handle.message)?;
+        if let Some(idx) = self.index.signal_pos.get(handle) {
+            if let Some(s) = msg.signals.get(*idx).filter(|s| s.name == handle.signal) {
+                return Some(s)
+            }
+        }
+        msg.signals.iter().find(|s| s.name == handle.signal)
+    }
+
+    /// Resolves a signal path in the form `Message.Signal`
+    pub fn signal_handle_by_path(&self, path: &str) -> Option<SignalHandle> {
+        if let Some(h) = self.index.by_path.get(path) {
+            if self.signal(h).is_some() {
+                return Some(h.clone())
+            }
+        }
+        let (msg_name, sig_name) = path.split_once('.')?;
+        let msg = self.message_by_name(msg_name)?;
+        let signal = msg.signals.iter().find(|s| s.name == sig_name)?;
+        self.signal_handle(msg, signal)
+    }
+
+    pub fn signal_by_path(&self, path: &str) -> Option<&Signal> {
+        self.signal_handle_by_path(path).and_then(|h| self.signal(&h))
+    }
+
+    /// Returns the `Message.Signal` path of a signal
+    pub fn signal_path(&self, handle: &SignalHandle) -> Option<String> {
+        self.message(handle.message).map(|m| format!("{}.{}", m.name, handle.signal))
+    }
+
+    /// Returns all messages the ECU receives at least one signal from
+    pub fn received_messages(&self, ecu: &str) -> Vec<&Message> {
+        self.ecus.iter()
+            .flat_map(|e| e.messages.iter())
+            .filter(|m| m.signals.iter().any(|s| s.receivers.iter().any(|r| r == ecu)))
+            .collect()
+    }
+
+    /// Builds the sender / receiver graph of the network. There is one link
+    /// per sender and receiver pair, listing the IDs of the messages exchanged
+    pub fn node_graph(&self) -> Vec<NodeLink> {
+        let mut links: Vec<NodeLink> = Vec::new();
+        for ecu in &self.ecus {
+            for msg in &ecu.messages {
+                for receiver in msg.receivers() {
+                    match links.iter_mut().find(|l| l.sender == ecu.name && l.receiver == receiver) {
+                        Some(link) => link.messages.push(msg.id),
+                        None => links.push(NodeLink {
+                            sender: ecu.name.clone(),
+                            receiver: receiver.to_string(),
+                            messages: vec![msg.id]
+                        })
+                    }
+                }
+            }
+        }
+        links
+    }
+
+    /// Returns the name of the value table with exactly these values, if any
+    pub fn matching_value_table(&self, values: &ValueTable) -> Option<&str> {
+        self.value_tables.iter().find(|(_, v)| *v == values).map(|(name, _)| name.as_str())
+    }
+
+    pub fn attribute_definition(&self, name: &str) -> Option<&AttributeDefinition> {
+        self.attribute_definitions.iter().find(|d| d.name == name)
+    }
+
+    /// Looks up an attribute from a set of object attributes, falling back to
+    /// the default value. Enum attributes are resolved to their label.
+    pub fn resolve_attribute(&self, attributes: &Attributes, name: &str) -> Option<AttributeValue> {
+        let def = self.attribute_definition(name);
+        let value = attributes.get(name).or_else(|| def.and_then(|d| d.default.as_ref()))?;
+        match def {
+            Some(d) => Some(d.resolve(value)),
+            None => Some(value.clone())
+        }
+    }
+
+    pub fn network_attribute(&self, name: &str) -> Option<AttributeValue> {
+        self.resolve_attribute(&self.attributes, name)
+    }
+
+    pub fn ecu_attribute(&self, ecu: &Ecu, name: &str) -> Option<AttributeValue> {
+        self.resolve_attribute(&ecu.attributes, name)
+    }
+
+    pub fn message_attribute(&self, msg: &Message, name: &str) -> Option<AttributeValue> {
+        self.resolve_attribute(&msg.attributes, name)
+    }
+
+    pub fn signal_attribute(&self, signal: &Signal, name: &str) -> Option<AttributeValue> {
+        self.resolve_attribute(&signal.attributes, name)
+    }
+
+    /// Returns every attribute applying to an object of the given type,
+    /// including those only set by a default value
+    pub fn all_attributes(&self, object: AttributeObject, attributes: &Attributes) -> Vec<(String, AttributeValue)> {
+        let mut res: Vec<(String, AttributeValue)> = self.attribute_definitions.iter()
+            .filter(|d| d.object == object)
+            .filter_map(|d| self.resolve_attribute(attributes, &d.name).map(|v| (d.name.clone(), v)))
+            .collect();
+        for (name, value) in attributes {
+            if self.attribute_definition(name).is_none() {
+                res.push((name.clone(), value.clone()));
+            }
+        }
+        res
+    }
+
+    /// Message cycle time in milliseconds (`GenMsgCycleTime`)
+    pub fn cycle_time_ms(&self, msg: &Message) -> Option<i64> {
+        self.message_attribute(msg, "GenMsgCycleTime").and_then(|v| v.as_i64())
+    }
+
+    /// Message send type (`GenMsgSendType`), E.g. Cyclic
+    pub fn send_type(&self, msg: &Message) -> Option<String> {
+        self.message_attribute(msg, "GenMsgSendType").map(|v| v.to_string())
+    }
+
+    /// Frame format of the message (`VFrameFormat`), E.g. StandardCAN
+    pub fn frame_format(&self, msg: &Message) -> Option<String> {
+        self.message_attribute(msg, "VFrameFormat").map(|v| v.to_string())
+    }
+
+    /// J1939 suspect parameter number of a signal (`SPN`)
+    pub fn spn(&self, signal: &Signal) -> Option<i64> {
+        // J1939 DBCs commonly default SPN to 0, which is not a valid SPN
+        self.signal_attribute(signal, "SPN").and_then(|v| v.as_i64()).filter(|spn| *spn > 0)
+    }
+
+    /// Raw start value of a signal (`GenSigStartValue`)
+    pub fn start_value(&self, signal: &Signal) -> Option<f64> {
+        self.signal_attribute(signal, "GenSigStartValue").and_then(|v| v.as_f64())
+    }
+}
+
+/// Directed edge of the network node graph
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct NodeLink {
+    pub sender: String,
+    pub receiver: String,
+    /// IDs of the messages sent from `sender` with signals consumed by `receiver`
+    pub messages: Vec<CanId>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum EnvVarType {
+    #[default]
+    Integer,
+    Float,
+    /// Raw bytes, with the length given by [EnvironmentVariable::data_size]
+    Data,
+}
+
+/// Environment variable (`EV_`)
+#[derive(Debug, Clone, PartialEq, PartialOrd, Default)]
+pub struct EnvironmentVariable {
+    pub name: String,
+    pub var_type: EnvVarType,
+    pub min: i64,
+    pub max: i64,
+    pub unit: String,
+    pub initial_value: f64,
+    pub id: i64,
+    /// 0 unrestricted, 1 read only, 2 write only, 3 read and write
+    pub access_type: u8,
+    /// Names of the nodes which access the variable
+    pub access_nodes: Vec<String>,
+    /// Length in bytes of a data variable (`ENVVAR_DATA_`)
+    pub data_size: Option<u64>,
+    pub comment: Option<String>,
+    pub attributes: Attributes,
+}
+
+/// Attribute values of a DBC object, keyed by attribute name
+pub type Attributes = BTreeMap<String, AttributeValue>;
+
+/// Type of DBC object an attribute applies to
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum AttributeObject {
+    #[default]
+    Network,
+    Node,
+    Message,
+    Signal,
+    EnvironmentVariable,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub enum AttributeValueType {
+    Int { min: i64, max: i64 },
+    Hex { min: i64, max: i64 },
+    Float { min: f64, max: f64 },
+    String,
+    Enum(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub enum AttributeValue {
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl AttributeValue {
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            AttributeValue::Int(i) => Some(*i),
+            AttributeValue::Float(f) => Some(*f as i64),
+            AttributeValue::String(s) => s.parse().ok(),
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            AttributeValue::Int(i) => Some(*i as f64),
+            AttributeValue::Float(f) => Some(*f),
+            AttributeValue::String(s) => s.parse().ok(),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            AttributeValue::String(s) => Some(s),
+            _ => None
+        }
+    }
+}
+
+impl std::fmt::Display for AttributeValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AttributeValue::Int(i) => write!(f, "{}", i),
+            AttributeValue::Float(x) => write!(f, "{}", x),
+            AttributeValue::String(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+/// Definition of an attribute (`BA_DEF_`)
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct AttributeDefinition {
+    pub name: String,
+    pub object: AttributeObject,
+    pub value_type: AttributeValueType,
+    /// Value used by objects which do not set the attribute (`BA_DEF_DEF_`)
+    pub default: Option<AttributeValue>,
+}
+
+impl AttributeDefinition {
+    /// Converts a stored value to its typed form. Enum values are stored as
+    /// an index in the DBC, and are resolved to their label
+    pub fn resolve(&self, value: &AttributeValue) -> AttributeValue {
+        match (&self.value_type, value) {
+            (AttributeValueType::Enum(labels), v) if v.as_str().is_none() => {
+                match v.as_i64().and_then(|idx| labels.get(idx as usize)) {
+                    Some(label) => AttributeValue::String(label.clone()),
+                    None => v.clone()
+                }
+            },
+            (AttributeValueType::Int { .. } | AttributeValueType::Hex { .. }, AttributeValue::Float(f)) => AttributeValue::Int(*f as i64),
+            _ => value.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_dbc_from_bytes;
+
+    const DBC: &str = r#"VERSION ""
+
+NS_ :
+
+BS_:
+
+BU_: Engine Dash Gateway Logger
+BO_ 256 Status: 8 Engine
+ SG_ Speed : 0|16@1+ (1,0) [0|0] "" Dash
+ SG_ Gear : 16|4@1+ (1,0) [0|0] "" Dash,Gateway
+
+BO_ 512 Lights: 2 Dash
+ SG_ Beam : 0|1@1+ (1,0) [0|0] "" Engine
+
+BO_ 256 StatusCopy: 4 Gateway
+ SG_ Speed : 8|16@1+ (1,0) [0|0] "" Dash
+"#;
+
+    fn dbc() -> TreeDbc {
+        load_dbc_from_bytes(DBC.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn duplicate_ids_have_their_own_handles() {
+        let dbc = dbc();
+        let handles: Vec<(MessageHandle, &str)> = dbc.messages_with_handles().map(|(h, m)| (h, m.name.as_str())).collect();
+        let status = MessageHandle { id: CanId::Standard(256), duplicate: 0 };
+        let copy = MessageHandle { id: CanId::Standard(256), duplicate: 1 };
+        let lights = MessageHandle::from(CanId::Standard(512));
+        assert_eq!(handles, [(status, "Status"), (lights, "Lights"), (copy, "StatusCopy")]);
+
+        for (handle, name) in &handles {
+            let msg = dbc.message(*handle).unwrap();
+            assert_eq!(msg.name, *name);
+            assert_eq!(dbc.message_handle(msg), Some(*handle));
+            // Copies of a message resolve to the message they were taken from
+            assert_eq!(dbc.message_handle(&msg.clone()), Some(*handle));
+        }
+        assert_eq!(dbc.message(MessageHandle { id: CanId::Standard(256), duplicate: 2 }), None);
+        assert_eq!(dbc.message_by_id(CanId::Standard(256)).unwrap().name, "Status");
+        assert_eq!(dbc.transmitter(copy).unwrap().name, "Gateway");
+        assert_eq!(dbc.message_by_name("StatusCopy").and_then(|m| dbc.message_handle(m)), Some(copy));
+
+        let speed = dbc.signal_handle_by_path("StatusCopy.Speed").unwrap();
+        assert_eq!(speed, SignalHandle { message: copy, signal: "Speed".into() });
+        assert_eq!(dbc.signal(&speed).unwrap().start_bit, 8);
+        assert_eq!(dbc.signal_path(&speed).as_deref(), Some("StatusCopy.Speed"));
+        assert_eq!(dbc.signal_by_path("Status.Speed").unwrap().start_bit, 0);
+        assert_eq!(dbc.signal_by_path("Status.Missing"), None);
+        assert_eq!(dbc.signal_by_path("Speed"), None);
+    }
+
+    #[test]
+    fn stale_index_is_not_used() {
+        let mut dbc = dbc();
+        let moved = dbc.ecus[0].messages.remove(0);
+        dbc.ecus[1].messages.insert(0, moved);
+        dbc.ecus[2].messages[0].signals.reverse();
+
+        // Lookups fall back to walking the tree
+        let status = MessageHandle::from(CanId::Standard(256));
+        assert_eq!(dbc.transmitter(status).unwrap().name, "Dash");
+        assert_eq!(dbc.message_by_name("Lights").unwrap().id, CanId::Standard(512));
+        assert_eq!(dbc.signal_by_path("Status.Gear").unwrap().start_bit, 16);
+
+        // The outdated index makes the tree differ from a reindexed copy
+        let mut reindexed = dbc.clone();
+        reindexed.reindex();
+        assert_ne!(dbc, reindexed);
+        dbc.reindex();
+        assert_eq!(dbc, reindexed);
+    }
+
+    #[test]
+    fn can_id_masks_to_id_length() {
+        assert_eq!(CanId::new(0x123, false), CanId::Standard(0x123));
+        assert_eq!(CanId::new(0xF123, false), CanId::Standard(0x123));
+        assert_eq!(CanId::new(0x18FF_0001, true), CanId::Extended(0x18FF_0001));
+        assert_eq!(CanId::new(0xF8FF_0001, true), CanId::Extended(0x18FF_0001));
+        assert_eq!(CanId::new(0x7FF, true).raw(), 0x7FF);
+    }
+
+    #[test]
+    fn can_id_from_and_to_dbc() {
+        for (dbc, id) in [
+            (0x100, CanId::Standard(0x100)),
+            (0x7FF, CanId::Standard(0x7FF)),
+            (0x8000_0100, CanId::Extended(0x100)),
+            (0x98FF_0001, CanId::Extended(0x18FF_0001)),
+            // Left for the lint pass to report
+            (0x800, CanId::Standard(0x800)),
+        ] {
+            assert_eq!(CanId::from_dbc(dbc), id, "{:X}", dbc);
+            assert_eq!(id.to_dbc(), dbc);
+            assert_eq!(id.is_extended(), dbc & CanId::DBC_EXTENDED_FLAG != 0);
+        }
+        // Bits 29 and 30 are not part of an extended ID
+        assert_eq!(CanId::from_dbc(0xC000_0000), CanId::Extended(0));
+        assert_eq!(CanId::Standard(0x12).to_string(), "0x0012");
+        assert_eq!(CanId::Extended(0x12).to_string(), "0x00000012 EXT");
+    }
+
+    #[test]
+    fn received_messages_and_node_graph() {
+        let dbc = dbc();
+        let ecus: Vec<(&str, usize)> = dbc.ecus.iter().map(|e| (e.name.as_str(), e.messages.len())).collect();
+        assert_eq!(ecus, [("Engine", 1), ("Dash", 1), ("Gateway", 1), ("Logger", 0)]);
+
+        let received = |ecu: &str| -> Vec<String> { dbc.received_messages(ecu).iter().map(|m| m.name.clone()).collect() };
+        assert_eq!(received("Dash"), ["Status", "StatusCopy"]);
+        assert_eq!(received("Gateway"), ["Status"]);
+        assert_eq!(received("Engine"), ["Lights"]);
+        assert!(received("Logger").is_empty());
+        assert_eq!(dbc.message_by_name("Status").unwrap().receivers(), ["Dash", "Gateway"]);
+
+        let link = |sender: &str, receiver: &str, ids: &[u32]| NodeLink {
+            sender: sender.into(),
+            receiver: receiver.into(),
+            messages: ids.iter().map(|id| CanId::Standard(*id)).collect(),
+        };
+        assert_eq!(dbc.node_graph(), [
+            link("Engine", "Dash", &[256]),
+            link("Engine", "Gateway", &[256]),
+            link("Dash", "Engine", &[512]),
+            link("Gateway", "Dash", &[256]),
+        ]);
+    }
+
+    const ATTRIBUTE_DBC: &str = r#"VERSION ""
+
+NS_ :
+
+BS_:
+
+BU_: Engine Dash
+BO_ 256 Status: 8 Engine
+ SG_ Speed : 0|16@1+ (1,0) [0|0] "" Dash
+
+BO_ 512 Lights: 2 Dash
+ SG_ Beam : 0|1@1+ (1,0) [0|0] "" Engine
+
+BA_DEF_  "BusType" STRING ;
+BA_DEF_ BU_  "NodeLayer" INT 0 10;
+BA_DEF_ BO_  "GenMsgCycleTime" INT 0 10000;
+BA_DEF_ BO_  "GenMsgSendType" ENUM  "Cyclic","Event";
+BA_DEF_ SG_  "GenSigStartValue" FLOAT 0 100;
+BA_DEF_ SG_  "SPN" INT 0 524287;
+BA_DEF_DEF_  "BusType" "CAN";
+BA_DEF_DEF_  "NodeLayer" 1;
+BA_DEF_DEF_  "GenMsgCycleTime" 100;
+BA_DEF_DEF_  "GenMsgSendType" "Cyclic";
+BA_DEF_DEF_  "GenSigStartValue" 0;
+BA_DEF_DEF_  "SPN" 0;
+BA_ "NodeLayer" BU_ Dash 3;
+BA_ "GenMsgCycleTime" BO_ 512 20;
+BA_ "GenMsgSendType" BO_ 512 1;
+BA_ "GenSigStartValue" SG_ 256 Speed 2.5;
+BA_ "SPN" SG_ 256 Speed 84;
+"#;
+
+    #[test]
+    fn attribute_accessors() {
+        let dbc = load_dbc_from_bytes(ATTRIBUTE_DBC.as_bytes()).unwrap();
+        let (engine, dash) = (&dbc.ecus[0], &dbc.ecus[1]);
+        let status = dbc.message_by_name("Status").unwrap();
+        let lights = dbc.message_by_name("Lights").unwrap();
+        let (speed, beam) = (&status.signals[0], &lights.signals[0]);
+
+        assert_eq!(dbc.network_attribute("BusType"), Some(AttributeValue::String("CAN".into())));
+        assert_eq!(dbc.ecu_attribute(engine, "NodeLayer").and_then(|v| v.as_i64()), Some(1));
+        assert_eq!(dbc.ecu_attribute(dash, "NodeLayer").and_then(|v| v.as_i64()), Some(3));
+        assert_eq!(dbc.ecu_attribute(dash, "Missing"), None);
+
+        assert_eq!((dbc.cycle_time_ms(status), dbc.cycle_time_ms(lights)), (Some(100), Some(20)));
+        assert_eq!(dbc.send_type(status).as_deref(), Some("Cyclic"));
+        // Enum values are stored as an index
+        assert_eq!(dbc.send_type(lights).as_deref(), Some("Event"));
+        assert_eq!(dbc.frame_format(status), None);
+
+        assert_eq!((dbc.start_value(speed), dbc.start_value(beam)), (Some(2.5), Some(0.0)));
+        // The default SPN of 0 is not a valid SPN
+        assert_eq!((dbc.spn(speed), dbc.spn(beam)), (Some(84), None));
+
+        let names: Vec<String> = dbc.all_attributes(AttributeObject::Message, &lights.attributes).iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        assert_eq!(names, ["GenMsgCycleTime=20", "GenMsgSendType=Event"]);
+    }
+
+    #[test]
+    fn all_attributes_lists_undefined_values() {
+        let dbc = load_dbc_from_bytes(ATTRIBUTE_DBC.as_bytes()).unwrap();
+        let mut attributes = Attributes::new();
+        attributes.insert("Custom".into(), AttributeValue::Int(7));
+        let names: Vec<String> = dbc.all_attributes(AttributeObject::Node, &attributes).iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        assert_eq!(names, ["NodeLayer=1", "Custom=7"]);
+    }
+
+    #[test]
+    fn resolve_attribute_definition() {
+        let send_type = AttributeDefinition {
+            name: "GenMsgSendType".into(),
+            object: AttributeObject::Message,
+            value_type: AttributeValueType::Enum(vec!["Cyclic".into(), "Event".into()]),
+            default: None,
+        };
+        assert_eq!(send_type.resolve(&AttributeValue::Int(1)), AttributeValue::String("Event".into()));
+        assert_eq!(send_type.resolve(&AttributeValue::Float(0.0)), AttributeValue::String("Cyclic".into()));
+        // Labels and unknown indexes are kept as they are
+        assert_eq!(send_type.resolve(&AttributeValue::String("Event".into())), AttributeValue::String("Event".into()));
+        assert_eq!(send_type.resolve(&AttributeValue::Int(2)), AttributeValue::Int(2));
+
+        let cycle_time = AttributeDefinition {
+            value_type: AttributeValueType::Int { min: 0, max: 10000 },
+            ..send_type.clone()
+        };
+        assert_eq!(cycle_time.resolve(&AttributeValue::Float(20.0)), AttributeValue::Int(20));
+        let hex = AttributeDefinition { value_type: AttributeValueType::Hex { min: 0, max: 0xFF }, ..send_type.clone() };
+        assert_eq!(hex.resolve(&AttributeValue::Float(255.0)), AttributeValue::Int(255));
+        let float = AttributeDefinition { value_type: AttributeValueType::Float { min: 0.0, max: 1.0 }, ..send_type };
+        assert_eq!(float.resolve(&AttributeValue::Float(0.5)), AttributeValue::Float(0.5));
+    }
+
+    const GROUP_DBC: &str = r#"VERSION ""
+
+NS_ :
+
+BS_:
+
+BU_: Body
+VAL_TABLE_ OnOff 0 "Off" 1 "On" ;
+VAL_TABLE_ Level 0 "Low" 1 "High" 2 "Auto" ;
+
+BO_ 300 Body: 8 Body
+ SG_ Mux M : 0|2@1+ (1,0) [0|0] "" Vector__XXX
+ SG_ Door m0 : 8|1@1+ (1,0) [0|0] "" Vector__XXX
+ SG_ Window m1 : 8|8@1+ (1,0) [0|0] "" Vector__XXX
+ SG_ Lock : 16|2@1+ (1,0) [0|0] "" Vector__XXX
+ SG_ Light : 18|2@1+ (1,0) [0|0] "" Vector__XXX
+
+VAL_ 300 Lock 0 "Off" 1 "On" ;
+VAL_ 300 Light 0 "Off" 1 "Dimmed" ;
+SIG_GROUP_ 300 Doors 1 : Door Lock;
+SIG_GROUP_ 300 Access 1 : Lock Window;
+"#;
+
+    #[test]
+    fn grouped_signals() {
+        let dbc = load_dbc_from_bytes(GROUP_DBC.as_bytes()).unwrap();
+        let msg = dbc.message_by_name("Body").unwrap();
+        let groups = |mux: u64| -> Vec<(Option<String>, Vec<String>)> {
+            let active = msg.active_signals(&MuxValues::from([("Mux".to_string(), mux)]));
+            msg.grouped_signals(&active).into_iter()
+                .map(|(g, signals)| (g.map(|g| g.name.clone()), signals.iter().map(|s| s.name.clone()).collect()))
+                .collect()
+        };
+        let group = |name: Option<&str>, signals: &[&str]| (name.map(String::from), signals.iter().map(|s| s.to_string()).collect::<Vec<_>>());
+        // Lock is in both groups, groups without active signals are left out
+        assert_eq!(groups(0), [group(Some("Doors"), &["Door", "Lock"]), group(Some("Access"), &["Lock"]), group(None, &["Mux", "Light"])]);
+        assert_eq!(groups(1), [group(Some("Doors"), &["Lock"]), group(Some("Access"), &["Window", "Lock"]), group(None, &["Mux", "Light"])]);
+        assert_eq!(msg.grouped_signals(&[]), []);
+        assert_eq!(msg.signal_groups[0], SignalGroup { name: "Doors".into(), repetitions: 1, signals: vec!["Door".into(), "Lock".into()] });
+    }
+
+    #[test]
+    fn shared_value_tables() {
+        let dbc = load_dbc_from_bytes(GROUP_DBC.as_bytes()).unwrap();
+        assert_eq!(dbc.value_tables.keys().collect::<Vec<_>>(), ["Level", "OnOff"]);
+        let msg = dbc.message_by_name("Body").unwrap();
+        // Only signals with exactly the values of a table use it
+        assert_eq!(msg.signals[3].value_table.as_deref(), Some("OnOff"));
+        assert_eq!(msg.signals[4].value_table, None);
+        assert_eq!(dbc.matching_value_table(&vec![(0, "Low".into()), (1, "High".into()), (2, "Auto".into())]), Some("Level"));
+        assert_eq!(dbc.matching_value_table(&vec![(0, "Low".into())]), None);
+    }
+}
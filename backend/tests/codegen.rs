@@ -0,0 +1,116 @@
+//! Compiles the code generated for `fixtures/codegen.dbc` and checks it
+//! against the backend's own decoder and encoder.
+//!
+//! After changing the generator, update the generated file with
+//! `CANVIEWER_UPDATE_CODEGEN=1 cargo test --test codegen`.
+
+#[allow(dead_code)]
+mod generated {
+    include!("fixtures/codegen_generated.rs");
+}
+
+use backend::{encode_message, load_database_from_file, parse_signal, SignalValue, TreeDbc};
+use generated::{CanError, DashData, Diag, DiagStatus, EngineStatus, Frame, GearTable};
+
+fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+fn dbc() -> TreeDbc {
+    load_database_from_file(&fixture("codegen.dbc"), None).unwrap()
+}
+
+/// Physical value of every signal of a message which is present in the payload, as decoded by the backend
+fn backend_values(dbc: &TreeDbc, msg: &str, data: &[u8]) -> Vec<f64> {
+    let msg = dbc.message_by_name(msg).unwrap();
+    let mux = backend::multiplexor_values(msg, data);
+    msg.active_signals(&mux).iter().map(|s| parse_signal(s, data).unwrap().physical).collect()
+}
+
+#[test]
+fn generated_code_is_up_to_date() {
+    let code = backend::generate_rust(&dbc()).unwrap();
+    let path = fixture("codegen_generated.rs");
+    if std::env::var_os("CANVIEWER_UPDATE_CODEGEN").is_some() {
+        std::fs::write(&path, &code).unwrap();
+    }
+    let current = std::fs::read_to_string(&path).unwrap();
+    assert!(current == code, "{} is out of date, regenerate it with CANVIEWER_UPDATE_CODEGEN=1 cargo test --test codegen", path);
+}
+
+#[test]
+fn decode_matches_backend() {
+    let dbc = dbc();
+    let data = [0x10, 0x27, 0x13, 0x05, 0xA3, 0x00, 0x18, 0xFC];
+    let status = EngineStatus::decode(&data).unwrap();
+    assert_eq!(status, EngineStatus {
+        speed: 100.0,
+        gear: GearTable::Drive,
+        running: true,
+        temp: 5.0,
+        counter: 3,
+        torque: -1000,
+    });
+    let values = backend_values(&dbc, "EngineStatus", &data);
+    assert_eq!(values, [
+        status.speed,
+        status.gear.raw() as f64,
+        status.running as u8 as f64,
+        status.temp,
+        status.counter as f64,
+        status.torque as f64,
+    ]);
+
+    let data = [0x02, 0x9C, 0xFF, 0x02];
+    let diag = Diag::decode(&data).unwrap();
+    assert_eq!(diag, Diag { page: 2, voltage: None, current: Some(-1.0), status: Some(DiagStatus::Fault) });
+    assert_eq!(backend_values(&dbc, "Diag", &data), [2.0, -1.0, 2.0]);
+}
+
+#[test]
+fn encode_matches_backend() {
+    let dbc = dbc();
+    let status = EngineStatus {
+        speed: 12.34,
+        gear: GearTable::Reverse,
+        running: false,
+        temp: -20.5,
+        counter: 15,
+        torque: 321,
+    };
+    let values = [
+        ("Speed", SignalValue::Physical(12.34)),
+        ("Gear", SignalValue::Label("Reverse".into())),
+        ("Running", SignalValue::Bool(false)),
+        ("Temp", SignalValue::Physical(-20.5)),
+        ("Counter", SignalValue::Exact(15)),
+        ("Torque", SignalValue::Exact(321)),
+    ].into_iter().map(|(n, v)| (n.to_string(), v)).collect();
+    let encoded = status.encode().unwrap();
+    assert_eq!(encoded.to_vec(), encode_message(dbc.message_by_name("EngineStatus").unwrap(), &values).unwrap());
+    assert_eq!(EngineStatus::decode(&encoded).unwrap(), status);
+
+    let encoded = DashData { ratio: -0.25, odometer: 123456.7 }.encode().unwrap();
+    let dash = DashData::decode(&encoded).unwrap();
+    assert_eq!(dash.ratio, -0.25);
+    assert!((dash.odometer - 123456.7).abs() < 1e-6);
+    assert_eq!(backend_values(&dbc, "DashData", &encoded), [dash.ratio, dash.odometer]);
+}
+
+#[test]
+fn multiplexed_round_trip() {
+    let diag = Diag { page: 1, voltage: Some(12.5), current: None, status: None };
+    let encoded = diag.encode().unwrap();
+    assert_eq!(encoded, [0x01, 0xD4, 0x30, 0x00]);
+    assert_eq!(Diag::decode(&encoded).unwrap(), diag);
+}
+
+#[test]
+fn frame_dispatch_and_errors() {
+    let data = Diag { page: 2, voltage: None, current: Some(3.0), status: Some(DiagStatus::Other(7)) }.encode().unwrap();
+    assert!(matches!(Frame::decode(Diag::ID, Diag::EXTENDED, &data), Ok(Frame::Diag(d)) if d.status == Some(DiagStatus::Other(7))));
+    assert_eq!(Frame::decode(Diag::ID, true, &data), Err(CanError::UnknownId { id: Diag::ID, extended: true }));
+    assert_eq!(DashData::decode(&data), Err(CanError::PayloadTooShort { expected: DashData::LENGTH, actual: 4 }));
+    let too_fast = EngineStatus { speed: 700.0, ..Default::default() };
+    assert!(matches!(too_fast.encode(), Err(CanError::OutOfRange { signal: "Speed", .. })));
+}
@@ -0,0 +1,378 @@
+// Generated by canviewer from a CAN database. Do not edit.
+
+/// Error decoding or encoding a generated CAN message
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CanError {
+    /// The payload is shorter than the last byte used by the message's signals
+    PayloadTooShort { expected: usize, actual: usize },
+    /// A physical value is outside of the signal's [min|max] range
+    OutOfRange { signal: &'static str, value: f64, min: f64, max: f64 },
+    /// A raw value does not fit in the bits of the signal
+    Overflow { signal: &'static str, raw: i64 },
+    /// No message is defined for the CAN ID
+    UnknownId { id: u32, extended: bool },
+}
+
+impl std::fmt::Display for CanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CanError::PayloadTooShort { expected, actual } => write!(f, "Payload is {} bytes, expected at least {}", actual, expected),
+            CanError::OutOfRange { signal, value, min, max } => write!(f, "{}: value {} is outside of range [{}|{}]", signal, value, min, max),
+            CanError::Overflow { signal, raw } => write!(f, "{}: raw value {} does not fit in the signal", signal, raw),
+            CanError::UnknownId { id, extended: true } => write!(f, "No message with extended ID 0x{:08X}", id),
+            CanError::UnknownId { id, extended: false } => write!(f, "No message with ID 0x{:03X}", id),
+        }
+    }
+}
+
+impl std::error::Error for CanError {}
+
+/// Reads the raw bits of a signal. Intel (little endian) signals start at their
+/// LSB, Motorola (big endian) signals start at their MSB and continue in
+/// 'sawtooth' order (7..0 of byte n, then 15..8 of byte n+1, etc)
+#[allow(dead_code)]
+fn read_bits(data: &[u8], start_bit: u32, length: u32, big_endian: bool) -> u64 {
+    let mut res = 0u64;
+    let mut pos = start_bit;
+    for i in 0..length {
+        let p = if big_endian { pos } else { start_bit + length - 1 - i };
+        res = (res << 1) | ((data[p as usize / 8] >> (p % 8)) & 0x01) as u64;
+        if big_endian {
+            pos = if pos & 0x07 == 0 { pos + 15 } else { pos - 1 };
+        }
+    }
+    res
+}
+
+/// Writes the lowest `length` bits of `value` at the position of a signal,
+/// with the same bit numbering as [read_bits]
+#[allow(dead_code)]
+fn write_bits(data: &mut [u8], start_bit: u32, length: u32, big_endian: bool, value: u64) {
+    let mut pos = start_bit;
+    for i in 0..length {
+        let p = if big_endian { pos } else { start_bit + length - 1 - i };
+        let bit = ((value >> (length - 1 - i)) & 0x01) as u8;
+        let byte = &mut data[p as usize / 8];
+        *byte = (*byte & !(1 << (p % 8))) | (bit << (p % 8));
+        if big_endian {
+            pos = if pos & 0x07 == 0 { pos + 15 } else { pos - 1 };
+        }
+    }
+}
+
+/// Sign extends the lowest `length` bits of `value`
+#[allow(dead_code)]
+fn sign_extend(value: u64, length: u32) -> i64 {
+    if length >= 64 {
+        return value as i64
+    }
+    let shift = 64 - length;
+    ((value << shift) as i64) >> shift
+}
+
+/// Converts a raw integer into the bit pattern of a signal, checking it fits
+#[allow(dead_code)]
+fn to_bits(raw: i64, length: u32, signed: bool, signal: &'static str) -> Result<u64, CanError> {
+    let fits = match (signed, length >= 64) {
+        (_, true) => signed || raw >= 0,
+        (true, false) => raw >= -(1i64 << (length - 1)) && raw < (1i64 << (length - 1)),
+        (false, false) => raw >= 0 && (raw as u64) < (1u64 << length),
+    };
+    if !fits {
+        return Err(CanError::Overflow { signal, raw })
+    }
+    if length >= 64 {
+        Ok(raw as u64)
+    } else {
+        Ok((raw as u64) & ((1u64 << length) - 1))
+    }
+}
+
+/// Value table GearTable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GearTable {
+    /// 0: Park
+    Park,
+    /// 1: Reverse
+    Reverse,
+    /// 2: Neutral
+    Neutral,
+    /// 3: Drive
+    Drive,
+    /// Raw value without a description
+    Other(i64),
+}
+
+impl GearTable {
+    pub fn from_raw(raw: i64) -> Self {
+        match raw {
+            0 => Self::Park,
+            1 => Self::Reverse,
+            2 => Self::Neutral,
+            3 => Self::Drive,
+            other => Self::Other(other),
+        }
+    }
+
+    pub fn raw(self) -> i64 {
+        match self {
+            Self::Park => 0,
+            Self::Reverse => 1,
+            Self::Neutral => 2,
+            Self::Drive => 3,
+            Self::Other(raw) => raw,
+        }
+    }
+}
+
+impl Default for GearTable {
+    fn default() -> Self {
+        Self::from_raw(0)
+    }
+}
+
+/// Values of Diag.Status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagStatus {
+    /// 0: Ok
+    Ok,
+    /// 1: Warning
+    Warning,
+    /// 2: Fault
+    Fault,
+    /// Raw value without a description
+    Other(i64),
+}
+
+impl DiagStatus {
+    pub fn from_raw(raw: i64) -> Self {
+        match raw {
+            0 => Self::Ok,
+            1 => Self::Warning,
+            2 => Self::Fault,
+            other => Self::Other(other),
+        }
+    }
+
+    pub fn raw(self) -> i64 {
+        match self {
+            Self::Ok => 0,
+            Self::Warning => 1,
+            Self::Fault => 2,
+            Self::Other(raw) => raw,
+        }
+    }
+}
+
+impl Default for DiagStatus {
+    fn default() -> Self {
+        Self::from_raw(0)
+    }
+}
+
+/// EngineStatus (0x0100)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct EngineStatus {
+    /// Speed [km/h], range 0 to 655.35
+    pub speed: f64,
+    /// Gear, range 0 to 3
+    pub gear: GearTable,
+    /// Running, range 0 to 1
+    pub running: bool,
+    /// Temp [degC], range -100 to 200
+    pub temp: f64,
+    /// Counter, range 0 to 15
+    pub counter: u8,
+    /// Torque [Nm], range -1000 to 1000
+    pub torque: i16,
+}
+
+impl EngineStatus {
+    pub const ID: u32 = 0x100;
+    pub const EXTENDED: bool = false;
+    pub const LENGTH: usize = 8;
+
+    /// Decodes the message from a frame payload
+    pub fn decode(data: &[u8]) -> Result<Self, CanError> {
+        if data.len() < 8 {
+            return Err(CanError::PayloadTooShort { expected: 8, actual: data.len() })
+        }
+        Ok(Self {
+            speed: read_bits(data, 0, 16, false) as f64 * 0.01,
+            gear: GearTable::from_raw(read_bits(data, 16, 4, false) as i64),
+            running: read_bits(data, 20, 1, false) != 0,
+            temp: sign_extend(read_bits(data, 31, 12, true), 12) as f64 * 0.5 + -40.0,
+            counter: read_bits(data, 35, 4, true) as u8,
+            torque: sign_extend(read_bits(data, 48, 16, false), 16) as i16,
+        })
+    }
+
+    /// Encodes the message into a frame payload. Multiplexed signals which are
+    /// `None` are left as 0, the multiplexor values are not checked.
+    pub fn encode(&self) -> Result<[u8; 8], CanError> {
+        let mut data = [0u8; 8];
+        {
+            let value = self.speed;
+            if !(0.0..=655.35).contains(&value) {
+                return Err(CanError::OutOfRange { signal: "Speed", value, min: 0.0, max: 655.35 })
+            }
+            write_bits(&mut data, 0, 16, false, to_bits((value / 0.01).round() as i64, 16, false, "Speed")?);
+        }
+        {
+            let value = self.gear;
+            write_bits(&mut data, 16, 4, false, to_bits(value.raw(), 4, false, "Gear")?);
+        }
+        {
+            let value = self.running;
+            write_bits(&mut data, 20, 1, false, value as u64);
+        }
+        {
+            let value = self.temp;
+            if !(-100.0..=200.0).contains(&value) {
+                return Err(CanError::OutOfRange { signal: "Temp", value, min: -100.0, max: 200.0 })
+            }
+            write_bits(&mut data, 31, 12, true, to_bits(((value - -40.0) / 0.5).round() as i64, 12, true, "Temp")?);
+        }
+        {
+            let value = self.counter;
+            if !(0.0..=15.0).contains(&(value as f64)) {
+                return Err(CanError::OutOfRange { signal: "Counter", value: (value as f64), min: 0.0, max: 15.0 })
+            }
+            write_bits(&mut data, 35, 4, true, to_bits(value as i64, 4, false, "Counter")?);
+        }
+        {
+            let value = self.torque;
+            if !(-1000.0..=1000.0).contains(&(value as f64)) {
+                return Err(CanError::OutOfRange { signal: "Torque", value: (value as f64), min: -1000.0, max: 1000.0 })
+            }
+            write_bits(&mut data, 48, 16, false, value as u64);
+        }
+        Ok(data)
+    }
+}
+
+/// Diag (0x0200)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Diag {
+    /// Page, range 0 to 255
+    pub page: u8,
+    /// Voltage [V], range 0 to 65.535, present when Page = 1
+    pub voltage: Option<f64>,
+    /// Current [A], range -327.68 to 327.67, present when Page = 2
+    pub current: Option<f64>,
+    /// Status, range 0 to 255, present when Page = 2
+    pub status: Option<DiagStatus>,
+}
+
+impl Diag {
+    pub const ID: u32 = 0x200;
+    pub const EXTENDED: bool = false;
+    pub const LENGTH: usize = 4;
+
+    /// Decodes the message from a frame payload
+    pub fn decode(data: &[u8]) -> Result<Self, CanError> {
+        if data.len() < 4 {
+            return Err(CanError::PayloadTooShort { expected: 4, actual: data.len() })
+        }
+        let mux_page = read_bits(data, 0, 8, false);
+        Ok(Self {
+            page: read_bits(data, 0, 8, false) as u8,
+            voltage: if mux_page == 1 { Some(read_bits(data, 8, 16, false) as f64 * 0.001) } else { None },
+            current: if mux_page == 2 { Some(sign_extend(read_bits(data, 8, 16, false), 16) as f64 * 0.01) } else { None },
+            status: if mux_page == 2 { Some(DiagStatus::from_raw(read_bits(data, 24, 8, false) as i64)) } else { None },
+        })
+    }
+
+    /// Encodes the message into a frame payload. Multiplexed signals which are
+    /// `None` are left as 0, the multiplexor values are not checked.
+    pub fn encode(&self) -> Result<[u8; 4], CanError> {
+        let mut data = [0u8; 4];
+        {
+            let value = self.page;
+            if !(0.0..=255.0).contains(&(value as f64)) {
+                return Err(CanError::OutOfRange { signal: "Page", value: (value as f64), min: 0.0, max: 255.0 })
+            }
+            write_bits(&mut data, 0, 8, false, value as u64);
+        }
+        if let Some(value) = self.voltage {
+            if !(0.0..=65.535).contains(&value) {
+                return Err(CanError::OutOfRange { signal: "Voltage", value, min: 0.0, max: 65.535 })
+            }
+            write_bits(&mut data, 8, 16, false, to_bits((value / 0.001).round() as i64, 16, false, "Voltage")?);
+        }
+        if let Some(value) = self.current {
+            if !(-327.68..=327.67).contains(&value) {
+                return Err(CanError::OutOfRange { signal: "Current", value, min: -327.68, max: 327.67 })
+            }
+            write_bits(&mut data, 8, 16, false, to_bits((value / 0.01).round() as i64, 16, true, "Current")?);
+        }
+        if let Some(value) = self.status {
+            write_bits(&mut data, 24, 8, false, to_bits(value.raw(), 8, false, "Status")?);
+        }
+        Ok(data)
+    }
+}
+
+/// DashData (0x18FEF1FE EXT)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DashData {
+    /// Ratio
+    pub ratio: f64,
+    /// Odometer [km], range 0 to 429496729.5
+    pub odometer: f64,
+}
+
+impl DashData {
+    pub const ID: u32 = 0x18FEF1FE;
+    pub const EXTENDED: bool = true;
+    pub const LENGTH: usize = 8;
+
+    /// Decodes the message from a frame payload
+    pub fn decode(data: &[u8]) -> Result<Self, CanError> {
+        if data.len() < 8 {
+            return Err(CanError::PayloadTooShort { expected: 8, actual: data.len() })
+        }
+        Ok(Self {
+            ratio: f32::from_bits(read_bits(data, 0, 32, false) as u32) as f64,
+            odometer: read_bits(data, 39, 32, true) as f64 * 0.1,
+        })
+    }
+
+    /// Encodes the message into a frame payload. Multiplexed signals which are
+    /// `None` are left as 0, the multiplexor values are not checked.
+    pub fn encode(&self) -> Result<[u8; 8], CanError> {
+        let mut data = [0u8; 8];
+        {
+            let value = self.ratio;
+            write_bits(&mut data, 0, 32, false, (value as f32).to_bits() as u64);
+        }
+        {
+            let value = self.odometer;
+            if !(0.0..=429496729.5).contains(&value) {
+                return Err(CanError::OutOfRange { signal: "Odometer", value, min: 0.0, max: 429496729.5 })
+            }
+            write_bits(&mut data, 39, 32, true, to_bits((value / 0.1).round() as i64, 32, false, "Odometer")?);
+        }
+        Ok(data)
+    }
+}
+
+/// Any message of the database
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Frame {
+    EngineStatus(EngineStatus),
+    Diag(Diag),
+    DashData(DashData),
+}
+
+impl Frame {
+    /// Decodes a frame by its CAN ID
+    pub fn decode(id: u32, extended: bool, data: &[u8]) -> Result<Self, CanError> {
+        match (id, extended) {
+            (0x100, false) => EngineStatus::decode(data).map(Self::EngineStatus),
+            (0x200, false) => Diag::decode(data).map(Self::Diag),
+            (0x18FEF1FE, true) => DashData::decode(data).map(Self::DashData),
+            _ => Err(CanError::UnknownId { id, extended }),
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use backend::{
+    load_database_from_file, load_kcd_from_bytes, load_sym_from_bytes, tree_dbc::{ByteOrder, CanId, Message, Multiplexing, MuxCondition, SignalType},
+    CanViewError, DatabaseFormat, TreeDbc,
+};
+
+fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+fn message<'a>(dbc: &'a TreeDbc, name: &str) -> &'a Message {
+    dbc.message_by_name(name).unwrap()
+}
+
+/// Name, start bit, length, byte order and signedness of each signal
+fn layout(msg: &Message) -> Vec<(&str, u64, u64, ByteOrder, bool)> {
+    msg.signals.iter().map(|s| (s.name.as_str(), s.start_bit, s.length_bits, s.order, s.signed)).collect()
+}
+
+/// Both fixtures describe the same two messages
+fn check_example(dbc: &TreeDbc) {
+    let status = message(dbc, "EngineStatus");
+    assert_eq!((status.id, status.length_bytes), (CanId::new(0x100, false), 8));
+    assert_eq!(layout(status), [
+        ("Speed", 0, 16, ByteOrder::LittleEndian, false),
+        ("Gear", 16, 4, ByteOrder::LittleEndian, false),
+        ("Temp", 31, 12, ByteOrder::BigEndian, true),
+    ]);
+    assert_eq!(status.signals[0].signal_type, SignalType::Linear { multi: 0.5, offset: -10.0 });
+    assert_eq!(status.signals[0].unit, "km/h");
+    assert_eq!(status.signals[0].comment.as_deref(), Some("Vehicle speed"));
+    assert_eq!(status.signals[2].signal_type, SignalType::Linear { multi: 0.5, offset: -40.0 });
+
+    let diag = message(dbc, "Diag");
+    assert_eq!((diag.id, diag.length_bytes), (CanId::new(0x18FF0001, true), 3));
+    assert_eq!(layout(diag), [
+        ("Page", 0, 8, ByteOrder::LittleEndian, false),
+        ("Volt", 8, 16, ByteOrder::LittleEndian, false),
+        ("Current", 8, 16, ByteOrder::LittleEndian, true),
+    ]);
+    assert_eq!(diag.signals[0].multiplexing, Multiplexing::Multiplexor);
+    for (signal, page) in diag.signals[1..].iter().zip([1, 2]) {
+        assert_eq!(signal.multiplexing, Multiplexing::Multiplexed(MuxCondition { multiplexor: "Page".into(), ranges: vec![(page, page)] }));
+    }
+}
+
+#[test]
+fn format_from_extension() {
+    assert_eq!(DatabaseFormat::from_path("car.dbc"), DatabaseFormat::Dbc);
+    assert_eq!(DatabaseFormat::from_path("dir.kcd/car.DBC"), DatabaseFormat::Dbc);
+    assert_eq!(DatabaseFormat::from_path("car.ARXML"), DatabaseFormat::Arxml);
+    assert_eq!(DatabaseFormat::from_path("/tmp/car.kcd"), DatabaseFormat::Kcd);
+    assert_eq!(DatabaseFormat::from_path("car.Sym"), DatabaseFormat::Sym);
+    assert_eq!(DatabaseFormat::from_path("car"), DatabaseFormat::Dbc);
+}
+
+#[test]
+fn load_kcd_file() {
+    let dbc = load_database_from_file(&fixture("example.kcd"), Some("Powertrain")).unwrap();
+    check_example(&dbc);
+    let status = message(&dbc, "EngineStatus");
+    assert_eq!(status.comment.as_deref(), Some("Engine state"));
+    assert_eq!(status.signals[0].receivers, ["Dash"]);
+    assert_eq!(status.signals[1].signal_type.entries(), Some(&vec![
+        (-1, "Invalid".into()),
+        (0, "Park".into()),
+        (1, "Drive".into()),
+        (2, "Drive".into()),
+    ]));
+    let senders: Vec<(&str, usize)> = dbc.ecus.iter().map(|e| (e.name.as_str(), e.messages.len())).collect();
+    assert_eq!(senders, [("Engine", 1), ("Dash", 1)]);
+}
+
+#[test]
+fn kcd_needs_bus_name() {
+    // The fixture has two buses
+    match load_database_from_file(&fixture("example.kcd"), None) {
+        Err(CanViewError::KcdError(e)) => assert!(e.contains("example.kcd") && e.contains("Powertrain, Body"), "{}", e),
+        other => panic!("unexpected result {:?}", other.map(|_| ())),
+    }
+    assert!(load_kcd_from_bytes(b"<NetworkDefinition/>", None).is_err());
+}
+
+#[test]
+fn load_sym_file() {
+    let dbc = load_database_from_file(&fixture("example.sym"), None).unwrap();
+    check_example(&dbc);
+    assert_eq!(message(&dbc, "EngineStatus").signals[1].signal_type.entries(), Some(&vec![
+        (0, "Park".into()),
+        (1, "Drive".into()),
+        (2, "Drive".into()),
+        (15, "Invalid".into()),
+    ]));
+}
+
+#[test]
+fn sym_errors_name_file_and_line() {
+    let text = std::fs::read_to_string(fixture("example.sym")).unwrap().replace("/e:Gear", "/e:Missing");
+    let path = std::env::temp_dir().join(format!("canviewer-missing-enum-{}.sym", std::process::id()));
+    std::fs::write(&path, text).unwrap();
+    let res = load_database_from_file(path.to_str().unwrap(), None);
+    std::fs::remove_file(&path).unwrap();
+    match res {
+        Err(CanViewError::SymError(e)) => assert!(e.ends_with("line 18: Signal Gear uses undefined enum Missing"), "{}", e),
+        other => panic!("unexpected result {:?}", other.map(|_| ())),
+    }
+}
+
+/// KCD bus with one message holding the given signal
+fn kcd_signal(signal: &str) -> String {
+    format!(r#"<NetworkDefinition xmlns="http://kayak.2codeornot2code.org/1.0">
+  <Bus name="Body">
+    <Message id="0x200" name="Doors" length="1">
+      {}
+    </Message>
+  </Bus>
+</NetworkDefinition>"#, signal)
+}
+
+#[test]
+fn one_bit_signals_keep_labels() {
+    let kcd = kcd_signal(r#"<Signal name="Lock" offset="0"><LabelSet><Label name="Unlocked" value="0"/><Label name="Locked" value="1"/></LabelSet></Signal>
+      <Signal name="Open" offset="1"/>"#);
+    let dbc = load_kcd_from_bytes(kcd.as_bytes(), None).unwrap();
+    let doors = message(&dbc, "Doors");
+    let entries = vec![(0, "Unlocked".to_string()), (1, "Locked".to_string())];
+    assert_eq!(doors.signals[0].signal_type, SignalType::Enum { entries: entries.clone(), multi: 1.0, offset: 0.0 });
+    assert_eq!(doors.signals[1].signal_type, SignalType::Bool);
+
+    let sym = "FormatVersion=6.0 // Do not edit this line!\n{ENUMS}\nenum Lock(0=\"Unlocked\", 1=\"Locked\")\n\
+        {SENDRECEIVE}\n[Doors]\nID=200h\nDLC=1\nVar=Lock bit 0,1 /e:Lock\nVar=Open bit 1,1\n";
+    let dbc = load_sym_from_bytes(sym.as_bytes()).unwrap();
+    let doors = message(&dbc, "Doors");
+    assert_eq!(doors.signals[0].signal_type, SignalType::Enum { entries, multi: 1.0, offset: 0.0 });
+    assert_eq!(doors.signals[1].signal_type, SignalType::Bool);
+}
+
+#[test]
+fn kcd_label_groups() {
+    let kcd = kcd_signal(r#"<Signal name="State" offset="0" length="8"><LabelSet><LabelGroup name="Reserved" from="16" to="255"/></LabelSet></Signal>"#);
+    let dbc = load_kcd_from_bytes(kcd.as_bytes(), None).unwrap();
+    let entries = message(&dbc, "Doors").signals[0].signal_type.entries().unwrap();
+    assert_eq!((entries.len(), entries[0].0, entries[239].0), (240, 16, 255));
+
+    // Every value of a group gets its own entry, so huge groups are rejected rather than cut short
+    let kcd = kcd_signal(r#"<Signal name="State" offset="0" length="32"><LabelSet><LabelGroup name="Reserved" from="0" to="65535"/></LabelSet></Signal>"#);
+    match load_kcd_from_bytes(kcd.as_bytes(), None) {
+        Err(CanViewError::KcdError(e)) => assert_eq!(e, "Label group Reserved of signal State covers 65536 values, at most 1024 are supported"),
+        other => panic!("unexpected result {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn sym_multiplexor_pages() {
+    let sym = "FormatVersion=6.0 // Do not edit this line!
+{SENDRECEIVE}
+[Info]
+ID=300h
+DLC=4
+Mux=Page 0,8 1
+Var=Status unsigned 8,8
+Var=Counter unsigned 24,8
+
+[Info]
+ID=300h
+DLC=4
+Mux=Page 0,8 2
+Var=Voltage unsigned 8,16 /u:V /f:0.01
+Var=Counter unsigned 24,8
+
+[Info]
+ID=300h
+DLC=4
+Mux=Page 0,8 3
+Var=Status unsigned 8,8
+Var=Counter unsigned 24,8
+";
+    let dbc = load_sym_from_bytes(sym.as_bytes()).unwrap();
+    assert_eq!(dbc.messages().count(), 1);
+    let info = message(&dbc, "Info");
+    let mux = |ranges: Vec<(u64, u64)>| Multiplexing::Multiplexed(MuxCondition { multiplexor: "Page".into(), ranges });
+    let signals: Vec<(&str, &Multiplexing)> = info.signals.iter().map(|s| (s.name.as_str(), &s.multiplexing)).collect();
+    assert_eq!(signals, [
+        ("Page", &Multiplexing::Multiplexor),
+        ("Status", &mux(vec![(1, 1), (3, 3)])),
+        ("Counter", &mux(vec![(1, 1), (2, 2), (3, 3)])),
+        ("Voltage", &mux(vec![(2, 2)])),
+    ]);
+}
+
+#[test]
+fn missing_file() {
+    assert!(matches!(load_database_from_file(&fixture("missing.kcd"), None), Err(CanViewError::IoError { .. })));
+}
@@ -0,0 +1,221 @@
+//! Raw SocketCAN socket which receives both classic CAN and CAN FD frames.
+//!
+//! Error frames are dropped. Remote (RTR) frames are kept, with an empty
+//! payload and the requested length in [Frame::rtr_len].
+//!
+//! This used to go through the SocketCAN channel of `ecu_diagnostics`, but that
+//! only reads classic 8 byte frames, with no way to enable `CAN_RAW_FD_FRAMES`
+//! on the socket. The few socket calls needed are made through `libc` instead,
+//! and all frame decoding is done in safe code by [parse_frame].
+
+use std::{ffi::CString, io, mem, os::fd::{AsRawFd, FromRawFd, OwnedFd}};
+
+use backend::tree_dbc::CanId;
+
+/// Maximum payload length of a CAN FD frame
+pub const CANFD_MAX_LEN: usize = 64;
+
+/// How long [CanSocket::read_frame] waits for a frame
+const READ_TIMEOUT_US: libc::suseconds_t = 100_000;
+
+/// Frame received from the bus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    pub id: CanId,
+    len: u8,
+    data: [u8; CANFD_MAX_LEN],
+    /// Frame is a CAN FD frame
+    pub fd: bool,
+    /// Bit rate switch, the data phase was sent at the faster data bit rate (CAN FD only)
+    pub brs: bool,
+    /// Error state indicator, the sender is error passive (CAN FD only)
+    pub esi: bool,
+    /// Remote transmission request, asking the sender of the ID for a frame of this length
+    pub rtr_len: Option<u8>,
+}
+
+impl Frame {
+    pub fn data(&self) -> &[u8] {
+        &self.data[..self.len as usize]
+    }
+
+    /// Short description of the frame format, E.g. "FD BRS" or "CAN RTR"
+    pub fn format(&self) -> String {
+        let mut res = String::from(if self.fd { "FD" } else { "CAN" });
+        if self.rtr_len.is_some() {
+            res.push_str(" RTR");
+        }
+        if self.brs {
+            res.push_str(" BRS");
+        }
+        if self.esi {
+            res.push_str(" ESI");
+        }
+        res
+    }
+}
+
+/// Parses a frame as read from a raw CAN socket, which is a `struct can_frame`
+/// (16 bytes) or a `struct canfd_frame` (72 bytes). Returns `None` for error frames
+pub fn parse_frame(buf: &[u8]) -> io::Result<Option<Frame>> {
+    let fd = match buf.len() {
+        libc::CAN_MTU => false,
+        libc::CANFD_MTU => true,
+        other => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Unexpected CAN frame size {}", other))),
+    };
+    let can_id = u32::from_ne_bytes([buf[0], buf[1], buf[2], buf[3]]);
+    if can_id & libc::CAN_ERR_FLAG != 0 {
+        return Ok(None)
+    }
+    let extended = can_id & libc::CAN_EFF_FLAG != 0;
+    let id = if extended { can_id & libc::CAN_EFF_MASK } else { can_id & libc::CAN_SFF_MASK };
+    let len = (buf[4] as usize).min(if fd { CANFD_MAX_LEN } else { 8 });
+    // CAN FD has no remote frames
+    let rtr_len = (!fd && can_id & libc::CAN_RTR_FLAG != 0).then_some(len as u8);
+    let len = if rtr_len.is_some() { 0 } else { len };
+    let flags = if fd { buf[5] as libc::c_int } else { 0 };
+    let mut data = [0u8; CANFD_MAX_LEN];
+    data[..len].copy_from_slice(&buf[8..8 + len]);
+    Ok(Some(Frame {
+        id: CanId::new(id, extended),
+        len: len as u8,
+        data,
+        fd,
+        brs: flags & libc::CANFD_BRS != 0,
+        esi: flags & libc::CANFD_ESI != 0,
+        rtr_len,
+    }))
+}
+
+pub struct CanSocket {
+    fd: OwnedFd,
+}
+
+fn check(ret: libc::c_int) -> io::Result<()> {
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+fn set_option<T>(fd: libc::c_int, level: libc::c_int, name: libc::c_int, value: &T) -> io::Result<()> {
+    // SAFETY: `value` is a valid reference for the duration of the call, and the
+    // length passed is exactly the size of `T`, so the kernel only reads from it
+    check(unsafe {
+        libc::setsockopt(fd, level, name, value as *const T as *const libc::c_void, mem::size_of::<T>() as libc::socklen_t)
+    })
+}
+
+impl CanSocket {
+    /// Opens a raw socket on the interface, with CAN FD frames enabled.
+    /// Interfaces which are not CAN FD capable just deliver classic frames
+    pub fn open(iface: &str) -> io::Result<Self> {
+        let name = CString::new(iface).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Invalid interface name"))?;
+        // SAFETY: `name` is a NUL terminated string which outlives the call
+        let if_index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if if_index == 0 {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("No CAN interface named {}", iface)))
+        }
+        // SAFETY: No pointers are passed, the result is checked below
+        let raw_fd = unsafe { libc::socket(libc::PF_CAN, libc::SOCK_RAW, libc::CAN_RAW) };
+        check(raw_fd)?;
+        // SAFETY: `raw_fd` is a newly opened socket which nothing else owns, so
+        // `OwnedFd` can take ownership of it and close it on drop
+        let socket = Self { fd: unsafe { OwnedFd::from_raw_fd(raw_fd) } };
+
+        // Kernels without CAN FD support reject this, and keep delivering classic frames
+        let _ = set_option(raw_fd, libc::SOL_CAN_RAW, libc::CAN_RAW_FD_FRAMES, &(1 as libc::c_int));
+        let timeout = libc::timeval { tv_sec: 0, tv_usec: READ_TIMEOUT_US };
+        set_option(raw_fd, libc::SOL_SOCKET, libc::SO_RCVTIMEO, &timeout)?;
+
+        // SAFETY: `sockaddr_can` is a plain C struct of integers, for which all
+        // zero bytes is a valid value
+        let mut addr: libc::sockaddr_can = unsafe { mem::zeroed() };
+        addr.can_family = libc::AF_CAN as libc::sa_family_t;
+        addr.can_ifindex = if_index as libc::c_int;
+        // SAFETY: `addr` is a fully initialised `sockaddr_can` which outlives the
+        // call, and the length passed is its size
+        check(unsafe {
+            libc::bind(
+                raw_fd,
+                &addr as *const libc::sockaddr_can as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_can>() as libc::socklen_t,
+            )
+        })?;
+        Ok(socket)
+    }
+
+    /// Waits for the next frame. Returns `None` if no frame arrived within
+    /// the read timeout, or an error frame was received
+    pub fn read_frame(&self) -> io::Result<Option<Frame>> {
+        // Layout of struct canfd_frame, struct can_frame is the first 16 bytes of it
+        let mut buf = [0u8; libc::CANFD_MTU];
+        // SAFETY: The kernel writes at most `buf.len()` bytes into `buf`, which is
+        // borrowed mutably for the duration of the call. The fd stays open as
+        // long as `self` does
+        let n = unsafe { libc::read(self.fd.as_raw_fd(), buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+        if n < 0 {
+            let e = io::Error::last_os_error();
+            return match e.kind() {
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted => Ok(None),
+                _ => Err(e),
+            }
+        }
+        parse_frame(&buf[..n as usize])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds the bytes of a `struct can_frame` or `struct canfd_frame`
+    fn raw_frame(can_id: u32, len: u8, flags: u8, data: &[u8], fd: bool) -> Vec<u8> {
+        let mut buf = vec![0u8; if fd { libc::CANFD_MTU } else { libc::CAN_MTU }];
+        buf[..4].copy_from_slice(&can_id.to_ne_bytes());
+        buf[4] = len;
+        buf[5] = flags;
+        buf[8..8 + data.len()].copy_from_slice(data);
+        buf
+    }
+
+    #[test]
+    fn classic_frames() {
+        let f = parse_frame(&raw_frame(0x123, 3, 0, &[1, 2, 3], false)).unwrap().unwrap();
+        assert_eq!((f.id, f.data(), f.format().as_str()), (CanId::new(0x123, false), &[1u8, 2, 3][..], "CAN"));
+
+        let f = parse_frame(&raw_frame(0x18FEF100 | libc::CAN_EFF_FLAG, 8, 0, &[0xFF; 8], false)).unwrap().unwrap();
+        assert_eq!((f.id, f.data().len()), (CanId::new(0x18FEF100, true), 8));
+
+        // DLC 9-15 still means 8 bytes on a classic frame
+        let f = parse_frame(&raw_frame(0x100, 15, 0, &[0xAA; 8], false)).unwrap().unwrap();
+        assert_eq!(f.data(), &[0xAA; 8]);
+    }
+
+    #[test]
+    fn fd_frames() {
+        let payload: Vec<u8> = (0..64).collect();
+        let f = parse_frame(&raw_frame(0x456, 64, libc::CANFD_BRS as u8, &payload, true)).unwrap().unwrap();
+        assert_eq!((f.data(), f.format().as_str()), (payload.as_slice(), "FD BRS"));
+
+        let f = parse_frame(&raw_frame(0x456, 12, (libc::CANFD_BRS | libc::CANFD_ESI) as u8, &payload[..12], true)).unwrap().unwrap();
+        assert_eq!((f.data().len(), f.format().as_str()), (12, "FD BRS ESI"));
+    }
+
+    #[test]
+    fn remote_frames() {
+        let f = parse_frame(&raw_frame(0x7DF | libc::CAN_RTR_FLAG, 8, 0, &[], false)).unwrap().unwrap();
+        assert_eq!((f.id, f.rtr_len, f.data().len()), (CanId::new(0x7DF, false), Some(8), 0));
+        assert_eq!(f.format(), "CAN RTR");
+        let f = parse_frame(&raw_frame(0x1234 | libc::CAN_EFF_FLAG | libc::CAN_RTR_FLAG, 2, 0, &[], false)).unwrap().unwrap();
+        assert_eq!((f.id, f.rtr_len), (CanId::new(0x1234, true), Some(2)));
+    }
+
+    #[test]
+    fn error_and_invalid_frames() {
+        assert_eq!(parse_frame(&raw_frame(libc::CAN_ERR_FLAG | 0x4, 8, 0, &[], false)).unwrap(), None);
+        assert!(parse_frame(&[0u8; 10]).is_err());
+        assert!(parse_frame(&[]).is_err());
+    }
+}
@@ -1,30 +1,71 @@
-use std::{sync::{atomic::{AtomicBool, Ordering}, Arc}, fs::File, io::Read, ops::Not};
+use std::{sync::{atomic::{AtomicBool, Ordering}, Arc}, ops::Not};
 
-use backend::{load_dbc_from_bytes, parse_signal, tree_dbc::{Signal, SignalType}};
-use ecu_diagnostics::{hardware::{Hardware, HardwareScanner, socketcan::{SocketCanScanner}}, channel::{CanFrame, Packet}};
+use backend::{lint_dbc, load_database_from_file, merge_dbcs, CanViewError, decode_message, DbcDecoder, J1939Id, ParsedSignal, TreeDbc, SignalParseResult, SignalStatus, search_dbc, DbcConflict, Diagnostic, SearchResult, SearchTarget, Severity, tree_dbc::{AttributeObject, CanId, Message, MessageHandle, SignalHandle, Signal, SignalType, Multiplexing, ValueType}};
 use eframe::{egui::*, epaint::{mutex::RwLock, ahash::{HashMap, HashMapExt}}};
 use egui_extras::*;
 use std::fmt::Write;
 
+use crate::can_socket::{CanSocket, Frame};
+
 pub struct CanViewer {
-    dbc: Option<backend::TreeDbc>,
+    /// All loaded DBC files merged together
+    dbc: Option<TreeDbc>,
+    /// Decoders compiled from `dbc`
+    decoder: DbcDecoder,
     dbc_name: String,
+    /// File each message was loaded from
+    dbc_sources: std::collections::HashMap<MessageHandle, String>,
+    /// CAN IDs defined differently by two of the loaded files
+    conflicts: Vec<DbcConflict>,
+    multiple_dbcs: bool,
     iface_name: String,
     is_reading: Arc<AtomicBool>,
-    load_error: Option<String>,
-    frames: Arc<RwLock<HashMap<u32, CanFrame>>>,
-    frames_previous: HashMap<u32, CanFrame>,
-    open_frames: Vec<(usize, usize)>,
-    described_signal: Option<Signal>
+    load_errors: Vec<CanViewError>,
+    frames: Arc<RwLock<HashMap<CanId, Frame>>>,
+    frames_previous: HashMap<CanId, Frame>,
+    open_frames: Vec<MessageHandle>,
+    described_signal: Option<Signal>,
+    described_message: Option<Message>,
+    lint: Vec<Diagnostic>,
+    show_lint: bool,
+    /// Show the raw integer of signals in frame windows, instead of the physical value
+    show_raw: bool,
+    search_query: String,
+    search_results: Vec<SearchResult>,
+    /// Message to expand and scroll to in the DBC Explorer on the next frame
+    explorer_jump: Option<MessageHandle>,
+}
+
+/// Maximum number of search results shown under the search box
+const MAX_SEARCH_RESULTS: usize = 50;
+
+/// Payload bytes shown per line. Longer CAN FD payloads wrap onto more lines
+const BYTES_PER_LINE: usize = 8;
+
+/// Row of a frame window's signal table
+enum FrameRow<'a> {
+    /// Heading of a signal group
+    Group(&'a str),
+    Signal(&'a Signal, &'a SignalParseResult<ParsedSignal>),
+}
+
+/// Colour used to highlight an invalid signal value
+fn status_color(status: SignalStatus) -> Option<Color32> {
+    match status {
+        SignalStatus::Valid => None,
+        SignalStatus::BelowMin | SignalStatus::AboveMax => Some(Color32::from_rgb(230, 140, 0)),
+        SignalStatus::UndefinedEnum | SignalStatus::ErrorValue => Some(Color32::RED),
+    }
+}
+
+fn file_name(path: &str) -> &str {
+    std::path::Path::new(path).file_name().and_then(|n| n.to_str()).unwrap_or(path)
 }
 
 
 impl CanViewer {
-    pub fn new(iface_name: String, dbc_path: Option<String>) -> ecu_diagnostics::hardware::HardwareResult<Self> {
-        let scanner = SocketCanScanner::new();
-        let can_hw = scanner.open_device_by_name(&iface_name)?;
-        let mut can_channel = Hardware::create_can_channel(can_hw)?;
-        can_channel.open().unwrap();
+    pub fn new(iface_name: String, dbc_paths: Vec<String>, network: Option<String>) -> std::io::Result<Self> {
+        let socket = CanSocket::open(&iface_name)?;
         let is_reading = Arc::new(AtomicBool::new(true));
         let frame_list = Arc::new(RwLock::new(HashMap::new()));
         let is_reading_c = is_reading.clone();
@@ -32,58 +73,171 @@ impl CanViewer {
 
         std::thread::spawn(move|| {
             loop {
-                if is_reading_c.load(Ordering::Relaxed) {
-                    match can_channel.read_packets(100, 10) {
-                        Ok(res) => {
-                            let mut lock = frame_list_c.write();
-                            for f in res {
-                                lock.insert(f.get_address(), f);
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!("Read error: {}", e);
+                // Frames are still read while paused, so that old frames are not shown on resume
+                match socket.read_frame() {
+                    Ok(Some(f)) => {
+                        if is_reading_c.load(Ordering::Relaxed) {
+                            frame_list_c.write().insert(f.id, f);
                         }
                     }
-                } else {
-                    can_channel.clear_rx_buffer();
+                    Ok(None) => {}
+                    Err(e) => {
+                        eprintln!("Read error: {}", e);
+                        std::thread::sleep(std::time::Duration::from_millis(10));
+                    }
                 }
-                std::thread::sleep(std::time::Duration::from_millis(10));
             }
         });
 
-        let mut dbc = None;
-        let mut load_error = None;
-        if let Some(path) = &dbc_path {
-            if let Ok(dbc_bytes) = File::open(path)
-                .and_then(|mut f| {
-                    let mut buf = Vec::new();
-                    f.read_to_end(&mut buf)?;
-                    Ok(buf)
-            }) {
-                match load_dbc_from_bytes(&dbc_bytes) {
-                    Ok(d) => {
-                        dbc = Some(d)
-                    },
-                    Err(e) => {
-                        load_error = Some(e.to_string())
-                    }
+        let mut loaded = Vec::new();
+        let mut load_errors = Vec::new();
+        for path in &dbc_paths {
+            match load_database_from_file(path, network.as_deref()) {
+                Ok(d) => {
+                    loaded.push((path.clone(), d))
+                },
+                Err(e) => {
+                    load_errors.push(e)
                 }
             }
         }
 
+        let multiple_dbcs = loaded.len() > 1;
+        let dbc_name = loaded.iter().map(|(p, _)| p.as_str()).collect::<Vec<_>>().join(", ");
+        let (dbc, dbc_sources, conflicts) = if loaded.is_empty() {
+            (None, Default::default(), Vec::new())
+        } else {
+            let merged = merge_dbcs(loaded);
+            (Some(merged.dbc), merged.sources, merged.conflicts)
+        };
+
+        let lint = dbc.as_ref().map(lint_dbc).unwrap_or_default();
+        let decoder = dbc.as_ref().map(DbcDecoder::new).unwrap_or_default();
+
         Ok(Self {
             dbc,
-            dbc_name: dbc_path.unwrap_or_default(),
+            decoder,
+            dbc_name,
+            dbc_sources,
+            conflicts,
+            multiple_dbcs,
             iface_name,
             is_reading,
-            load_error,
+            load_errors,
             frames: frame_list,
             frames_previous: HashMap::new(),
             open_frames: Vec::new(),
-            described_signal: None
+            described_signal: None,
+            described_message: None,
+            lint,
+            show_lint: false,
+            show_raw: false,
+            search_query: String::new(),
+            search_results: Vec::new(),
+            explorer_jump: None,
         })
 
     }
+
+    /// Shows the payload and decoded signals of a received frame
+    fn show_frame(&self, ui: &mut Ui, dbc: &TreeDbc, msg: &Message, cf: &Frame) {
+        if let Some(len) = cf.rtr_len {
+            ui.label(format!("Remote request for {} bytes", len));
+            return
+        }
+        let data = cf.data();
+        ui.label(format!("{} frame, {} bytes", cf.format(), data.len()));
+        for line in data.chunks(BYTES_PER_LINE) {
+            ui.monospace(format!("{:02X?}", line));
+        }
+        let decoded = self.decoder.decode(msg.id, data).unwrap_or_else(|| decode_message(msg, data));
+        if decoded.length_mismatch() {
+            ui.label(RichText::new(format!("Expected {} bytes, received {}", decoded.expected_len, decoded.payload_len)).color(Color32::RED));
+        }
+        if !decoded.mux_chain.is_empty() {
+            let groups: Vec<String> = decoded.mux_chain.iter().map(|(mux, v)| format!("{} = {}", msg.signals[*mux].name, v)).collect();
+            ui.label(format!("Mux group: {}", groups.join(" -> ")));
+        } else if decoded.multiplexed {
+            ui.label(RichText::new("Cannot read multiplexor").color(Color32::RED));
+        }
+        let mut rows = Vec::new();
+        if msg.signal_groups.is_empty() {
+            rows.extend(decoded.signals.iter().map(|d| FrameRow::Signal(&msg.signals[d.index], &d.value)));
+        } else {
+            let values: std::collections::HashMap<&str, _> = decoded.signals.iter()
+                .map(|d| (msg.signals[d.index].name.as_str(), &d.value))
+                .collect();
+            let signals: Vec<&Signal> = decoded.signals.iter().map(|d| &msg.signals[d.index]).collect();
+            for (group, members) in msg.grouped_signals(&signals) {
+                rows.push(FrameRow::Group(group.map_or("Other signals", |g| g.name.as_str())));
+                rows.extend(members.into_iter().filter_map(|s| values.get(s.name.as_str()).map(|v| FrameRow::Signal(s, v))));
+            }
+        }
+        // Counted from the rows shown, which were already parsed above
+        let invalid = rows.iter().filter(|r| matches!(r, FrameRow::Signal(_, Ok(p)) if !p.is_valid())).count();
+        if invalid > 0 {
+            ui.label(RichText::new(format!("{} invalid value(s)", invalid)).color(Color32::RED));
+        }
+
+        let table = TableBuilder::new(ui)
+            .striped(true)
+            .scroll(true)
+            .clip(false)
+            .cell_layout(Layout::left_to_right(Align::Center).with_cross_align(Align::Center))
+            .column(Size::initial(60.0).at_least(60.0)) // Value name
+            .column(Size::initial(400.0).at_least(500.0)); // Value
+
+        table.header(15.0, |mut header| {
+            header.col(|u| {u.label("Signal name");});
+            header.col(|u| {u.label("Value");});
+        }).body(|body| {
+            body.rows(18.0, rows.len(), |row_id, mut row| {
+                let (signal, parsed) = match &rows[row_id] {
+                    FrameRow::Group(name) => {
+                        row.col(|x| {x.label(RichText::new(*name).strong());});
+                        row.col(|_| {});
+                        return;
+                    },
+                    FrameRow::Signal(s, p) => (*s, p),
+                };
+                row.col(|x| {
+                    let r = x.label(&signal.name);
+                    let hover = match (dbc.spn(signal), &signal.comment) {
+                        (Some(spn), Some(cmt)) => Some(format!("SPN {}\n{}", spn, cmt)),
+                        (Some(spn), None) => Some(format!("SPN {}", spn)),
+                        (None, cmt) => cmt.clone(),
+                    };
+                    if let Some(h) = hover {
+                        r.on_hover_text(h);
+                    }
+                });
+                row.col(|x| {
+                    match parsed {
+                        Ok(s) => {
+                            let text = if self.show_raw {
+                                s.raw.to_string()
+                            } else {
+                                s.to_string()
+                            };
+                            match status_color(s.status) {
+                                Some(c) => {
+                                    x.label(RichText::new(text).color(c))
+                                        .on_hover_text(format!("{} (raw {}, range {} to {})", s.status, s.raw, signal.min, signal.max));
+                                },
+                                None => {
+                                    x.label(text);
+                                }
+                            }
+                        },
+                        Err(e) => {
+                            x.label(RichText::new(format!("{:?}", e)).color(Color32::RED));
+                        }
+                    }
+                });
+
+            })
+        });
+    }
 }
 
 impl eframe::App for CanViewer {
@@ -97,29 +251,110 @@ impl eframe::App for CanViewer {
                 let new_state = self.is_reading.load(Ordering::Relaxed).not();
                 self.is_reading.store(new_state, Ordering::Relaxed);
             }
+            ui.checkbox(&mut self.show_raw, "Show raw signal values");
 
             ui.heading("DBC Explorer");
             ui.separator();
             if let Some(dbc) = &self.dbc {
+                ui.checkbox(&mut self.show_lint, format!("Show DBC lint ({} problems)", self.lint.len()));
+                if !self.conflicts.is_empty() {
+                    ui.collapsing(RichText::new(format!("{} conflicting definitions", self.conflicts.len())).color(Color32::RED), |conflict_ui| {
+                        for c in &self.conflicts {
+                            conflict_ui.label(c.to_string());
+                        }
+                    });
+                }
+                ui.horizontal(|row| {
+                    row.label("Search");
+                    if row.text_edit_singleline(&mut self.search_query).changed() {
+                        self.search_results = search_dbc(dbc, &self.search_query, MAX_SEARCH_RESULTS);
+                    }
+                });
+                if !self.search_results.is_empty() {
+                    ScrollArea::vertical().id_source("SearchResults").max_height(200.0).show(ui, |results_ui| {
+                        for result in &self.search_results {
+                            let handle = result.target.message();
+                            let msg = match dbc.message(handle) {
+                                Some(m) => m,
+                                None => continue
+                            };
+                            let signal = match &result.target {
+                                SearchTarget::Signal(h) => dbc.signal(h),
+                                SearchTarget::Message(_) => None,
+                            };
+                            let title = match signal {
+                                Some(s) => format!("{}.{}", msg.name, s.name),
+                                None => format!("{} ({})", msg.name, msg.id),
+                            };
+                            results_ui.horizontal(|row| {
+                                let r = row.selectable_label(false, title)
+                                    .on_hover_text(format!("Matched {}: {}", result.field, result.matched));
+                                if r.clicked() {
+                                    self.explorer_jump = Some(handle);
+                                }
+                                if !self.open_frames.contains(&handle) && row.small_button("Frame").clicked() {
+                                    self.open_frames.push(handle);
+                                }
+                                if row.small_button("Details").clicked() {
+                                    match signal {
+                                        Some(s) => self.described_signal = Some(s.clone()),
+                                        None => self.described_message = Some(msg.clone()),
+                                    }
+                                }
+                            });
+                        }
+                    });
+                    ui.separator();
+                }
+                let jump = self.explorer_jump.take();
+                let jump_ecu = jump.and_then(|h| dbc.transmitter(h)).map(|e| e.name.clone());
                 ScrollArea::new([true, true]).show(ui, |scroll| {
-                    scroll.collapsing(&self.dbc_name, |dbc_content| {
-                        for (ecu_idx, ecu) in dbc.ecus.iter().enumerate() {
-                            dbc_content.collapsing(format!("ECU {}", ecu.name), |ecu_ui| {
-                                for (msg_idx, msg) in ecu.messages.iter().enumerate() {
-                                    ecu_ui.collapsing(format!("Msg {} (0x{:04X})", msg.name, msg.id), |msg_ui| {
-
-                                        if self.open_frames.contains(&(ecu_idx, msg_idx)) {
-                                            if msg_ui.button("Hide Frame").clicked() {
-                                                self.open_frames.retain(|(e, m)| *e != ecu_idx && *m != msg_idx)
-                                            }
-                                        } else if msg_ui.button("Show Frame").clicked() {
-                                            self.open_frames.push((ecu_idx, msg_idx));
+                    CollapsingHeader::new(&self.dbc_name).open(jump.map(|_| true)).show(scroll, |dbc_content| {
+                        for ecu in &dbc.ecus {
+                            let open_ecu = jump_ecu.as_ref().filter(|n| **n == ecu.name).map(|_| true);
+                            CollapsingHeader::new(format!("ECU {}", ecu.name)).open(open_ecu).show(dbc_content, |ecu_ui| {
+                                for msg in &ecu.messages {
+                                    let handle = match dbc.message_handle(msg) {
+                                        Some(h) => h,
+                                        None => continue,
+                                    };
+                                    let is_jump = jump == Some(handle);
+                                    let mut title = format!("Msg {} ({})", msg.name, msg.id);
+                                    if self.multiple_dbcs {
+                                        if let Some(src) = self.dbc_sources.get(&handle) {
+                                            write!(title, " [{}]", file_name(src)).unwrap();
                                         }
+                                    }
+                                    let header = CollapsingHeader::new(title)
+                                        .open(is_jump.then_some(true))
+                                        .show(ecu_ui, |msg_ui| {
+
+                                            if self.open_frames.contains(&handle) {
+                                                if msg_ui.button("Hide Frame").clicked() {
+                                                    self.open_frames.retain(|h| *h != handle)
+                                                }
+                                            } else if msg_ui.button("Show Frame").clicked() {
+                                                self.open_frames.push(handle);
+                                            }
+                                            if msg_ui.button("Details").clicked() {
+                                                self.described_message = Some(msg.clone())
+                                            }
 
-                                        for signal in &msg.signals {
-                                            if msg_ui.selectable_label(false, &signal.name).clicked() {
-                                                self.described_signal = Some(signal.clone())
+                                            for signal in &msg.signals {
+                                                if msg_ui.selectable_label(false, &signal.name).clicked() {
+                                                    self.described_signal = Some(signal.clone())
+                                                }
                                             }
+                                        });
+                                    if is_jump {
+                                        header.header_response.scroll_to_me(Some(Align::TOP));
+                                    }
+                                }
+                                let received = dbc.received_messages(&ecu.name);
+                                if !received.is_empty() {
+                                    ecu_ui.collapsing("Received messages", |rx_ui| {
+                                        for msg in received {
+                                            rx_ui.label(format!("Msg {} ({})", msg.name, msg.id));
                                         }
                                     });
                                 }
@@ -131,12 +366,41 @@ impl eframe::App for CanViewer {
 
             } else {
                 ui.label("No DBC loaded");
-                if let Some(err) = &self.load_error {
-                    ui.label(format!("DBC Load error: {}", err));
-                }
+            }
+            for err in &self.load_errors {
+                ui.label(RichText::new("DBC Load error").color(Color32::RED));
+                // Monospace so the marker lines up with the error in the snippet
+                ui.label(RichText::new(err.to_string()).monospace());
             }
         });
 
+        if self.show_lint {
+            SidePanel::left("LintBar").show(ctx, |ui| {
+                ui.heading("DBC Lint");
+                ui.separator();
+                if self.lint.is_empty() {
+                    ui.label("No problems found");
+                }
+                ScrollArea::new([true, true]).show(ui, |scroll| {
+                    for d in &self.lint {
+                        let colour = match d.severity {
+                            Severity::Error => Color32::RED,
+                            Severity::Warning => Color32::from_rgb(255, 165, 0),
+                            Severity::Info => scroll.visuals().text_color(),
+                        };
+                        let r = scroll.selectable_label(false, RichText::new(d.to_string()).color(colour))
+                            .on_hover_text(&d.explanation);
+                        if r.clicked() {
+                            if let (Some(dbc), Some(id), Some(sig_name)) = (&self.dbc, d.message_id, &d.signal) {
+                                let handle = SignalHandle { message: MessageHandle::from(id), signal: sig_name.clone() };
+                                self.described_signal = dbc.signal(&handle).cloned();
+                            }
+                        }
+                    }
+                });
+            });
+        }
+
         // Status bottom bar
         TopBottomPanel::bottom("Statusbar").show(ctx, |ui| {
             widgets::global_dark_light_mode_buttons(ui);
@@ -145,49 +409,34 @@ impl eframe::App for CanViewer {
         CentralPanel::default().show(ctx, |cui| {
             // Now show all the CAN Frames!
             if let Some(dbc) = &self.dbc {
-                for (ecu_idx, msg_idx) in &self.open_frames {
-                    let msg = &dbc.ecus[*ecu_idx].messages[*msg_idx];
-                    containers::Window::new(format!("Frame {} (ID 0x{:04X})", msg.name, msg.id)).show(cui.ctx(), |ui| {
-                        if let Some(cf) = self.frames.read().get(&msg.id) {
-                            ui.label(format!("{:02X?}", cf.get_data()));
-                            
-
-                            let table = TableBuilder::new(ui)
-                                .striped(true)
-                                .scroll(true)
-                                .clip(false)
-                                .cell_layout(Layout::left_to_right(Align::Center).with_cross_align(Align::Center))
-                                .column(Size::initial(60.0).at_least(60.0)) // Value name
-                                .column(Size::initial(400.0).at_least(500.0)); // Value
-
-                            table.header(15.0, |mut header| {
-                                header.col(|u| {u.label("Signal name");});
-                                header.col(|u| {u.label("Value");});
-                            }).body(|body| {
-                                body.rows(18.0, msg.signals.len(), |row_id, mut row| {
-                                    let signal = &msg.signals[row_id];
-                                    row.col(|x| {
-                                        let r = x.label(&signal.name);
-                                        if let Some(cmt) = &signal.comment {
-                                            r.on_hover_text(cmt);
-                                        }
-                                    });
-                                    row.col(|x| {
-                                        match parse_signal(signal, cf.get_data()) {
-                                            Ok(s) => {
-                                                x.label(s.to_string());
-                                            },
-                                            Err(e) => {
-                                                x.label(RichText::new(format!("{:?}", e)).color(Color32::RED));
-                                            }
-                                        }
-                                    });
-
-                                })
-                            });
+                for msg in self.open_frames.iter().filter_map(|h| dbc.message(*h)) {
+                    containers::Window::new(format!("Frame {} (ID {})", msg.name, msg.id)).show(cui.ctx(), |ui| {
+                        let frames = self.frames.read();
+                        let j1939 = dbc.is_j1939(msg);
+                        // J1939 messages match frames of any source address and priority
+                        let mut instances: Vec<(&CanId, &Frame)> = if j1939 {
+                            frames.iter().filter(|(id, _)| dbc.message_for_frame(**id).is_some_and(|m| m.id == msg.id)).collect()
                         } else {
+                            frames.get_key_value(&msg.id).into_iter().collect()
+                        };
+                        instances.sort_by_key(|(id, _)| **id);
+                        if instances.is_empty() {
                             ui.label("No CAN data for this frame.");
                         }
+                        for (id, cf) in instances {
+                            match J1939Id::from_can_id(*id).filter(|_| j1939) {
+                                Some(j) => {
+                                    CollapsingHeader::new(format!("Source address 0x{:02X}", j.source_address))
+                                        .id_source(id)
+                                        .default_open(true)
+                                        .show(ui, |ui| {
+                                            ui.label(j.to_string());
+                                            ui.push_id(id, |ui| self.show_frame(ui, dbc, msg, cf));
+                                        });
+                                },
+                                None => self.show_frame(ui, dbc, msg, cf),
+                            }
+                        }
                     });
                 }
             }
@@ -198,62 +447,70 @@ impl eframe::App for CanViewer {
                                 .scroll(true)
                                 .clip(false)
                                 .cell_layout(Layout::left_to_right(Align::Center).with_cross_align(Align::Center))
-                                .column(Size::initial(60.0).at_least(60.0)) // CAN ID
-                                .column(Size::initial(30.0).at_least(30.0)) //1st byte
-                                .column(Size::initial(30.0).at_least(30.0)) //2nd byte
-                                .column(Size::initial(30.0).at_least(30.0)) //3rd byte
-                                .column(Size::initial(30.0).at_least(30.0)) //4th byte
-                                .column(Size::initial(30.0).at_least(30.0)) //5th byte
-                                .column(Size::initial(30.0).at_least(30.0)) //6th byte
-                                .column(Size::initial(30.0).at_least(30.0)) //7th byte
-                                .column(Size::initial(30.0).at_least(30.0)) //8th byte
+                                .column(Size::initial(110.0).at_least(110.0)) // CAN ID
+                                .column(Size::initial(50.0).at_least(50.0)) // Frame format
+                                .columns(Size::initial(30.0).at_least(30.0), BYTES_PER_LINE) // Data bytes
                                 .column(Size::initial(100.0).at_least(100.0)); // ASCII
 
                             table.header(15.0, |mut header| {
                                 header.col(|u| {u.label("CAN ID");});
-                                header.col(|u| {u.label("");});
-                                header.col(|u| {u.label("");});
-                                header.col(|u| {u.label("");});
-                                header.col(|u| {u.label("");});
-                                header.col(|u| {u.label("");});
-                                header.col(|u| {u.label("");});
-                                header.col(|u| {u.label("");});
-                                header.col(|u| {u.label("");});
+                                header.col(|u| {u.label("Type");});
+                                for _ in 0..BYTES_PER_LINE {
+                                    header.col(|u| {u.label("");});
+                                }
                                 header.col(|u| {u.label("ASCII");});
                             }).body(|body| {
-                                let mut map_clone: Vec<CanFrame> = self.frames.read().values().cloned().collect();
-                                map_clone.sort_by(|c, n| { c.get_address().cmp(&n.get_address()) });
-                                body.rows(18.0, map_clone.len(), |r, mut row| {
-                                    let frame = map_clone[r];
-                                    row.col(|u| {u.label(format!("0x{:04X}", frame.get_address()));});
+                                let mut map_clone: Vec<(CanId, Frame)> = self.frames.read().iter().map(|(id, f)| (*id, *f)).collect();
+                                map_clone.sort_by(|c, n| { c.0.cmp(&n.0) });
+                                // CAN FD payloads are wrapped, BYTES_PER_LINE bytes per line
+                                let heights: Vec<f32> = map_clone.iter()
+                                    .map(|(_, f)| 18.0 * f.data().len().div_ceil(BYTES_PER_LINE).max(1) as f32)
+                                    .collect();
+                                body.heterogeneous_rows(heights.into_iter(), |r, mut row| {
+                                    let (id, frame) = map_clone[r];
+                                    row.col(|u| {
+                                        let r = u.label(id.to_string());
+                                        let msg = self.dbc.as_ref().and_then(|dbc| dbc.message_for_frame(id).map(|m| (dbc, m)));
+                                        match msg {
+                                            Some((dbc, m)) => match J1939Id::from_can_id(id).filter(|_| dbc.is_j1939(m)) {
+                                                Some(j) => r.on_hover_text(format!("{}\n{}", m.name, j)),
+                                                None => r.on_hover_text(&m.name),
+                                            },
+                                            None => r,
+                                        };
+                                    });
+                                    row.col(|u| {u.label(frame.format());});
 
-                                    let mut ascii = String::new();
-                                    for idx in 0..8 {
-                                        match frame.get_data().get(idx) {
-                                            Some(byte) => {
-                                                row.col(|u| {
+                                    let data = frame.data();
+                                    let previous = self.frames_previous.get(&id).map(|f| f.data());
+                                    for column in 0..BYTES_PER_LINE {
+                                        row.col(|u| {
+                                            u.vertical(|u| {
+                                                for idx in (column..data.len()).step_by(BYTES_PER_LINE) {
+                                                    let byte = data[idx];
                                                     let mut l = RichText::new(format!("{:02X}", byte));
-                                                    if let Some(of) = self.frames_previous.get(&frame.get_address()) {
-                                                        if of.get_data()[idx] > *byte {
-                                                            l = l.color(Color32::RED);
-                                                        } else if of.get_data()[idx] < *byte {
-                                                            l = l.color(Color32::BLUE);
-                                                        }
+                                                    match previous.and_then(|p| p.get(idx)) {
+                                                        Some(old) if *old > byte => l = l.color(Color32::RED),
+                                                        Some(old) if *old < byte => l = l.color(Color32::BLUE),
+                                                        _ => {}
                                                     }
                                                     u.label(l);
-                                                });
-                                                if byte.is_ascii_graphic() {
-                                                    write!(ascii, "{}", String::from_utf8_lossy(&[*byte])).unwrap();
-                                                } else {
-                                                    ascii.push('.');
                                                 }
-                                            },
-                                            None => {
-                                                row.col(|_| {});
-                                            }
+                                            });
+                                        });
+                                    }
+                                    let mut ascii = String::new();
+                                    for (idx, byte) in data.iter().enumerate() {
+                                        if idx > 0 && idx % BYTES_PER_LINE == 0 {
+                                            ascii.push('\n');
+                                        }
+                                        if byte.is_ascii_graphic() {
+                                            write!(ascii, "{}", String::from_utf8_lossy(&[*byte])).unwrap();
+                                        } else {
+                                            ascii.push('.');
                                         }
                                     }
-                                    self.frames_previous.insert(frame.get_address(), frame);
+                                    self.frames_previous.insert(id, frame);
                                     // ASCII row
                                     row.col(|x| {x.label(ascii);});
                                 })
@@ -274,8 +531,36 @@ impl eframe::App for CanViewer {
                         ui.heading("Signal bit data");
                         ui.label(format!("Bit offset: {}", signal.start_bit));
                         ui.label(format!("Length bits: {}", signal.length_bits));
-                        ui.label(format!("Signed data: {}", signal.signed));
+                        match signal.value_type {
+                            ValueType::Integer => {
+                                ui.label(format!("Signed data: {}", signal.signed));
+                            },
+                            ValueType::Float32 => {
+                                ui.label("Value type: IEEE float (32 bit)");
+                            },
+                            ValueType::Float64 => {
+                                ui.label("Value type: IEEE double (64 bit)");
+                            },
+                        }
                         ui.label(format!("Byte order: {:?}", signal.order));
+                        if let Some(spn) = self.dbc.as_ref().and_then(|dbc| dbc.spn(signal)) {
+                            ui.label(format!("J1939 SPN: {}", spn));
+                        }
+                        if !signal.receivers.is_empty() {
+                            ui.label(format!("Receivers: {}", signal.receivers.join(", ")));
+                        }
+                        match &signal.multiplexing {
+                            Multiplexing::Plain => {},
+                            Multiplexing::Multiplexor => {
+                                ui.label("Multiplexor switch");
+                            },
+                            Multiplexing::Multiplexed(c) => {
+                                ui.label(format!("Multiplexed: present when {}", c));
+                            },
+                            Multiplexing::MultiplexorAndMultiplexed(c) => {
+                                ui.label(format!("Multiplexor switch, present when {}", c));
+                            },
+                        }
                         ui.add_space(2.0);
                         ui.heading("Data representation");
                         match &signal.signal_type {
@@ -288,20 +573,84 @@ impl eframe::App for CanViewer {
                                 ui.label(format!("  Multiplier: {}", multi));
                                 ui.label(format!("  Offset: {}", offset));
                             },
-                            SignalType::Enum(list) => {
-                                ui.label("Enumeration");
+                            SignalType::Enum { entries: list, multi, offset } => {
+                                match &signal.value_table {
+                                    Some(table) => ui.label(format!("Enumeration (value table {})", table)),
+                                    None => ui.label("Enumeration"),
+                                };
                                 ui.separator();
+                                if *multi != 1.0 || *offset != 0.0 {
+                                    ui.label(format!("  Multiplier: {}", multi));
+                                    ui.label(format!("  Offset: {}", offset));
+                                }
                                 for e in list {
                                     ui.label(format!("{} - {}", e.0, e.1));
                                 }
                             },
                         }
+                        if let Some(dbc) = &self.dbc {
+                            let attrs = dbc.all_attributes(AttributeObject::Signal, &signal.attributes);
+                            if !attrs.is_empty() {
+                                ui.add_space(2.0);
+                                ui.heading("Attributes");
+                                for (name, value) in attrs {
+                                    ui.label(format!("{}: {}", name, value));
+                                }
+                            }
+                        }
                     });
             }
             if !win_open { // Window was closed
                 self.described_signal = None;       
             }
 
+            let mut msg_win_open = true;
+            if let (Some(msg), Some(dbc)) = (&self.described_message, &self.dbc) {
+                containers::Window::new(format!("Message details ({})", msg.name))
+                    .open(&mut msg_win_open)
+                    .show(cui.ctx(), |ui| {
+                        if let Some(d) = &msg.comment {
+                            ui.heading("Message description");
+                            ui.label(d);
+                        }
+                        ui.add_space(2.0);
+                        ui.heading("Frame data");
+                        ui.label(format!("ID: {}", msg.id));
+                        if let Some(j) = dbc.j1939_id(msg) {
+                            ui.label(format!("J1939 PGN: {} (0x{:05X}), matched from any source address", j.pgn, j.pgn));
+                        }
+                        ui.label(format!("Length bytes: {}", msg.length_bytes));
+                        ui.label(format!("Signals: {}", msg.signals.len()));
+                        if let Some(src) = dbc.message_handle(msg).and_then(|h| self.dbc_sources.get(&h)) {
+                            ui.label(format!("Defined in: {}", src));
+                        }
+                        let receivers = msg.receivers();
+                        if !receivers.is_empty() {
+                            ui.label(format!("Receivers: {}", receivers.join(", ")));
+                        }
+                        if let Some(f) = dbc.frame_format(msg) {
+                            ui.label(format!("Frame format: {}", f));
+                        }
+                        if let Some(t) = dbc.send_type(msg) {
+                            ui.label(format!("Send type: {}", t));
+                        }
+                        if let Some(c) = dbc.cycle_time_ms(msg) {
+                            ui.label(format!("Cycle time: {}ms", c));
+                        }
+                        let attrs = dbc.all_attributes(AttributeObject::Message, &msg.attributes);
+                        if !attrs.is_empty() {
+                            ui.add_space(2.0);
+                            ui.heading("Attributes");
+                            for (name, value) in attrs {
+                                ui.label(format!("{}: {}", name, value));
+                            }
+                        }
+                    });
+            }
+            if !msg_win_open {
+                self.described_message = None;
+            }
+
             ctx.request_repaint();
         });
 
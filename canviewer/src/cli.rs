@@ -0,0 +1,165 @@
+use backend::{decode_message, generate_rust, lint_dbc, load_database_from_file, tree_dbc::CanId, J1939Id, Severity};
+use clap::Subcommand;
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum Command {
+    /// Check a DBC file for consistency problems
+    Lint {
+        /// DBC File to check (ARXML, KCD and SYM files are converted first)
+        dbc_file: String,
+        /// ARXML cluster or KCD bus to check
+        #[arg(long)]
+        network: Option<String>
+    },
+    /// Generate Rust structs with decode / encode functions for every message
+    Codegen {
+        /// DBC File to generate code for (ARXML, KCD and SYM files are converted first)
+        dbc_file: String,
+        /// ARXML cluster or KCD bus to generate code for
+        #[arg(long)]
+        network: Option<String>,
+        /// File to write the code to, instead of stdout
+        #[arg(short, long)]
+        output: Option<String>
+    },
+    /// Decode a single frame and print its signals
+    Decode {
+        /// DBC File describing the frame
+        dbc_file: String,
+        /// CAN ID of the frame, decimal or hex with a 0x prefix
+        id: String,
+        /// Payload as hex bytes, E.g. 01A2FF or "01 A2 FF"
+        data: String,
+        /// Frame uses a 29 bit extended ID. Implied for IDs above 0x7FF
+        #[arg(short, long)]
+        extended: bool,
+        /// ARXML cluster or KCD bus describing the frame
+        #[arg(long)]
+        network: Option<String>
+    }
+}
+
+/// Runs a command line subcommand, returning the process exit code
+pub fn run(command: Command) -> i32 {
+    match command {
+        Command::Lint { dbc_file, network } => lint(&dbc_file, network.as_deref()),
+        Command::Codegen { dbc_file, network, output } => codegen(&dbc_file, network.as_deref(), output.as_deref()),
+        Command::Decode { dbc_file, id, data, extended, network } => decode(&dbc_file, &id, &data, extended, network.as_deref()),
+    }
+}
+
+fn lint(path: &str, network: Option<&str>) -> i32 {
+    let dbc = match load_database_from_file(path, network) {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2
+        }
+    };
+    let diagnostics = lint_dbc(&dbc);
+    for d in &diagnostics {
+        println!("{}", d);
+        println!("    {}", d.explanation);
+    }
+    let errors = diagnostics.iter().filter(|d| d.severity == Severity::Error).count();
+    println!("{}: {} problem(s), {} error(s)", path, diagnostics.len(), errors);
+    if errors > 0 {
+        1
+    } else {
+        0
+    }
+}
+
+fn codegen(path: &str, network: Option<&str>, output: Option<&str>) -> i32 {
+    let code = match load_database_from_file(path, network).and_then(|dbc| generate_rust(&dbc)) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2
+        }
+    };
+    match output {
+        Some(out) => match std::fs::write(out, code) {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("Cannot write {}: {}", out, e);
+                2
+            }
+        },
+        None => {
+            print!("{}", code);
+            0
+        }
+    }
+}
+
+fn parse_id(id: &str) -> Option<u32> {
+    match id.strip_prefix("0x").or_else(|| id.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => id.parse().ok(),
+    }
+}
+
+fn parse_hex(data: &str) -> Option<Vec<u8>> {
+    let digits: Vec<char> = data.chars().filter(|c| !c.is_whitespace()).collect();
+    digits.chunks(2).map(|pair| match pair {
+        [high, low] => Some((high.to_digit(16)? * 16 + low.to_digit(16)?) as u8),
+        _ => None,
+    }).collect()
+}
+
+fn decode(path: &str, id: &str, data: &str, extended: bool, network: Option<&str>) -> i32 {
+    let (raw_id, payload) = match (parse_id(id), parse_hex(data)) {
+        (Some(i), Some(d)) => (i, d),
+        (None, _) => {
+            eprintln!("Invalid CAN ID {}", id);
+            return 2
+        },
+        (_, None) => {
+            eprintln!("Invalid payload {}, expected hex bytes", data);
+            return 2
+        }
+    };
+    let dbc = match load_database_from_file(path, network) {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2
+        }
+    };
+    let can_id = CanId::new(raw_id, extended || raw_id > 0x7FF);
+    let msg = match dbc.message_for_frame(can_id) {
+        Some(m) => m,
+        None => {
+            eprintln!("{} has no message with ID {}", path, can_id);
+            return 2
+        }
+    };
+    let decoded = decode_message(msg, &payload);
+    println!("{} (ID {})", msg.name, msg.id);
+    if let Some(j) = J1939Id::from_can_id(can_id).filter(|_| dbc.is_j1939(msg)) {
+        println!("J1939 {}", j);
+    }
+    if decoded.length_mismatch() {
+        println!("Expected {} bytes, received {}", decoded.expected_len, decoded.payload_len);
+    }
+    if !decoded.mux_chain.is_empty() {
+        let groups: Vec<String> = decoded.mux_chain.iter().map(|(mux, v)| format!("{} = {}", msg.signals[*mux].name, v)).collect();
+        println!("Mux group: {}", groups.join(" -> "));
+    } else if decoded.multiplexed {
+        println!("Cannot read multiplexor");
+    }
+    for signal in &decoded.signals {
+        let name = &msg.signals[signal.index].name;
+        match &signal.value {
+            Ok(p) if p.is_valid() => println!("    {} = {}", name, p),
+            Ok(p) => println!("    {} = {} ({})", name, p, p.status),
+            Err(e) => println!("    {}: {:?}", name, e),
+        }
+    }
+    if decoded.errors().next().is_some() {
+        1
+    } else {
+        0
+    }
+}
@@ -1,21 +1,35 @@
 use canviewer::CanViewer;
 use clap::{Parser};
+mod can_socket;
 mod canviewer;
+mod cli;
 mod error_page;
 use eframe::{NativeOptions, IconData, epaint::Vec2};
 
 use error_page::ErrorPage;
 
 #[derive(Debug, Parser, Clone)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct CanViewerSettings {
+    #[command(subcommand)]
+    command: Option<cli::Command>,
     /// Socket CAN Interface name to connect to
-    socketcan_iface: String,
-    /// Optional DBC File to load
-    dbc_file: Option<String>
+    #[arg(required = true)]
+    socketcan_iface: Option<String>,
+    /// Optional DBC files to load. Files are merged, with the first file
+    /// taking priority when two define the same CAN ID. ARXML, KCD and SYM
+    /// files are loaded based on their file extension
+    dbc_files: Vec<String>,
+    /// ARXML cluster or KCD bus to load, for files describing multiple networks
+    #[arg(long)]
+    network: Option<String>
 }
 
 fn main() {
     let args = CanViewerSettings::parse();
+    if let Some(command) = args.command {
+        std::process::exit(cli::run(command));
+    }
 
     let icon = image::load_from_memory(include_bytes!("../logo.png")).unwrap().to_rgba8();
     let (icon_w, icon_h) = icon.dimensions();
@@ -37,9 +51,9 @@ fn main() {
     {
         native_options.renderer = Renderer::Wgpu;
     }
-    let c = args.socketcan_iface.clone();
+    let c = args.socketcan_iface.clone().unwrap_or_default();
     eframe::run_native("CanViewerRS", native_options, Box::new(|_| {
-        match CanViewer::new(c, args.dbc_file) {
+        match CanViewer::new(c, args.dbc_files, args.network) {
             Ok(viewer) => {
                 Box::new(viewer)
             },